@@ -1,22 +1,52 @@
-use std::{
-    collections::BTreeMap,
-    sync::{Arc, RwLock},
+use std::sync::{
+    Arc,
+    atomic::{AtomicU32, AtomicUsize, Ordering},
+    mpsc::{self, Receiver, Sender, SyncSender},
 };
 
 use serde::{Deserialize, Serialize};
 
-use super::synthesizer::{WaveForm, WaveTable, WaveTableOscillator};
+use super::{
+    envelope::Envelope,
+    graph::{CompiledGraph, Graph, GraphError, Voice},
+    scope::{ScopeBuffer, ScopeSnapshot},
+    smoothing::Smoothing,
+    synthesizer::{WaveForm, WaveTable, WaveTableOscillator},
+    transport::{Transport, TransportEvent, TransportStep},
+};
+
+/// The amount of channels the engine produces samples for, which are interleaved as left and right
+pub const CHANNELS: u16 = 2;
+
+/// The shared transport step while no timeline is playing
+const NO_STEP: usize = usize::MAX;
+
+/// The shared scope voice while only the mixed output is shown
+const NO_VOICE: usize = usize::MAX;
+
+/// The amount of values which can wait to be freed by the drop thread, beyond which they are freed in place
+const DROP_QUEUE_SIZE: usize = 1024;
 
-/// A struct representing an audio engine, providing an api for things like creating, updating and deleting oscillators
+/// A struct representing an audio engine, producing the samples played on the audio thread.
+/// It is controlled through the [`EngineControl`] api, either directly or from another thread through an [`EngineController`]
 pub struct AudioEngine {
-    sample_rate: usize,
     wavetable: WaveTable,
-    oscillators: BTreeMap<usize, WaveTableOscillator>,
-    _time: f32, // eventually used in the future for syncing oscillators
-    volume_multiple: f32,
-    volume: Volume,
-    latestid: usize,
+    smoothing: Smoothing,
+    /// Whether every oscillator restarts at phase zero when the engine starts playing
+    phase_reset: bool,
+    /// The graph playing the oscillators, which owns them
+    graph: CompiledGraph,
     is_playing: bool,
+    /// The timeline being played, if any
+    transport: Option<Transport>,
+    /// The number of the timeline being played, or of the last one which was
+    timeline: usize,
+    /// The right sample of the current frame, produced together with the left one
+    right_sample: Option<f32>,
+    shared: Arc<EngineShared>,
+    commands: Receiver<EngineCommand>,
+    command_sender: Sender<EngineCommand>,
+    drop_queue: DropQueue,
     // // DEBUG: used for measuring if the sample_rate is correct
     // last_sample_time: std::time::Instant,
     // last_sample_index: usize, // mod 48000
@@ -26,111 +56,373 @@ pub struct AudioEngine {
 impl AudioEngine {
     pub fn new(sample_rate: usize) -> Self {
         let volume = Volume::new(-4.0);
+        let (command_sender, commands) = mpsc::channel();
         Self {
-            sample_rate,
             wavetable: WaveTable::default(),
-            oscillators: BTreeMap::new(),
-            _time: 0.0,
-            volume_multiple: volume.multiple(),
-            volume,
-            latestid: 0,
+            smoothing: Smoothing::default(),
+            phase_reset: false,
+            graph: Graph::default()
+                .compile(sample_rate)
+                .expect("the default graph has a single output"),
             is_playing: false,
+            transport: None,
+            timeline: 0,
+            right_sample: None,
+            shared: Arc::new(EngineShared {
+                sample_rate,
+                volume: AtomicF32::new(volume.get()),
+                volume_multiple: AtomicF32::new(volume.multiple()),
+                latestid: AtomicUsize::new(0),
+                transport_step: AtomicUsize::new(NO_STEP),
+                timelines_sent: AtomicUsize::new(0),
+                timeline_stopped: AtomicUsize::new(0),
+                scope: ScopeBuffer::default(),
+                scope_voice: AtomicUsize::new(NO_VOICE),
+            }),
+            commands,
+            command_sender,
+            drop_queue: DropQueue::spawn(),
             // last_sample_time: std::time::Instant::now(),
             // last_sample_index: 0,
             // last_sample_durations: [1.0; 48000],
         }
     }
 
-    /// Reset the volume, wavetable and oscillators to their default values
+    /// Create a controller for the engine, used to control it after it has been moved to the audio thread
+    pub fn controller(&self) -> EngineController {
+        EngineController {
+            sender: self.command_sender.clone(),
+            shared: self.shared.clone(),
+        }
+    }
+
+    /// Get all active oscillators with their ids, in the order they were added
+    pub fn get_oscillators(&self) -> impl Iterator<Item = (usize, &WaveTableOscillator)> {
+        self.graph
+            .voices()
+            .playing
+            .iter()
+            .map(|voice| (voice.id, &voice.oscillator))
+    }
+
+    /// Reset the volume, wavetable and phase reset to their default values
     pub fn reset(&mut self) {
-        let volume = Volume::new(-4.0);
-        self.volume = volume;
-        self.volume_multiple = volume.multiple();
-        self.wavetable = WaveTable::default();
-        self._time = 0.0;
+        EngineControl::reset(self);
     }
 
-    /// Make the audio engine play
+    /// Make the audio engine play, starting the attack of every oscillator
     pub fn play(&mut self) {
-        self.is_playing = true;
+        EngineControl::play(self);
     }
 
-    /// Make the audio engine stop playing
+    /// Make the audio engine stop playing, starting the release of every oscillator
     pub fn stop(&mut self) {
-        self.is_playing = false;
+        EngineControl::stop(self);
     }
 
     /// Get the current volume
     pub fn get_volume(&self) -> Volume {
-        self.volume
+        EngineControl::get_volume(self)
     }
 
     /// new_volume should be a base 2 gain
     pub fn set_volume(&mut self, new_volume: Volume) {
-        self.volume_multiple = new_volume.multiple();
-        self.volume = new_volume;
-        dbg!("Volume set: ", new_volume,);
+        EngineControl::set_volume(self, new_volume);
     }
 
-    /// Adds an oscillator to the engine, and returns the id
+    /// Adds an oscillator to the engine, and returns the id. If the engine is playing, the oscillator starts its attack.
+    /// The oscillator plays the project waveform unless it is given its own
     pub fn add_oscillator(
         &mut self,
         frequency: SharedFrequency,
         volume_multiplier: SharedVolumeMultiplier,
+        envelope: Envelope,
+        waveform: Option<WaveForm>,
     ) -> usize {
-        let id = self.latestid;
-        self.latestid += 1;
+        EngineControl::add_oscillator(self, frequency, volume_multiplier, envelope, waveform)
+    }
 
-        self.oscillators.insert(
-            id,
-            WaveTableOscillator::new(
-                self.sample_rate,
+    /// Remove an oscillator from the engine by its id if it exists, letting it finish its release first
+    pub fn remove_oscillator(&mut self, id: &usize) {
+        EngineControl::remove_oscillator(self, id);
+    }
+
+    /// Remove all oscillators from the engine, letting them finish their release first
+    pub fn clear_oscillators(&mut self) {
+        EngineControl::clear_oscillators(self);
+    }
+
+    /// Set the project waveform, played by every oscillator without its own
+    pub fn set_waveform(&mut self, waveform: WaveForm) {
+        EngineControl::set_waveform(self, waveform);
+    }
+
+    fn voice_mut(&mut self, id: usize) -> Option<&mut Voice> {
+        self.graph.voices_mut().get_mut(id)
+    }
+
+    fn apply(&mut self, command: EngineCommand) {
+        match command {
+            EngineCommand::AddOscillator {
+                id,
                 frequency,
                 volume_multiplier,
-                self.wavetable.clone(),
-            ),
-        );
-        id
+                envelope,
+                wavetable,
+            } => {
+                let has_own_wavetable = wavetable.is_some();
+                let mut oscillator = WaveTableOscillator::new(
+                    self.shared.sample_rate,
+                    frequency,
+                    volume_multiplier,
+                    wavetable.unwrap_or_else(|| self.wavetable.clone()),
+                    envelope,
+                    self.smoothing,
+                );
+                if self.is_playing {
+                    oscillator.note_on();
+                }
+                let voice = Voice {
+                    id,
+                    oscillator,
+                    has_own_wavetable,
+                };
+                match self.voice_mut(id) {
+                    Some(existing) => {
+                        let replaced = std::mem::replace(existing, voice);
+                        self.drop_queue
+                            .free(Garbage::Oscillator(replaced.oscillator));
+                    }
+                    // only reallocates with more oscillators than there is room for
+                    None => self.graph.voices_mut().playing.push(voice),
+                }
+            }
+            EngineCommand::RemoveOscillator(id) => {
+                let playing = &mut self.graph.voices_mut().playing;
+                if let Some(index) = playing.iter().position(|voice| voice.id == id) {
+                    let voice = playing.remove(index);
+                    self.release(voice.oscillator);
+                }
+            }
+            EngineCommand::ClearOscillators => {
+                while let Some(voice) = self.graph.voices_mut().playing.pop() {
+                    self.release(voice.oscillator);
+                }
+            }
+            EngineCommand::SetOscillatorEnvelope { id, envelope } => {
+                if let Some(voice) = self.voice_mut(id) {
+                    voice.oscillator.set_envelope(envelope);
+                }
+            }
+            EngineCommand::SetOscillatorWaveTable { id, wavetable } => {
+                let Some(voice) = self.graph.voices_mut().get_mut(id) else {
+                    return;
+                };
+                voice.has_own_wavetable = wavetable.is_some();
+                let wavetable = wavetable.unwrap_or_else(|| self.wavetable.clone());
+                let replaced = voice.oscillator.set_wavetable(wavetable);
+                self.drop_queue.free(Garbage::WaveTable(replaced));
+            }
+            EngineCommand::SetOscillatorPan { id, pan } => {
+                if let Some(voice) = self.voice_mut(id) {
+                    voice.oscillator.set_pan(pan);
+                }
+            }
+            EngineCommand::SetOscillatorSync { id, ratio } => {
+                if let Some(voice) = self.voice_mut(id) {
+                    voice.oscillator.set_sync(ratio);
+                }
+            }
+            EngineCommand::SetOscillatorGate { id, gate } => {
+                let phase_reset = self.phase_reset;
+                let Some(voice) = self.voice_mut(id) else {
+                    return;
+                };
+                if gate {
+                    // like playing, a silent voice starts in phase if phase reset is on
+                    if phase_reset && voice.oscillator.is_silent() {
+                        voice.oscillator.reset_phase();
+                    }
+                    voice.oscillator.note_on();
+                } else {
+                    voice.oscillator.note_off();
+                }
+            }
+            EngineCommand::SetWaveTable(wavetable) => {
+                for voice in self.graph.voices_mut().playing.iter_mut() {
+                    if !voice.has_own_wavetable {
+                        let replaced = voice.oscillator.set_wavetable(wavetable.clone());
+                        self.drop_queue.free(Garbage::WaveTable(replaced));
+                    }
+                }
+                let replaced = std::mem::replace(&mut self.wavetable, wavetable);
+                self.drop_queue.free(Garbage::WaveTable(replaced));
+            }
+            EngineCommand::SetSmoothing(smoothing) => {
+                for voice in self.graph.voices_mut().playing.iter_mut() {
+                    voice.oscillator.set_smoothing(smoothing);
+                }
+                self.smoothing = smoothing;
+            }
+            EngineCommand::SetPhaseReset(phase_reset) => {
+                self.phase_reset = phase_reset;
+            }
+            EngineCommand::SetGraph(mut graph) => {
+                graph.inherit(&mut self.graph);
+                let replaced = std::mem::replace(&mut self.graph, graph);
+                self.drop_queue.free(Garbage::Graph(replaced));
+            }
+            EngineCommand::Play => {
+                self.stop_transport();
+                // oscillators sharing a global frequency start in phase, since they all start at zero
+                let reset_phase = self.phase_reset && !self.is_playing;
+                self.is_playing = true;
+                for voice in self.graph.voices_mut().playing.iter_mut() {
+                    if reset_phase {
+                        voice.oscillator.reset_phase();
+                    }
+                    voice.oscillator.note_on();
+                }
+            }
+            EngineCommand::Stop => {
+                self.stop_transport();
+                self.is_playing = false;
+                for voice in self.graph.voices_mut().playing.iter_mut() {
+                    voice.oscillator.note_off();
+                }
+            }
+            EngineCommand::PlayTimeline {
+                steps,
+                looping,
+                number,
+            } => {
+                self.stop_transport();
+                // the first step decides which oscillators play
+                for voice in self.graph.voices_mut().playing.iter_mut() {
+                    voice.oscillator.note_off();
+                }
+                self.is_playing = true;
+                self.transport = Some(Transport::new(steps, looping));
+                self.timeline = number;
+            }
+        }
     }
+}
 
-    /// Get a reference to all active oscillators
-    pub fn get_oscillators(&self) -> &BTreeMap<usize, WaveTableOscillator> {
-        &self.oscillators
+impl AudioEngine {
+    /// Let a removed oscillator play its release, or cut it short if too many are releasing already
+    fn release(&mut self, mut oscillator: WaveTableOscillator) {
+        oscillator.note_off();
+        let releasing = &mut self.graph.voices_mut().releasing;
+        if releasing.len() < releasing.capacity() {
+            releasing.push(oscillator);
+        } else {
+            self.drop_queue.free(Garbage::Oscillator(oscillator));
+        }
     }
 
-    /// Remove an oscillator from the engine by its id if it exists
-    pub fn remove_oscillator(&mut self, id: &usize) {
-        self.oscillators.remove(id);
+    /// Free the releasing oscillators which have become silent, without reallocating the buffer of them
+    fn free_silent(&mut self) {
+        let mut index = 0;
+        while index < self.graph.voices().releasing.len() {
+            if self.graph.voices().releasing[index].is_silent() {
+                let oscillator = self.graph.voices_mut().releasing.swap_remove(index);
+                self.drop_queue.free(Garbage::Oscillator(oscillator));
+            } else {
+                index += 1;
+            }
+        }
     }
 
-    /// Remove all oscillators from the engine
-    pub fn clear_oscillators(&mut self) {
-        self.oscillators.clear();
+    /// Stop playing the timeline, if any, freeing its steps on the drop thread
+    fn stop_transport(&mut self) {
+        if let Some(transport) = self.transport.take() {
+            self.drop_queue.free(Garbage::Transport(transport));
+            self.shared
+                .timeline_stopped
+                .store(self.timeline, Ordering::Relaxed);
+        }
+        self.shared.transport_step.store(NO_STEP, Ordering::Relaxed);
     }
 
-    ///// Sets the oscillator with the provided id's frequency
-    //pub fn set_oscillator_frequency(&mut self, id: &usize, frequency: f32) {
-    //    match self.oscillators.get_mut(id) {
-    //        Some(osc) => osc.set_frequency(frequency),
-    //        None => {}
-    //    }
-    //}
-
-    /// Set the waveform
-    pub fn set_waveform(&mut self, waveform: WaveForm) {
-        let new_table = match waveform {
-            WaveForm::Sine => WaveTable::sine(),
-            WaveForm::Triangle => WaveTable::triangle(),
-            WaveForm::Square => WaveTable::square(),
-            WaveForm::Saw => WaveTable::saw(),
+    /// Move the transport forward by a frame, retuning and gating the oscillators when a step starts
+    fn advance_transport(&mut self) {
+        let Some(transport) = &mut self.transport else {
+            return;
         };
-        for osc in self.oscillators.values_mut() {
-            osc.set_wavetable(new_table.clone());
+        match transport.advance() {
+            Some(TransportEvent::StepStarted(index)) => {
+                let Some(step) = transport.step(index) else {
+                    return;
+                };
+                for (frequency, value) in &step.frequencies {
+                    frequency.set(*value);
+                }
+                for Voice { id, oscillator, .. } in self.graph.voices_mut().playing.iter_mut() {
+                    let is_active = step.oscillators.contains(id);
+                    // voices playing in consecutive steps are held instead of restarted
+                    if is_active && !oscillator.is_held() {
+                        if self.phase_reset && oscillator.is_silent() {
+                            oscillator.reset_phase();
+                        }
+                        oscillator.note_on();
+                    } else if !is_active && oscillator.is_held() {
+                        oscillator.note_off();
+                    }
+                }
+                self.shared.transport_step.store(index, Ordering::Relaxed);
+            }
+            Some(TransportEvent::Finished) => {
+                self.stop_transport();
+                self.is_playing = false;
+                for voice in self.graph.voices_mut().playing.iter_mut() {
+                    voice.oscillator.note_off();
+                }
+            }
+            None => {}
         }
-        self.wavetable = new_table;
     }
 }
 
+/// A value the audio thread is done with, which is freed on the drop thread
+// the values are never read, only held until they are dropped
+#[allow(dead_code)]
+enum Garbage {
+    Oscillator(WaveTableOscillator),
+    WaveTable(WaveTable),
+    Transport(Transport),
+    Graph(CompiledGraph),
+}
+
+/// Sends values to a thread which frees them, since freeing memory may wait for a lock in the allocator
+struct DropQueue(SyncSender<Garbage>);
+
+impl DropQueue {
+    /// Start the drop thread, which stops once the queue is dropped
+    fn spawn() -> Self {
+        let (sender, receiver) = mpsc::sync_channel(DROP_QUEUE_SIZE);
+        std::thread::spawn(move || receiver.into_iter().for_each(drop));
+        Self(sender)
+    }
+
+    /// Free a value on the drop thread, or right away if it can't keep up
+    fn free(&self, garbage: Garbage) {
+        // a failed send returns the value in the error, which is dropped here
+        let _ = self.0.try_send(garbage);
+    }
+}
+
+impl EngineControl for AudioEngine {
+    fn send(&mut self, command: EngineCommand) {
+        self.apply(command);
+    }
+
+    fn shared(&self) -> &EngineShared {
+        &self.shared
+    }
+}
+
+/// Produces interleaved stereo samples, starting with the left sample of every frame
 impl Iterator for AudioEngine {
     type Item = f32;
 
@@ -151,19 +443,288 @@ impl Iterator for AudioEngine {
         //     }
         // }
 
-        if !self.is_playing {
-            return Some(0.0);
+        if let Some(right_sample) = self.right_sample.take() {
+            return Some(right_sample);
         }
-        let mut sum = 0.0;
-        for osc in self.oscillators.values_mut() {
-            sum += osc.next().unwrap_or(0.0);
+
+        // never blocks, so the gui thread can't cause dropouts
+        while let Ok(command) = self.commands.try_recv() {
+            self.apply(command);
+        }
+        self.advance_transport();
+
+        let scope_voice = self.shared.scope_voice.load(Ordering::Relaxed);
+        let ([left, right], voice) = self.graph.process(scope_voice);
+        self.free_silent();
+        let volume_multiple = self.shared.volume_multiple.get();
+        self.shared.scope.push(
+            (left + right) * 0.5 * volume_multiple,
+            voice * volume_multiple,
+        );
+        self.right_sample = Some(right * volume_multiple);
+        Some(left * volume_multiple)
+    }
+}
+
+/// A handle for controlling an [`AudioEngine`] running on another thread. Parameters are passed through atomics
+/// and everything else through a command queue, so the audio thread never has to wait for the gui thread
+#[derive(Clone)]
+pub struct EngineController {
+    sender: Sender<EngineCommand>,
+    shared: Arc<EngineShared>,
+}
+
+impl EngineControl for EngineController {
+    fn send(&mut self, command: EngineCommand) {
+        // if the engine has been dropped there is nothing left to control
+        let _ = self.sender.send(command);
+    }
+
+    fn shared(&self) -> &EngineShared {
+        &self.shared
+    }
+}
+
+/// A command for the audio engine, applied before the next sample is produced
+pub enum EngineCommand {
+    AddOscillator {
+        id: usize,
+        frequency: SharedFrequency,
+        volume_multiplier: SharedVolumeMultiplier,
+        envelope: Envelope,
+        /// Overrides the project wavetable if set
+        wavetable: Option<WaveTable>,
+    },
+    RemoveOscillator(usize),
+    ClearOscillators,
+    SetOscillatorEnvelope {
+        id: usize,
+        envelope: Envelope,
+    },
+    /// Override the project wavetable for a single oscillator, or go back to it with None
+    SetOscillatorWaveTable {
+        id: usize,
+        wavetable: Option<WaveTable>,
+    },
+    /// Pan an oscillator between the left channel at -1 and the right channel at 1
+    SetOscillatorPan {
+        id: usize,
+        pan: f32,
+    },
+    /// Hard sync an oscillator to a global frequency, given as the ratio of the oscillator frequency to it,
+    /// or stop syncing it with None
+    SetOscillatorSync {
+        id: usize,
+        ratio: Option<f32>,
+    },
+    /// Start the attack of a single oscillator when the gate opens, or its release when it closes
+    SetOscillatorGate {
+        id: usize,
+        gate: bool,
+    },
+    SetWaveTable(WaveTable),
+    SetSmoothing(Smoothing),
+    SetPhaseReset(bool),
+    /// Replace the graph playing the oscillators, which takes them over with the state of the nodes in both
+    SetGraph(CompiledGraph),
+    Play,
+    Stop,
+    /// Play the steps of a timeline from the start, replacing the playing of every oscillator
+    PlayTimeline {
+        steps: Vec<TransportStep>,
+        looping: bool,
+        /// The number the timeline was sent with, which counts the timelines sent to the engine
+        number: usize,
+    },
+}
+
+/// State shared between an engine and its controllers, only accessed through atomics
+pub struct EngineShared {
+    sample_rate: usize,
+    volume: AtomicF32,
+    volume_multiple: AtomicF32,
+    latestid: AtomicUsize,
+    /// The index of the timeline step being played, or [NO_STEP]
+    transport_step: AtomicUsize,
+    /// The number of timelines sent to the engine, which is also the number of the latest one
+    timelines_sent: AtomicUsize,
+    /// The number of the last timeline which finished or was stopped
+    timeline_stopped: AtomicUsize,
+    /// The latest frames played, which the gui draws
+    scope: ScopeBuffer,
+    /// The id of the oscillator the scope shows on its own, or [NO_VOICE]
+    scope_voice: AtomicUsize,
+}
+
+/// The api for creating, updating and deleting oscillators, implemented both by the engine itself
+/// and by [`EngineController`]
+pub trait EngineControl {
+    /// Apply a command to the engine, either directly or by queueing it for the audio thread
+    fn send(&mut self, command: EngineCommand);
+
+    fn shared(&self) -> &EngineShared;
+
+    /// Get the sample rate the engine produces samples at
+    fn sample_rate(&self) -> usize {
+        self.shared().sample_rate
+    }
+
+    /// Reset the volume, wavetable and phase reset to their default values
+    fn reset(&mut self) {
+        self.set_volume(Volume::new(-4.0));
+        // the default wavetable is built here, so it is never built on the audio thread
+        self.send(EngineCommand::SetWaveTable(WaveTable::default()));
+        self.send(EngineCommand::SetPhaseReset(false));
+    }
+
+    /// Make the audio engine play, starting the attack of every oscillator
+    fn play(&mut self) {
+        self.send(EngineCommand::Play);
+    }
+
+    /// Make the audio engine stop playing, starting the release of every oscillator
+    fn stop(&mut self) {
+        self.send(EngineCommand::Stop);
+    }
+
+    /// Make the audio engine play the steps of a timeline, stopping at the end unless it loops
+    fn play_timeline(&mut self, steps: Vec<TransportStep>, looping: bool) {
+        let number = self.shared().timelines_sent.fetch_add(1, Ordering::Relaxed) + 1;
+        self.send(EngineCommand::PlayTimeline {
+            steps,
+            looping,
+            number,
+        });
+    }
+
+    /// Whether the latest timeline sent to the engine has stopped playing, which it does by itself
+    /// at its end unless it loops
+    fn is_timeline_stopped(&self) -> bool {
+        let shared = self.shared();
+        shared.timeline_stopped.load(Ordering::Relaxed)
+            == shared.timelines_sent.load(Ordering::Relaxed)
+    }
+
+    /// Get the index of the timeline step the engine is playing, or None if it isn't playing a timeline
+    fn transport_step(&self) -> Option<usize> {
+        match self.shared().transport_step.load(Ordering::Relaxed) {
+            NO_STEP => None,
+            step => Some(step),
         }
-        Some(sum * self.volume_multiple)
+    }
+
+    /// Copy the latest frames the engine played, mixed to mono, for drawing them
+    fn scope(&self, frames: usize) -> ScopeSnapshot {
+        self.shared().scope.snapshot(frames)
+    }
+
+    /// Set the oscillator with the provided id the scope shows next to the mixed output, or show none with None
+    fn set_scope_voice(&mut self, id: Option<usize>) {
+        self.shared()
+            .scope_voice
+            .store(id.unwrap_or(NO_VOICE), Ordering::Relaxed);
+    }
+
+    /// Get the current volume
+    fn get_volume(&self) -> Volume {
+        Volume::new(self.shared().volume.get())
+    }
+
+    /// new_volume should be a base 2 gain
+    fn set_volume(&mut self, new_volume: Volume) {
+        let shared = self.shared();
+        shared.volume.set(new_volume.get());
+        shared.volume_multiple.set(new_volume.multiple());
+    }
+
+    /// Adds an oscillator to the engine, and returns the id. If the engine is playing, the oscillator starts its attack.
+    /// The oscillator plays the project waveform unless it is given its own
+    fn add_oscillator(
+        &mut self,
+        frequency: SharedFrequency,
+        volume_multiplier: SharedVolumeMultiplier,
+        envelope: Envelope,
+        waveform: Option<WaveForm>,
+    ) -> usize {
+        let id = self.shared().latestid.fetch_add(1, Ordering::Relaxed);
+        self.send(EngineCommand::AddOscillator {
+            id,
+            frequency,
+            volume_multiplier,
+            envelope,
+            wavetable: waveform.map(WaveTable::from_waveform),
+        });
+        id
+    }
+
+    /// Remove an oscillator from the engine by its id if it exists, letting it finish its release first
+    fn remove_oscillator(&mut self, id: &usize) {
+        self.send(EngineCommand::RemoveOscillator(*id));
+    }
+
+    /// Remove all oscillators from the engine, letting them finish their release first
+    fn clear_oscillators(&mut self) {
+        self.send(EngineCommand::ClearOscillators);
+    }
+
+    /// Set the envelope of the oscillator with the provided id if it exists
+    fn set_oscillator_envelope(&mut self, id: &usize, envelope: Envelope) {
+        self.send(EngineCommand::SetOscillatorEnvelope { id: *id, envelope });
+    }
+
+    /// Set the waveform of the oscillator with the provided id if it exists, or make it play the project waveform with None
+    fn set_oscillator_waveform(&mut self, id: &usize, waveform: Option<WaveForm>) {
+        self.send(EngineCommand::SetOscillatorWaveTable {
+            id: *id,
+            wavetable: waveform.map(WaveTable::from_waveform),
+        });
+    }
+
+    /// Set the pan of the oscillator with the provided id if it exists, from -1 for left to 1 for right
+    fn set_oscillator_pan(&mut self, id: &usize, pan: f32) {
+        self.send(EngineCommand::SetOscillatorPan { id: *id, pan });
+    }
+
+    /// Restart the period of the oscillator with the provided id every period of its global frequency,
+    /// where the ratio is the oscillator frequency divided by the global frequency. None stops the syncing
+    fn set_oscillator_sync(&mut self, id: &usize, ratio: Option<f32>) {
+        self.send(EngineCommand::SetOscillatorSync { id: *id, ratio });
+    }
+
+    /// Open or close the gate of the oscillator with the provided id if it exists, playing it on its own
+    fn set_oscillator_gate(&mut self, id: &usize, gate: bool) {
+        self.send(EngineCommand::SetOscillatorGate { id: *id, gate });
+    }
+
+    /// Set whether every oscillator restarts at phase zero when the engine starts playing,
+    /// so voices sharing a global frequency are phase coherent
+    fn set_phase_reset(&mut self, phase_reset: bool) {
+        self.send(EngineCommand::SetPhaseReset(phase_reset));
+    }
+
+    /// Set the graph the voices are played through. The graph is compiled by the caller, so it
+    /// is never compiled on the audio thread
+    fn set_graph(&mut self, graph: &Graph) -> Result<(), GraphError> {
+        let graph = graph.compile(self.sample_rate())?;
+        self.send(EngineCommand::SetGraph(graph));
+        Ok(())
+    }
+
+    /// Set how every oscillator moves to new frequencies and volumes
+    fn set_smoothing(&mut self, smoothing: Smoothing) {
+        self.send(EngineCommand::SetSmoothing(smoothing));
+    }
+
+    /// Set the project waveform, played by every oscillator without its own. The wavetable is built by the caller, so it is never built on the audio thread
+    fn set_waveform(&mut self, waveform: WaveForm) {
+        self.send(EngineCommand::SetWaveTable(WaveTable::from_waveform(
+            waveform,
+        )));
     }
 }
 
 /// A struct representing a volume in base 2 gain. It is always clamped to be less than or equal to zero
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Volume(f32);
 
 impl Volume {
@@ -181,39 +742,56 @@ impl Volume {
     }
 }
 
+/// An f32 which can be shared between threads without locking
+struct AtomicF32(AtomicU32);
+
+impl AtomicF32 {
+    fn new(value: f32) -> Self {
+        Self(AtomicU32::new(value.to_bits()))
+    }
+
+    fn get(&self) -> f32 {
+        f32::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    fn set(&self, value: f32) {
+        self.0.store(value.to_bits(), Ordering::Relaxed);
+    }
+}
+
 /// A frequency shared between the gui thread and the audio thread to not have to put
 /// locks on all data, only what's required
 #[derive(Clone)]
-pub struct SharedFrequency(Arc<RwLock<f32>>);
+pub struct SharedFrequency(Arc<AtomicF32>);
 
 impl SharedFrequency {
     pub fn new(frequency: f32) -> Self {
-        Self(Arc::new(RwLock::new(frequency)))
+        Self(Arc::new(AtomicF32::new(frequency)))
     }
 
     pub fn get(&self) -> f32 {
-        *self.0.read().unwrap()
+        self.0.get()
     }
 
     pub fn set(&self, frequency: f32) {
-        *self.0.write().unwrap() = frequency;
+        self.0.set(frequency);
     }
 }
 
 #[derive(Clone)]
-pub struct SharedVolumeMultiplier(Arc<RwLock<f32>>);
+pub struct SharedVolumeMultiplier(Arc<AtomicF32>);
 
 impl SharedVolumeMultiplier {
     pub fn new(volume_multiplier: f32) -> Self {
-        Self(Arc::new(RwLock::new(volume_multiplier)))
+        Self(Arc::new(AtomicF32::new(volume_multiplier)))
     }
 
     pub fn get(&self) -> f32 {
-        *self.0.read().unwrap()
+        self.0.get()
     }
 
     pub fn set(&self, volume_multiplier: f32) {
         let volume_multiplier = volume_multiplier.clamp(0.0, 1.0);
-        *self.0.write().unwrap() = volume_multiplier;
+        self.0.set(volume_multiplier);
     }
 }
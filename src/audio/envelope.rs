@@ -0,0 +1,139 @@
+use serde::{Deserialize, Serialize};
+
+/// The settings of an attack, decay, sustain, release amplitude envelope
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Envelope {
+    /// The time in seconds it takes to rise from silence to full volume
+    pub attack: f32,
+    /// The time in seconds it takes to fall from full volume to the sustain level
+    pub decay: f32,
+    /// The volume multiplier held while the voice is playing, between 0 and 1
+    pub sustain: f32,
+    /// The time in seconds it takes to fall to silence after the voice stops
+    pub release: f32,
+}
+
+impl Default for Envelope {
+    fn default() -> Self {
+        Self {
+            attack: 0.02,
+            decay: 0.1,
+            sustain: 1.0,
+            release: 0.1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Stage {
+    Idle,
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+}
+
+/// Generates the amplitude of an envelope sample by sample. Every stage is linear
+pub struct EnvelopeGenerator {
+    envelope: Envelope,
+    sample_rate_recip: f32,
+    stage: Stage,
+    level: f32,
+    /// How much the level decreases per sample during the release stage
+    release_step: f32,
+}
+
+impl EnvelopeGenerator {
+    pub fn new(sample_rate: usize, envelope: Envelope) -> Self {
+        Self {
+            envelope,
+            sample_rate_recip: (sample_rate as f32).recip(),
+            stage: Stage::Idle,
+            level: 0.0,
+            release_step: 0.0,
+        }
+    }
+
+    pub fn envelope(&self) -> Envelope {
+        self.envelope
+    }
+
+    /// Change the settings, taking effect from the current level so no clicks are produced
+    pub fn set_envelope(&mut self, envelope: Envelope) {
+        self.envelope = envelope;
+        if self.stage == Stage::Release {
+            self.start_release();
+        }
+    }
+
+    /// Start the attack from the current level
+    pub fn note_on(&mut self) {
+        self.stage = Stage::Attack;
+    }
+
+    /// Start the release from the current level
+    pub fn note_off(&mut self) {
+        if self.stage != Stage::Idle {
+            self.start_release();
+        }
+    }
+
+    /// Whether the envelope has fully released, meaning the voice is silent
+    pub fn is_idle(&self) -> bool {
+        self.stage == Stage::Idle
+    }
+
+    /// Whether the note is held, so the envelope hasn't started its release
+    pub fn is_held(&self) -> bool {
+        matches!(self.stage, Stage::Attack | Stage::Decay | Stage::Sustain)
+    }
+
+    fn start_release(&mut self) {
+        self.stage = Stage::Release;
+        self.release_step = self.level * self.step(self.envelope.release);
+    }
+
+    /// The fraction of a stage of the given duration that passes each sample
+    fn step(&self, duration: f32) -> f32 {
+        if duration <= 0.0 {
+            1.0
+        } else {
+            (self.sample_rate_recip / duration).min(1.0)
+        }
+    }
+
+    /// Advance the envelope by one sample and get the amplitude multiplier
+    pub fn next_level(&mut self) -> f32 {
+        let sustain = self.envelope.sustain.clamp(0.0, 1.0);
+        match self.stage {
+            Stage::Idle => {
+                self.level = 0.0;
+            }
+            Stage::Attack => {
+                self.level += self.step(self.envelope.attack);
+                if self.level >= 1.0 {
+                    self.level = 1.0;
+                    self.stage = Stage::Decay;
+                }
+            }
+            Stage::Decay => {
+                self.level -= (1.0 - sustain) * self.step(self.envelope.decay);
+                if self.level <= sustain {
+                    self.level = sustain;
+                    self.stage = Stage::Sustain;
+                }
+            }
+            Stage::Sustain => {
+                self.level = sustain;
+            }
+            Stage::Release => {
+                self.level -= self.release_step;
+                if self.level <= 0.0 || self.release_step <= 0.0 {
+                    self.level = 0.0;
+                    self.stage = Stage::Idle;
+                }
+            }
+        }
+        self.level
+    }
+}
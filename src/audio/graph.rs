@@ -0,0 +1,630 @@
+use std::{
+    collections::BTreeMap,
+    f32::consts::{FRAC_1_SQRT_2, PI},
+    fmt::Display,
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    envelope::{Envelope, EnvelopeGenerator},
+    synthesizer::WaveTableOscillator,
+};
+
+/// A left and right sample, which is the signal flowing between nodes
+type Frame = [f32; 2];
+
+/// The amount of oscillators the voices have room for without allocating on the audio thread
+const MAX_OSCILLATORS: usize = 256;
+
+/// The amount of removed oscillators which can play their release at once, beyond which they are cut short
+/// so the audio thread never grows its buffer of them
+const MAX_RELEASING: usize = 256;
+
+/// The kind of a node in the graph, with its parameters
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum NodeKind {
+    /// The voices, each played by its own oscillator with its envelope and pan
+    Oscillator,
+    /// Multiplies its input by a base 2 gain
+    Gain(f32),
+    /// Sums the given amount of inputs
+    Mixer(usize),
+    Filter(Filter),
+    /// Multiplies its input by an envelope, which is held while any voice is held
+    Envelope(Envelope),
+    /// The signal played by the engine, before the master volume. A graph has a single output
+    Output,
+}
+
+impl NodeKind {
+    /// Every kind of node which can be added to a graph, with its default parameters
+    pub fn addable() -> [NodeKind; 5] {
+        [
+            NodeKind::Oscillator,
+            NodeKind::Gain(0.0),
+            NodeKind::Mixer(2),
+            NodeKind::Filter(Filter::default()),
+            NodeKind::Envelope(Envelope::default()),
+        ]
+    }
+
+    /// Get the amount of inputs of the node
+    pub fn inputs(&self) -> usize {
+        match self {
+            NodeKind::Oscillator => 0,
+            NodeKind::Mixer(inputs) => *inputs,
+            NodeKind::Gain(_) | NodeKind::Filter(_) | NodeKind::Envelope(_) | NodeKind::Output => 1,
+        }
+    }
+
+    /// Whether the node has an output, which every node but the output of the graph has
+    pub fn has_output(&self) -> bool {
+        *self != NodeKind::Output
+    }
+}
+
+impl Display for NodeKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                NodeKind::Oscillator => "Oscillator",
+                NodeKind::Gain(_) => "Gain",
+                NodeKind::Mixer(_) => "Mixer",
+                NodeKind::Filter(_) => "Filter",
+                NodeKind::Envelope(_) => "Envelope",
+                NodeKind::Output => "Output",
+            }
+        )
+    }
+}
+
+/// The settings of a resonant state variable filter
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Filter {
+    pub mode: FilterMode,
+    /// The cutoff frequency in Hz
+    pub cutoff: f32,
+    /// The quality factor, where the response is flat at 1/sqrt(2) and peaks at the cutoff above it
+    pub resonance: f32,
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Self {
+            mode: FilterMode::LowPass,
+            cutoff: 2000.0,
+            resonance: FRAC_1_SQRT_2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FilterMode {
+    LowPass,
+    HighPass,
+    BandPass,
+}
+
+impl FilterMode {
+    pub const ALL: [FilterMode; 3] = [Self::LowPass, Self::HighPass, Self::BandPass];
+}
+
+impl Display for FilterMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                FilterMode::LowPass => "Low pass",
+                FilterMode::HighPass => "High pass",
+                FilterMode::BandPass => "Band pass",
+            }
+        )
+    }
+}
+
+/// A node of the graph, placed in the node editor
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Node {
+    pub kind: NodeKind,
+    /// The position of the top left corner of the node in the node editor
+    pub x: f32,
+    pub y: f32,
+}
+
+/// The output of a node connected to an input of another node, given as the index of the input
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Connection {
+    pub from: usize,
+    pub to: usize,
+    pub input: usize,
+}
+
+/// A graph of nodes processing the voices into the signal played by the engine
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Graph {
+    pub nodes: BTreeMap<usize, Node>,
+    /// Every input has at most one connection, while an output can be connected to several inputs
+    pub connections: Vec<Connection>,
+}
+
+/// The graph of projects from before graphs, which plays the voices as they are
+impl Default for Graph {
+    fn default() -> Self {
+        Self {
+            nodes: BTreeMap::from([
+                (
+                    1,
+                    Node {
+                        kind: NodeKind::Oscillator,
+                        x: 20.0,
+                        y: 40.0,
+                    },
+                ),
+                (
+                    2,
+                    Node {
+                        kind: NodeKind::Output,
+                        x: 300.0,
+                        y: 40.0,
+                    },
+                ),
+            ]),
+            connections: vec![Connection {
+                from: 1,
+                to: 2,
+                input: 0,
+            }],
+        }
+    }
+}
+
+impl Graph {
+    /// Add a node at a position in the node editor, returning its id
+    pub fn add_node(&mut self, kind: NodeKind, x: f32, y: f32) -> usize {
+        let id = self
+            .nodes
+            .last_key_value()
+            .map(|(id, _)| id + 1)
+            .unwrap_or(1);
+        self.nodes.insert(id, Node { kind, x, y });
+        id
+    }
+
+    /// Remove a node together with its connections
+    pub fn remove_node(&mut self, id: usize) {
+        self.nodes.remove(&id);
+        self.connections
+            .retain(|connection| connection.from != id && connection.to != id);
+    }
+
+    /// Set the parameters of a node, disconnecting the inputs it no longer has
+    pub fn set_kind(&mut self, id: usize, kind: NodeKind) {
+        let Some(node) = self.nodes.get_mut(&id) else {
+            return;
+        };
+        node.kind = kind;
+        self.connections
+            .retain(|connection| connection.to != id || connection.input < kind.inputs());
+    }
+
+    /// Connect the output of a node to an input, replacing the connection the input had. The graph is left
+    /// unchanged if the connection would make the nodes process each other in a cycle
+    pub fn connect(&mut self, connection: Connection) -> Result<(), GraphError> {
+        let previous = self.connections.clone();
+        self.disconnect(connection.to, connection.input);
+        self.connections.push(connection);
+        if let Err(error) = self.processing_order(self.nodes.keys().copied()) {
+            self.connections = previous;
+            return Err(error);
+        }
+        Ok(())
+    }
+
+    /// Remove the connection of an input, if it has one
+    pub fn disconnect(&mut self, to: usize, input: usize) {
+        self.connections
+            .retain(|connection| connection.to != to || connection.input != input);
+    }
+
+    /// Get the connection of an input, if it has one
+    pub fn connection(&self, to: usize, input: usize) -> Option<Connection> {
+        self.connections
+            .iter()
+            .find(|connection| connection.to == to && connection.input == input)
+            .copied()
+    }
+
+    /// Get the id of the output node
+    pub fn output(&self) -> Result<usize, GraphError> {
+        let mut outputs = self
+            .nodes
+            .iter()
+            .filter(|(_, node)| node.kind == NodeKind::Output)
+            .map(|(id, _)| *id);
+        match (outputs.next(), outputs.next()) {
+            (Some(output), None) => Ok(output),
+            (None, _) => Err(GraphError::NoOutput),
+            (Some(_), Some(_)) => Err(GraphError::MultipleOutputs),
+        }
+    }
+
+    /// Check that the graph has a single output and no nodes connected in a cycle, like a graph edited by hand
+    pub fn validate(&self) -> Result<(), GraphError> {
+        self.output()?;
+        self.processing_order(self.nodes.keys().copied())?;
+        Ok(())
+    }
+
+    /// Get the order the nodes which the roots depend on are processed in, where every node comes after
+    /// the nodes connected to its inputs
+    fn processing_order(
+        &self,
+        roots: impl Iterator<Item = usize>,
+    ) -> Result<Vec<usize>, GraphError> {
+        let mut order = Vec::new();
+        let mut path = Vec::new();
+        for id in roots {
+            self.visit(id, &mut path, &mut order)?;
+        }
+        Ok(order)
+    }
+
+    /// Add a node to the processing order after the nodes connected to its inputs, where the path is the
+    /// nodes being visited which it is connected to
+    fn visit(
+        &self,
+        id: usize,
+        path: &mut Vec<usize>,
+        order: &mut Vec<usize>,
+    ) -> Result<(), GraphError> {
+        if order.contains(&id) {
+            return Ok(());
+        }
+        if let Some(start) = path.iter().position(|visited| *visited == id) {
+            let mut cycle = path[start..].to_vec();
+            cycle.push(id);
+            return Err(GraphError::Cycle(cycle));
+        }
+        if !self.nodes.contains_key(&id) {
+            return Ok(());
+        }
+        path.push(id);
+        for connection in self
+            .connections
+            .iter()
+            .filter(|connection| connection.to == id)
+        {
+            self.visit(connection.from, path, order)?;
+        }
+        path.pop();
+        order.push(id);
+        Ok(())
+    }
+
+    /// Compile the nodes the output depends on into the order they are processed in, so the graph can be
+    /// processed without looking anything up. Nodes which don't reach the output are left out
+    pub fn compile(&self, sample_rate: usize) -> Result<CompiledGraph, GraphError> {
+        let order = self.processing_order(std::iter::once(self.output()?))?;
+        let slots: BTreeMap<usize, usize> = order
+            .iter()
+            .enumerate()
+            .map(|(slot, id)| (*id, slot))
+            .collect();
+        let processors = order
+            .iter()
+            .map(|id| {
+                let kind = self.nodes[id].kind;
+                let inputs = (0..kind.inputs())
+                    .map(|input| {
+                        self.connection(*id, input)
+                            .and_then(|connection| slots.get(&connection.from).copied())
+                    })
+                    .collect();
+                Processor {
+                    node: *id,
+                    inputs,
+                    kind: ProcessorKind::new(kind, sample_rate),
+                }
+            })
+            .collect();
+        Ok(CompiledGraph {
+            processors,
+            frames: vec![[0.0; 2]; order.len()],
+            gate: false,
+            voices: Voices::new(),
+        })
+    }
+}
+
+/// An error in how the nodes of a graph are connected
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GraphError {
+    /// The nodes are connected in a cycle, given from a node back to itself
+    Cycle(Vec<usize>),
+    NoOutput,
+    MultipleOutputs,
+}
+
+impl Display for GraphError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GraphError::Cycle(cycle) => write!(
+                f,
+                "the nodes are connected in a cycle: {}",
+                cycle
+                    .iter()
+                    .map(|id| format!("node {id}"))
+                    .collect::<Vec<String>>()
+                    .join(" -> ")
+            ),
+            GraphError::NoOutput => write!(f, "the graph has no output node"),
+            GraphError::MultipleOutputs => write!(f, "the graph has more than one output node"),
+        }
+    }
+}
+
+impl std::error::Error for GraphError {}
+
+/// A graph compiled into the order its nodes are processed in, where the output is processed last.
+/// It is compiled before it is sent to the audio thread, which only processes it
+pub struct CompiledGraph {
+    processors: Vec<Processor>,
+    /// The last frame produced by every processor, by its index
+    frames: Vec<Frame>,
+    /// Whether any voice is held, which the envelopes follow
+    gate: bool,
+    /// The voices the oscillator nodes play, which are empty until they are taken over from the previous graph
+    voices: Voices,
+}
+
+impl CompiledGraph {
+    /// Play a frame of the voices and process it into a frame of the output. Also returns the sample
+    /// of the voice with the id on its own, mixed to mono, or silence if no voice has it
+    pub fn process(&mut self, voice_id: usize) -> (Frame, f32) {
+        self.set_gate(self.voices.is_held());
+        // every oscillator node outputs the sum of the voices, so they are played once per frame
+        let (voices, voice) = self.voices.next_frame(voice_id);
+        for (index, processor) in self.processors.iter_mut().enumerate() {
+            let input = |input: usize| {
+                processor.inputs[input]
+                    .map(|slot| self.frames[slot])
+                    .unwrap_or([0.0; 2])
+            };
+            let frame = match &mut processor.kind {
+                ProcessorKind::Oscillator => voices,
+                ProcessorKind::Gain(multiple) => input(0).map(|sample| sample * *multiple),
+                ProcessorKind::Mixer => (0..processor.inputs.len()).map(input).fold(
+                    [0.0; 2],
+                    |[left, right], [input_left, input_right]| {
+                        [left + input_left, right + input_right]
+                    },
+                ),
+                ProcessorKind::Filter(filter) => filter.process(input(0)),
+                ProcessorKind::Envelope(envelope) => {
+                    let level = envelope.next_level();
+                    input(0).map(|sample| sample * level)
+                }
+                ProcessorKind::Output => input(0),
+            };
+            self.frames[index] = frame;
+        }
+        (self.frames.last().copied().unwrap_or([0.0; 2]), voice)
+    }
+
+    pub fn voices(&self) -> &Voices {
+        &self.voices
+    }
+
+    pub fn voices_mut(&mut self) -> &mut Voices {
+        &mut self.voices
+    }
+
+    /// Start the attack of the envelopes when any voice is held, or their release when none is
+    fn set_gate(&mut self, gate: bool) {
+        if gate == self.gate {
+            return;
+        }
+        self.gate = gate;
+        for processor in &mut self.processors {
+            if let ProcessorKind::Envelope(envelope) = &mut processor.kind {
+                if gate {
+                    envelope.note_on();
+                } else {
+                    envelope.note_off();
+                }
+            }
+        }
+    }
+
+    /// Continue from the state of the graph this one replaces, taking over its voices, so they keep playing
+    /// and filters and envelopes of nodes in both don't restart and click
+    pub fn inherit(&mut self, previous: &mut CompiledGraph) {
+        // the emptied voices of this graph are left in the previous one, so nothing is allocated or freed
+        std::mem::swap(&mut self.voices, &mut previous.voices);
+        self.gate = previous.gate;
+        for processor in &mut self.processors {
+            let inherited = previous
+                .processors
+                .iter_mut()
+                .find(|previous| previous.node == processor.node)
+                .is_some_and(|previous| processor.kind.inherit(&mut previous.kind));
+            // envelopes of new nodes start like the voices started before them
+            if !inherited
+                && self.gate
+                && let ProcessorKind::Envelope(envelope) = &mut processor.kind
+            {
+                envelope.note_on();
+            }
+        }
+    }
+}
+
+/// The oscillators played by the oscillator nodes of a graph, each with its envelope and pan
+pub struct Voices {
+    /// The oscillators in the order they were added, with room for [MAX_OSCILLATORS]
+    pub playing: Vec<Voice>,
+    /// Removed oscillators which are still playing their release, with room for [MAX_RELEASING]
+    pub releasing: Vec<WaveTableOscillator>,
+}
+
+/// An oscillator with the id it was added with
+pub struct Voice {
+    pub id: usize,
+    pub oscillator: WaveTableOscillator,
+    /// Whether the oscillator overrides the project wavetable
+    pub has_own_wavetable: bool,
+}
+
+impl Voices {
+    fn new() -> Self {
+        Self {
+            playing: Vec::with_capacity(MAX_OSCILLATORS),
+            releasing: Vec::with_capacity(MAX_RELEASING),
+        }
+    }
+
+    /// Get the voice with the id, if it is playing
+    pub fn get_mut(&mut self, id: usize) -> Option<&mut Voice> {
+        self.playing.iter_mut().find(|voice| voice.id == id)
+    }
+
+    /// Whether any voice is held, which the envelope nodes follow
+    fn is_held(&self) -> bool {
+        self.playing.iter().any(|voice| voice.oscillator.is_held())
+    }
+
+    /// Play a frame of every oscillator, returning their sum and the mono sample of the voice with the id.
+    /// Oscillators are silent once their envelope has released, so they don't need to be skipped
+    fn next_frame(&mut self, voice_id: usize) -> (Frame, f32) {
+        let mut sum = [0.0; 2];
+        let mut voice = 0.0;
+        // releasing oscillators have no id, so they are never the voice
+        for (id, oscillator) in self
+            .playing
+            .iter_mut()
+            .map(|voice| (Some(voice.id), &mut voice.oscillator))
+            .chain(
+                self.releasing
+                    .iter_mut()
+                    .map(|oscillator| (None, oscillator)),
+            )
+        {
+            let [left, right] = oscillator.next_frame();
+            sum[0] += left;
+            sum[1] += right;
+            if id == Some(voice_id) {
+                voice = (left + right) * 0.5;
+            }
+        }
+        (sum, voice)
+    }
+}
+
+/// A node compiled for processing
+struct Processor {
+    /// The id of the node it was compiled from
+    node: usize,
+    /// The index of the processor connected to each input, if any
+    inputs: Vec<Option<usize>>,
+    kind: ProcessorKind,
+}
+
+enum ProcessorKind {
+    /// Outputs the sum of the voices
+    Oscillator,
+    /// The gain as a volume multiplier
+    Gain(f32),
+    Mixer,
+    Filter(FilterProcessor),
+    Envelope(EnvelopeGenerator),
+    Output,
+}
+
+impl ProcessorKind {
+    fn new(kind: NodeKind, sample_rate: usize) -> Self {
+        match kind {
+            NodeKind::Oscillator => ProcessorKind::Oscillator,
+            NodeKind::Gain(gain) => ProcessorKind::Gain(gain.exp2()),
+            NodeKind::Mixer(_) => ProcessorKind::Mixer,
+            NodeKind::Filter(filter) => {
+                ProcessorKind::Filter(FilterProcessor::new(filter, sample_rate))
+            }
+            NodeKind::Envelope(envelope) => {
+                ProcessorKind::Envelope(EnvelopeGenerator::new(sample_rate, envelope))
+            }
+            NodeKind::Output => ProcessorKind::Output,
+        }
+    }
+
+    /// Take over the state of the processor of the same node in the previous graph, returning whether it could
+    fn inherit(&mut self, previous: &mut ProcessorKind) -> bool {
+        match (self, previous) {
+            (ProcessorKind::Filter(filter), ProcessorKind::Filter(previous)) => {
+                filter.state = previous.state;
+                true
+            }
+            (ProcessorKind::Envelope(envelope), ProcessorKind::Envelope(previous)) => {
+                // the previous generator keeps its stage and level, with the new settings
+                let settings = envelope.envelope();
+                std::mem::swap(envelope, previous);
+                envelope.set_envelope(settings);
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// A stereo state variable filter, discretized with the trapezoidal rule so it stays stable at any cutoff
+struct FilterProcessor {
+    mode: FilterMode,
+    /// The damping, which is the reciprocal of the resonance
+    k: f32,
+    a1: f32,
+    a2: f32,
+    a3: f32,
+    /// The states of the two integrators of the left and right channel
+    state: [[f32; 2]; 2],
+}
+
+impl FilterProcessor {
+    fn new(filter: Filter, sample_rate: usize) -> Self {
+        let sample_rate = sample_rate as f32;
+        // the cutoff is kept below the nyquist frequency, where the filter would be unstable
+        let cutoff = filter.cutoff.clamp(10.0, sample_rate * 0.49);
+        let g = (PI * cutoff / sample_rate).tan();
+        let k = filter.resonance.max(0.1).recip();
+        let a1 = (1.0 + g * (g + k)).recip();
+        let a2 = g * a1;
+        Self {
+            mode: filter.mode,
+            k,
+            a1,
+            a2,
+            a3: g * a2,
+            state: [[0.0; 2]; 2],
+        }
+    }
+
+    fn process(&mut self, frame: Frame) -> Frame {
+        let mut output = [0.0; 2];
+        for (channel, sample) in frame.into_iter().enumerate() {
+            let [ic1eq, ic2eq] = &mut self.state[channel];
+            let v3 = sample - *ic2eq;
+            let v1 = self.a1 * *ic1eq + self.a2 * v3;
+            let v2 = *ic2eq + self.a2 * *ic1eq + self.a3 * v3;
+            *ic1eq = 2.0 * v1 - *ic1eq;
+            *ic2eq = 2.0 * v2 - *ic2eq;
+            output[channel] = match self.mode {
+                FilterMode::LowPass => v2,
+                FilterMode::BandPass => v1,
+                FilterMode::HighPass => sample - self.k * v1 - v2,
+            };
+        }
+        output
+    }
+}
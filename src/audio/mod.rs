@@ -1,3 +1,11 @@
 pub mod engine;
+pub mod envelope;
+pub mod graph;
+pub mod output;
+pub mod render;
+pub mod scope;
+pub mod smoothing;
+pub mod source;
 pub mod synthesizer;
 pub mod theory;
+pub mod transport;
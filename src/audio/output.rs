@@ -0,0 +1,312 @@
+use std::{
+    fmt::Display,
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use rodio::{
+    OutputStream, PlayError, Source, StreamError, SupportedStreamConfig,
+    cpal::{
+        self, SampleRate,
+        traits::{DeviceTrait, HostTrait},
+    },
+};
+
+use crate::settings::Settings;
+
+use super::{
+    engine::{AudioEngine, CHANNELS, EngineControl, EngineController},
+    source::AudioSource,
+};
+
+/// The sample rate used when the output device doesn't decide it
+pub const DEFAULT_SAMPLE_RATE: usize = 48000;
+
+/// The sample rates offered in the settings, if the output device supports them
+const COMMON_SAMPLE_RATES: [u32; 6] = [22050, 44100, 48000, 88200, 96000, 192000];
+
+/// How often the null backend wakes up to consume samples
+const NULL_BLOCK_DURATION: Duration = Duration::from_millis(10);
+
+/// An output playing an audio engine, either on a device or on the null backend. Playback stops when it is dropped
+pub struct AudioOutput {
+    backend: Backend,
+    device_name: Option<String>,
+    sample_rate: u32,
+}
+
+enum Backend {
+    /// The stream is only held so it keeps playing
+    Device {
+        _stream: OutputStream,
+    },
+    Null(NullOutput),
+}
+
+impl AudioOutput {
+    /// Open the output of the settings, or fall back to the null backend if no device can be opened,
+    /// so the engine runs even on machines without sound hardware
+    pub fn from_settings(settings: &Settings) -> (Self, EngineController) {
+        match Self::open(settings.output_device.as_deref(), settings.sample_rate) {
+            Ok(output) => output,
+            Err(error) => {
+                eprintln!("failed to open an audio output device, using the null backend: {error}");
+                let sample_rate = settings.sample_rate.unwrap_or(DEFAULT_SAMPLE_RATE as u32);
+                let file = settings.null_output_file.as_deref();
+                Self::null(sample_rate, file).unwrap_or_else(|error| {
+                    eprintln!("failed to create the null output file, not writing to it: {error}");
+                    Self::null(sample_rate, None).expect("the null backend only fails on files")
+                })
+            }
+        }
+    }
+
+    /// Consume the samples of a new engine in real time without playing them, optionally writing them to a wav file
+    pub fn null(
+        sample_rate: u32,
+        file: Option<&Path>,
+    ) -> Result<(Self, EngineController), OutputError> {
+        let writer = file
+            .map(|path| {
+                hound::WavWriter::create(
+                    path,
+                    hound::WavSpec {
+                        channels: CHANNELS,
+                        sample_rate,
+                        bits_per_sample: 32,
+                        sample_format: hound::SampleFormat::Float,
+                    },
+                )
+            })
+            .transpose()?;
+        let engine = AudioEngine::new(sample_rate as usize);
+        let controller = engine.controller();
+        Ok((
+            Self {
+                backend: Backend::Null(NullOutput::spawn(engine, writer, file.map(Path::to_owned))),
+                device_name: None,
+                sample_rate,
+            },
+            controller,
+        ))
+    }
+
+    /// Open the output device with the given name at the given sample rate, and start playing a new engine on it.
+    /// The default device and its default sample rate are used for anything that is unset or unavailable
+    pub fn open(
+        device_name: Option<&str>,
+        sample_rate: Option<u32>,
+    ) -> Result<(Self, EngineController), OutputError> {
+        match Self::open_exact(device_name, sample_rate) {
+            Err(_) if device_name.is_some() || sample_rate.is_some() => {
+                Self::open_exact(None, None)
+            }
+            result => result,
+        }
+    }
+
+    fn open_exact(
+        device_name: Option<&str>,
+        sample_rate: Option<u32>,
+    ) -> Result<(Self, EngineController), OutputError> {
+        let host = cpal::default_host();
+        let device = device_name
+            .and_then(|name| find_device(&host, name))
+            .or_else(|| host.default_output_device())
+            .ok_or(StreamError::NoDevice)?;
+        let config = match sample_rate.and_then(|sample_rate| find_config(&device, sample_rate)) {
+            Some(config) => config,
+            None => device
+                .default_output_config()
+                .map_err(StreamError::DefaultStreamConfigError)?,
+        };
+        let sample_rate = config.sample_rate().0;
+
+        let (stream, stream_handle) = OutputStream::try_from_device_config(&device, config)?;
+        let engine = AudioEngine::new(sample_rate as usize);
+        let controller = engine.controller();
+        stream_handle.play_raw(AudioSource::new(engine).convert_samples())?;
+
+        Ok((
+            Self {
+                backend: Backend::Device { _stream: stream },
+                device_name: device.name().ok(),
+                sample_rate,
+            },
+            controller,
+        ))
+    }
+
+    /// Get the name of the device being played on, which is None on the null backend
+    pub fn device_name(&self) -> Option<&str> {
+        self.device_name.as_deref()
+    }
+
+    /// Whether the engine runs on the null backend instead of a device
+    pub fn is_null(&self) -> bool {
+        matches!(self.backend, Backend::Null(_))
+    }
+
+    /// Get the file the null backend writes to, if any
+    pub fn null_output_file(&self) -> Option<&Path> {
+        match &self.backend {
+            Backend::Null(null_output) => null_output.file.as_deref(),
+            Backend::Device { .. } => None,
+        }
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+}
+
+/// A thread consuming the samples of an engine at the pace a device would
+struct NullOutput {
+    file: Option<PathBuf>,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl NullOutput {
+    fn spawn(
+        mut engine: AudioEngine,
+        mut writer: Option<hound::WavWriter<BufWriter<File>>>,
+        file: Option<PathBuf>,
+    ) -> Self {
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let stop = stop.clone();
+            std::thread::spawn(move || {
+                let sample_rate = engine.sample_rate() as f64;
+                let start = Instant::now();
+                let mut frames = 0u64;
+                while !stop.load(Ordering::Relaxed) {
+                    // produce every frame which would have been played by now, then sleep until the next block
+                    let due_frames = (start.elapsed().as_secs_f64() * sample_rate) as u64;
+                    for _ in frames..due_frames {
+                        for sample in engine.by_ref().take(CHANNELS as usize) {
+                            if let Some(error) = writer
+                                .as_mut()
+                                .and_then(|writer| writer.write_sample(sample).err())
+                            {
+                                eprintln!("failed to write to the null output file: {error}");
+                                writer = None;
+                            }
+                        }
+                    }
+                    frames = frames.max(due_frames);
+                    std::thread::sleep(NULL_BLOCK_DURATION);
+                }
+                if let Some(Err(error)) = writer.map(hound::WavWriter::finalize) {
+                    eprintln!("failed to finalize the null output file: {error}");
+                }
+            })
+        };
+        Self {
+            file,
+            stop,
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for NullOutput {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Get the names of every available output device
+pub fn output_devices() -> Vec<String> {
+    cpal::default_host()
+        .output_devices()
+        .map(|devices| devices.filter_map(|device| device.name().ok()).collect())
+        .unwrap_or_default()
+}
+
+/// Get the common sample rates supported by the output device with the given name, or the default device if None
+pub fn supported_sample_rates(device_name: Option<&str>) -> Vec<u32> {
+    let host = cpal::default_host();
+    let Some(device) = device_name
+        .and_then(|name| find_device(&host, name))
+        .or_else(|| host.default_output_device())
+    else {
+        return Vec::new();
+    };
+    COMMON_SAMPLE_RATES
+        .into_iter()
+        .filter(|sample_rate| find_config(&device, *sample_rate).is_some())
+        .collect()
+}
+
+fn find_device(host: &cpal::Host, name: &str) -> Option<cpal::Device> {
+    host.output_devices()
+        .ok()?
+        .find(|device| device.name().is_ok_and(|device_name| device_name == name))
+}
+
+/// Find a config of the device at the sample rate, preferring one with as many channels as the engine
+fn find_config(device: &cpal::Device, sample_rate: u32) -> Option<SupportedStreamConfig> {
+    let configs: Vec<_> = device
+        .supported_output_configs()
+        .ok()?
+        .filter(|config| {
+            (config.min_sample_rate().0..=config.max_sample_rate().0).contains(&sample_rate)
+        })
+        .collect();
+    configs
+        .iter()
+        .find(|config| config.channels() == CHANNELS)
+        .or(configs.first())
+        .cloned()
+        .map(|config| config.with_sample_rate(SampleRate(sample_rate)))
+}
+
+/// An error which occurred while opening an output device
+#[derive(Debug)]
+pub enum OutputError {
+    Stream(StreamError),
+    Play(PlayError),
+    /// The file of the null backend couldn't be created
+    Wav(hound::Error),
+}
+
+impl Display for OutputError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OutputError::Stream(error) => write!(f, "{error}"),
+            OutputError::Play(error) => write!(f, "{error}"),
+            OutputError::Wav(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for OutputError {}
+
+impl From<StreamError> for OutputError {
+    fn from(error: StreamError) -> Self {
+        Self::Stream(error)
+    }
+}
+
+impl From<PlayError> for OutputError {
+    fn from(error: PlayError) -> Self {
+        Self::Play(error)
+    }
+}
+
+impl From<hound::Error> for OutputError {
+    fn from(error: hound::Error) -> Self {
+        Self::Wav(error)
+    }
+}
@@ -0,0 +1,99 @@
+use std::{
+    fmt::Display,
+    io::{Seek, Write},
+    path::Path,
+    time::Duration,
+};
+
+use super::engine::{AudioEngine, CHANNELS, EngineControl};
+
+/// The sample format of a rendered wav file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SampleFormat {
+    #[default]
+    Int16,
+    Int24,
+    Float32,
+}
+
+impl SampleFormat {
+    pub const ALL: [SampleFormat; 3] = [Self::Int16, Self::Int24, Self::Float32];
+
+    fn wav_spec(&self, sample_rate: u32) -> hound::WavSpec {
+        let (bits_per_sample, sample_format) = match self {
+            SampleFormat::Int16 => (16, hound::SampleFormat::Int),
+            SampleFormat::Int24 => (24, hound::SampleFormat::Int),
+            SampleFormat::Float32 => (32, hound::SampleFormat::Float),
+        };
+        hound::WavSpec {
+            channels: CHANNELS,
+            sample_rate,
+            bits_per_sample,
+            sample_format,
+        }
+    }
+}
+
+impl Display for SampleFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                SampleFormat::Int16 => "16-bit",
+                SampleFormat::Int24 => "24-bit",
+                SampleFormat::Float32 => "32-bit float",
+            }
+        )
+    }
+}
+
+/// Render the output of the engine for the given duration to a wav file at the engine's sample rate.
+/// The engine is driven like it would be by the audio thread, so make sure it is playing
+pub fn render_to_wav(
+    engine: &mut AudioEngine,
+    duration: Duration,
+    format: SampleFormat,
+    path: impl AsRef<Path>,
+) -> Result<(), hound::Error> {
+    let spec = format.wav_spec(engine.sample_rate() as u32);
+    let writer = hound::WavWriter::create(path, spec)?;
+    write_samples(engine, duration, format, writer)
+}
+
+/// Same as [`render_to_wav`], but writes the wav data to any seekable writer
+pub fn render_to_writer<W: Write + Seek>(
+    engine: &mut AudioEngine,
+    duration: Duration,
+    format: SampleFormat,
+    writer: W,
+) -> Result<(), hound::Error> {
+    let spec = format.wav_spec(engine.sample_rate() as u32);
+    let writer = hound::WavWriter::new(writer, spec)?;
+    write_samples(engine, duration, format, writer)
+}
+
+fn write_samples<W: Write + Seek>(
+    engine: &mut AudioEngine,
+    duration: Duration,
+    format: SampleFormat,
+    mut writer: hound::WavWriter<W>,
+) -> Result<(), hound::Error> {
+    let frame_count = (duration.as_secs_f64() * engine.sample_rate() as f64).round() as usize;
+
+    for sample in engine.by_ref().take(frame_count * CHANNELS as usize) {
+        match format {
+            SampleFormat::Int16 => {
+                writer.write_sample((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16)?
+            }
+            SampleFormat::Int24 => {
+                // 24-bit samples are written from the lower bits of an i32
+                const I24_MAX: f32 = ((1 << 23) - 1) as f32;
+                writer.write_sample((sample.clamp(-1.0, 1.0) * I24_MAX) as i32)?
+            }
+            SampleFormat::Float32 => writer.write_sample(sample)?,
+        }
+    }
+
+    writer.finalize()
+}
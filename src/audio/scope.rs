@@ -0,0 +1,94 @@
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+/// The amount of frames a scope keeps, which is more than a second at common sample rates
+const SCOPE_FRAMES: usize = 1 << 16;
+
+/// A ring buffer of the latest frames the engine produced, mixed to mono. It is written by the audio thread
+/// and read by the gui thread without locking, where a frame overwritten while it is read only shows as a
+/// glitch in the drawing
+pub struct ScopeBuffer {
+    /// The mixed output of the engine
+    mixed: Box<[AtomicU32]>,
+    /// The voice shown on its own, which is silent if no voice is
+    voice: Box<[AtomicU32]>,
+    /// The amount of frames written since the engine was created, which is the index of the next frame
+    written: AtomicU64,
+}
+
+impl Default for ScopeBuffer {
+    fn default() -> Self {
+        Self {
+            mixed: (0..SCOPE_FRAMES).map(|_| AtomicU32::new(0)).collect(),
+            voice: (0..SCOPE_FRAMES).map(|_| AtomicU32::new(0)).collect(),
+            written: AtomicU64::new(0),
+        }
+    }
+}
+
+impl ScopeBuffer {
+    /// Write the next frame, overwriting the oldest one. Only the audio thread writes
+    pub fn push(&self, mixed: f32, voice: f32) {
+        let written = self.written.load(Ordering::Relaxed);
+        let index = (written % SCOPE_FRAMES as u64) as usize;
+        self.mixed[index].store(mixed.to_bits(), Ordering::Relaxed);
+        self.voice[index].store(voice.to_bits(), Ordering::Relaxed);
+        // the frame is visible to readers once the count including it is
+        self.written.store(written + 1, Ordering::Release);
+    }
+
+    /// Copy the latest frames, at most half of what the buffer keeps so they aren't overwritten while copied
+    pub fn snapshot(&self, frames: usize) -> ScopeSnapshot {
+        let written = self.written.load(Ordering::Acquire);
+        let frames = (frames.min(SCOPE_FRAMES / 2) as u64).min(written);
+        let start = written - frames;
+        let read = |samples: &[AtomicU32]| {
+            (start..written)
+                .map(|frame| {
+                    f32::from_bits(
+                        samples[(frame % SCOPE_FRAMES as u64) as usize].load(Ordering::Relaxed),
+                    )
+                })
+                .collect()
+        };
+        ScopeSnapshot {
+            start,
+            mixed: read(&self.mixed),
+            voice: read(&self.voice),
+        }
+    }
+}
+
+/// The latest frames of a [ScopeBuffer], copied for drawing
+#[derive(Debug, Clone, Default)]
+pub struct ScopeSnapshot {
+    /// The index of the first frame since the engine was created
+    pub start: u64,
+    pub mixed: Vec<f32>,
+    pub voice: Vec<f32>,
+}
+
+impl ScopeSnapshot {
+    /// Find the index of the frame a window of the given length starts at, so that consecutive snapshots of a
+    /// periodic signal are drawn in the same place. With the period of the fundamental in frames, the window
+    /// starts at the latest whole number of periods since the engine was created, which a just intonation chord
+    /// repeats at whatever the phases of its voices. Without one, it starts at the latest rising zero crossing
+    pub fn trigger(&self, period: Option<f64>, window: usize) -> Option<usize> {
+        let latest = self.mixed.len().checked_sub(window)?;
+        if let Some(period) = period.filter(|period| *period >= 1.0) {
+            let latest_frame = self.start + latest as u64;
+            let periods = (latest_frame as f64 / period).floor();
+            let frame = (periods * period).round() as u64;
+            // a period longer than the snapshot can't be triggered on
+            if let Some(index) = frame
+                .checked_sub(self.start)
+                .filter(|index| *index <= latest as u64)
+            {
+                return Some(index as usize);
+            }
+        }
+        (1..=latest)
+            .rev()
+            .find(|index| self.mixed[index - 1] < 0.0 && self.mixed[*index] >= 0.0)
+            .or(Some(latest))
+    }
+}
@@ -0,0 +1,83 @@
+use serde::{Deserialize, Serialize};
+
+/// The settings for how oscillators move to new frequencies and volumes
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Smoothing {
+    /// The time in seconds frequency and volume changes are spread over to prevent clicks
+    pub time: f32,
+    /// Whether frequency changes glide over the portamento time instead
+    pub portamento: bool,
+    /// The time in seconds of a deliberate glide between frequencies
+    pub portamento_time: f32,
+}
+
+impl Smoothing {
+    /// The time in seconds a frequency change is spread over
+    pub fn frequency_time(&self) -> f32 {
+        if self.portamento {
+            self.portamento_time
+        } else {
+            self.time
+        }
+    }
+}
+
+impl Default for Smoothing {
+    fn default() -> Self {
+        Self {
+            time: 0.02,
+            portamento: false,
+            portamento_time: 0.5,
+        }
+    }
+}
+
+/// Moves linearly towards a target value over a given number of samples
+pub struct Smoother {
+    current: f32,
+    target: f32,
+    step: f32,
+    remaining: usize,
+}
+
+impl Smoother {
+    /// Create a smoother which starts out settled at the value
+    pub fn new(value: f32) -> Self {
+        Self {
+            current: value,
+            target: value,
+            step: 0.0,
+            remaining: 0,
+        }
+    }
+
+    /// Start moving from the current value to the target, reaching it after the given amount of samples
+    pub fn set_target(&mut self, target: f32, samples: usize) {
+        self.target = target;
+        if samples == 0 || !self.current.is_finite() {
+            self.current = target;
+            self.remaining = 0;
+        } else {
+            self.step = (target - self.current) / samples as f32;
+            self.remaining = samples;
+        }
+    }
+
+    /// Whether the smoother hasn't reached its target yet
+    pub fn is_moving(&self) -> bool {
+        self.remaining > 0
+    }
+
+    /// Advance the smoother by one sample and get the current value
+    pub fn next_value(&mut self) -> f32 {
+        if self.remaining > 0 {
+            self.remaining -= 1;
+            self.current = if self.remaining == 0 {
+                self.target
+            } else {
+                self.current + self.step
+            };
+        }
+        self.current
+    }
+}
@@ -0,0 +1,39 @@
+use rodio::Source;
+
+use super::engine::{AudioEngine, CHANNELS, EngineControl};
+
+/// A rodio source playing the output of an audio engine, which is controlled from the gui thread
+/// through an [`EngineController`](super::engine::EngineController)
+pub struct AudioSource(AudioEngine);
+
+impl AudioSource {
+    pub fn new(engine: AudioEngine) -> Self {
+        Self(engine)
+    }
+}
+
+impl Iterator for AudioSource {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next()
+    }
+}
+
+impl Source for AudioSource {
+    fn current_frame_len(&self) -> Option<usize> {
+        None //TODO: maybe research this more
+    }
+
+    fn channels(&self) -> u16 {
+        CHANNELS
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.0.sample_rate() as u32
+    }
+
+    fn total_duration(&self) -> Option<std::time::Duration> {
+        None
+    }
+}
@@ -1,52 +1,196 @@
-use std::f32::consts::TAU;
+use std::{
+    f32::consts::{FRAC_PI_4, PI, TAU},
+    sync::{Arc, OnceLock},
+};
 
 use serde::{Deserialize, Serialize};
 
-use super::engine::{SharedFrequency, SharedVolumeMultiplier};
+use super::{
+    engine::{SharedFrequency, SharedVolumeMultiplier},
+    envelope::{Envelope, EnvelopeGenerator},
+    smoothing::{Smoother, Smoothing},
+};
 
 pub const WAVETABLE_SIZE: usize = 1024;
 
+/// The most harmonics a table can hold without aliasing within the table itself
+const MAX_HARMONICS: usize = WAVETABLE_SIZE / 2;
+
+/// A sine partial of a waveform at a whole multiple of the fundamental frequency
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Harmonic {
+    pub amplitude: f32,
+    /// The phase offset in periods of the harmonic
+    pub phase: f32,
+}
+
+impl Harmonic {
+    pub fn new(amplitude: f32, phase: f32) -> Self {
+        Self { amplitude, phase }
+    }
+}
+
+/// A single period of a waveform, mip-mapped into band-limited tables. The first table holds every harmonic,
+/// and each following table holds half as many, so it can be played an octave higher without aliasing.
+/// Cloning is cheap, since the samples are shared
 #[derive(Clone)]
-pub struct WaveTable([f32; WAVETABLE_SIZE]);
+pub struct WaveTable(Arc<Vec<[f32; WAVETABLE_SIZE]>>);
 
 impl WaveTable {
     pub fn sine() -> Self {
-        Self::from_fn(|time| (time * TAU).sin())
+        Self::from_harmonics(&[Harmonic::new(1.0, 0.0)])
     }
 
     pub fn triangle() -> Self {
-        Self::from_fn(|time| 4.0 * (time + 0.25 - (time + 0.75).floor()).abs() - 1.0)
+        Self::from_harmonics(
+            &(1..=MAX_HARMONICS)
+                .map(|n| match n % 4 {
+                    1 => 8.0 / (PI * PI * (n * n) as f32),
+                    3 => -8.0 / (PI * PI * (n * n) as f32),
+                    _ => 0.0,
+                })
+                .map(|amplitude| Harmonic::new(amplitude, 0.0))
+                .collect::<Vec<_>>(),
+        )
     }
 
     pub fn square() -> Self {
-        Self::from_fn(|time| if time <= 0.5 { 1.0 } else { -1.0 })
+        Self::from_harmonics(
+            &(1..=MAX_HARMONICS)
+                .map(|n| {
+                    if n % 2 == 1 {
+                        4.0 / (PI * n as f32)
+                    } else {
+                        0.0
+                    }
+                })
+                .map(|amplitude| Harmonic::new(amplitude, 0.0))
+                .collect::<Vec<_>>(),
+        )
     }
 
     pub fn saw() -> Self {
-        Self::from_fn(|time| (2.0 * time + 3.0) % 2.0 - 1.0)
+        Self::from_harmonics(
+            &(1..=MAX_HARMONICS)
+                .map(|n| {
+                    let amplitude = 2.0 / (PI * n as f32);
+                    if n % 2 == 1 { amplitude } else { -amplitude }
+                })
+                .map(|amplitude| Harmonic::new(amplitude, 0.0))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    /// Get the wavetable of a waveform. Every builtin waveform is only built once, after which its tables are shared
+    pub fn from_waveform(waveform: WaveForm) -> Self {
+        static SINE: OnceLock<WaveTable> = OnceLock::new();
+        static TRIANGLE: OnceLock<WaveTable> = OnceLock::new();
+        static SQUARE: OnceLock<WaveTable> = OnceLock::new();
+        static SAW: OnceLock<WaveTable> = OnceLock::new();
+        match waveform {
+            WaveForm::Sine => SINE.get_or_init(Self::sine),
+            WaveForm::Triangle => TRIANGLE.get_or_init(Self::triangle),
+            WaveForm::Square => SQUARE.get_or_init(Self::square),
+            WaveForm::Saw => SAW.get_or_init(Self::saw),
+            WaveForm::Custom(harmonics) => return Self::from_harmonics(&harmonics).normalized(),
+        }
+        .clone()
     }
 
-    /// Create a wavetable from a periodic function of period 1
+    /// Build band-limited tables additively, where the first harmonic is the fundamental
+    pub fn from_harmonics(harmonics: &[Harmonic]) -> Self {
+        let harmonics = &harmonics[..harmonics.len().min(MAX_HARMONICS)];
+        let sine: Vec<f32> = (0..WAVETABLE_SIZE)
+            .map(|i| (i as f32 / WAVETABLE_SIZE as f32 * TAU).sin())
+            .collect();
+
+        let mut tables: Vec<[f32; WAVETABLE_SIZE]> = Vec::new();
+        let mut harmonic_count = MAX_HARMONICS;
+        while harmonic_count >= 1 {
+            let table = match tables.last() {
+                // no harmonics are dropped, so the table is the same as the last one
+                Some(last) if harmonic_count >= harmonics.len() => *last,
+                _ => additive_table(&sine, &harmonics[..harmonics.len().min(harmonic_count)]),
+            };
+            tables.push(table);
+            harmonic_count /= 2;
+        }
+        Self(Arc::new(tables))
+    }
+
+    /// Scale every table by the peak of the first one, which holds every harmonic, so it peaks at 1.
+    /// Harmonics drawn by the user can add up far above the peak of the builtin waveforms
+    fn normalized(self) -> Self {
+        let peak = self.0[0]
+            .iter()
+            .fold(0f32, |peak, sample| peak.max(sample.abs()));
+        if peak == 0.0 {
+            return self;
+        }
+        let tables = self
+            .0
+            .iter()
+            .map(|table| table.map(|sample| sample / peak))
+            .collect();
+        Self(Arc::new(tables))
+    }
+
+    /// Create a wavetable from a periodic function of period 1. It is not band-limited,
+    /// so functions with sharp corners or discontinuities will alias
     pub fn from_fn(f: fn(f32) -> f32) -> Self {
         let mut table = [0f32; WAVETABLE_SIZE];
         for (i, val) in table.iter_mut().enumerate() {
             *val = f(i as f32 * ((WAVETABLE_SIZE as f32).recip()));
         }
-        Self(table)
+        Self(Arc::new(vec![table]))
     }
 
-    pub fn get_index(&self, index: usize) -> f32 {
-        self.0[index]
+    /// Get the index of the table with the most harmonics which don't alias at the given phase increment,
+    /// which is the frequency divided by the sample rate
+    pub fn table_index(&self, phase_increment: f32) -> usize {
+        // harmonics above half the sample rate alias
+        let max_harmonics = (0.5 / phase_increment.abs().max(f32::MIN_POSITIVE)) as usize;
+        let mut index = 0;
+        while MAX_HARMONICS >> index > max_harmonics && index + 1 < self.0.len() {
+            index += 1;
+        }
+        index
     }
 
-    pub fn get_interpolated_value(&self, index: f32) -> f32 {
+    pub fn get_index(&self, table: usize, index: usize) -> f32 {
+        self.0[table][index]
+    }
+
+    pub fn get_interpolated_value(&self, table: usize, index: f32) -> f32 {
+        let table = &self.0[table.min(self.0.len() - 1)];
         let index = index % (WAVETABLE_SIZE as f32);
         let floor = index as usize;
-        let (sample0, sample1) = (self.0[floor], self.0[(floor + 1) % WAVETABLE_SIZE]);
+        let (sample0, sample1) = (table[floor], table[(floor + 1) % WAVETABLE_SIZE]);
         lerp(sample0, sample1, index - (floor as f32))
     }
 }
 
+/// Sum the harmonics into a single table, using a precomputed sine table of the same size
+fn additive_table(sine: &[f32], harmonics: &[Harmonic]) -> [f32; WAVETABLE_SIZE] {
+    let mut table = [0f32; WAVETABLE_SIZE];
+    for (n, harmonic) in (1..).zip(harmonics) {
+        if harmonic.amplitude == 0.0 {
+            continue;
+        }
+        // a * sin(x + phase) = a * cos(phase) * sin(x) + a * sin(phase) * cos(x)
+        let (sin_amplitude, cos_amplitude) = (
+            harmonic.amplitude * (harmonic.phase * TAU).cos(),
+            harmonic.amplitude * (harmonic.phase * TAU).sin(),
+        );
+        for (i, val) in table.iter_mut().enumerate() {
+            let index = n * i;
+            *val += sin_amplitude * sine[index % WAVETABLE_SIZE]
+                + cos_amplitude * sine[(index + WAVETABLE_SIZE / 4) % WAVETABLE_SIZE];
+        }
+    }
+    table
+}
+
 impl Default for WaveTable {
     fn default() -> Self {
         Self::sine()
@@ -57,27 +201,87 @@ const fn lerp(sample0: f32, sample1: f32, t: f32) -> f32 {
     (1.0 - t) * sample0 + t * sample1
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
 pub enum WaveForm {
+    #[default]
     Sine,
     Triangle,
     Square,
     Saw,
+    /// A waveform built additively from its harmonics, where the first harmonic is the fundamental
+    Custom(Vec<Harmonic>),
 }
 
-impl Default for WaveForm {
-    fn default() -> Self {
-        Self::Sine
+impl WaveForm {
+    /// Every builtin waveform
+    pub const ALL: [WaveForm; 4] = [Self::Sine, Self::Triangle, Self::Square, Self::Saw];
+}
+
+impl std::fmt::Display for WaveForm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                WaveForm::Sine => "Sine",
+                WaveForm::Triangle => "Triangle",
+                WaveForm::Square => "Square",
+                WaveForm::Saw => "Saw",
+                WaveForm::Custom(_) => "Custom",
+            }
+        )
+    }
+}
+
+/// The log2 gain below which an oscillator is considered silent
+const SILENT_GAIN: f32 = -24.0;
+
+fn to_log_gain(volume_multiplier: f32) -> f32 {
+    if volume_multiplier > 0.0 {
+        volume_multiplier.log2().max(SILENT_GAIN)
+    } else {
+        SILENT_GAIN
+    }
+}
+
+fn from_log_gain(gain: f32) -> f32 {
+    if gain <= SILENT_GAIN {
+        0.0
+    } else {
+        gain.exp2()
     }
 }
 
+/// The gains of the left and right channel for a pan from -1 to 1. The pan law is constant power,
+/// so a voice is equally loud wherever it is panned
+fn pan_gains(pan: f32) -> [f32; 2] {
+    let angle = (pan.clamp(-1.0, 1.0) + 1.0) * FRAC_PI_4;
+    [angle.cos(), angle.sin()]
+}
+
 pub struct WaveTableOscillator {
-    _sample_rate: usize,
+    sample_rate: usize,
     sample_rate_recip: f32,
     frequency: SharedFrequency,
     volume_multiplier: SharedVolumeMultiplier,
+    /// The last seen values of the shared frequency and volume multiplier, used for noticing changes
+    targets: (f32, f32),
+    /// Smooths frequency changes linearly in cents, by moving in log2 of the frequency
+    pitch: Smoother,
+    /// Smooths volume changes linearly in decibels, by moving in log2 of the volume multiplier
+    gain: Smoother,
+    /// Smooths pan changes linearly between -1 for left and 1 for right
+    pan: Smoother,
+    /// The gains of the left and right channel at the current pan
+    pan_gains: [f32; 2],
+    smoothing: Smoothing,
     wavetable: WaveTable,
+    envelope: EnvelopeGenerator,
     time: f32,
+    /// The ratio of the frequency to the global frequency the oscillator is hard synced to, if any
+    sync_ratio: Option<f32>,
+    /// The phase in periods of the global frequency the oscillator is synced to
+    sync_phase: f32,
 }
 
 impl WaveTableOscillator {
@@ -86,23 +290,121 @@ impl WaveTableOscillator {
         frequency: SharedFrequency,
         volume_multiplier: SharedVolumeMultiplier,
         wavetable: WaveTable,
+        envelope: Envelope,
+        smoothing: Smoothing,
     ) -> Self {
+        let targets = (frequency.get(), volume_multiplier.get());
         Self {
-            _sample_rate: sample_rate,
+            sample_rate,
             sample_rate_recip: (sample_rate as f32).recip(),
             frequency,
             volume_multiplier,
+            targets,
+            pitch: Smoother::new(targets.0.max(f32::MIN_POSITIVE).log2()),
+            gain: Smoother::new(to_log_gain(targets.1)),
+            pan: Smoother::new(0.0),
+            pan_gains: pan_gains(0.0),
+            smoothing,
             wavetable,
+            envelope: EnvelopeGenerator::new(sample_rate, envelope),
             time: 0f32,
+            sync_ratio: None,
+            sync_phase: 0f32,
         }
     }
 
+    pub fn set_smoothing(&mut self, smoothing: Smoothing) {
+        self.smoothing = smoothing;
+    }
+
+    /// Start smoothing towards the shared frequency and volume if they have changed.
+    /// A silent oscillator jumps to them directly, so it doesn't glide when it starts playing again
+    fn update_targets(&mut self) {
+        let (frequency, volume_multiplier) = (self.frequency.get(), self.volume_multiplier.get());
+        let samples = |time: f32| {
+            if self.envelope.is_idle() {
+                0
+            } else {
+                (time.max(0.0) * self.sample_rate as f32) as usize
+            }
+        };
+        if frequency != self.targets.0 {
+            let samples = samples(self.smoothing.frequency_time());
+            self.pitch
+                .set_target(frequency.max(f32::MIN_POSITIVE).log2(), samples);
+        }
+        if volume_multiplier != self.targets.1 {
+            let samples = samples(self.smoothing.time);
+            self.gain
+                .set_target(to_log_gain(volume_multiplier), samples);
+        }
+        self.targets = (frequency, volume_multiplier);
+    }
+
+    /// Pan the oscillator from -1 for left to 1 for right, smoothed like volume changes
+    pub fn set_pan(&mut self, pan: f32) {
+        let samples = if self.envelope.is_idle() {
+            0
+        } else {
+            (self.smoothing.time.max(0.0) * self.sample_rate as f32) as usize
+        };
+        self.pan.set_target(pan.clamp(-1.0, 1.0), samples);
+        if !self.pan.is_moving() {
+            self.pan_gains = pan_gains(self.pan.next_value());
+        }
+    }
+
+    /// Advance the oscillator by one sample and get it panned into a left and right sample
+    pub fn next_frame(&mut self) -> [f32; 2] {
+        let sample = self.next().unwrap_or(0.0);
+        if self.pan.is_moving() {
+            self.pan_gains = pan_gains(self.pan.next_value());
+        }
+        self.pan_gains.map(|gain| sample * gain)
+    }
+
+    /// Hard sync the oscillator to a global frequency, given as the ratio of the frequency to it, or stop syncing with None
+    pub fn set_sync(&mut self, ratio: Option<f32>) {
+        self.sync_ratio = ratio.filter(|ratio| *ratio > 0.0);
+    }
+
+    /// Restart the waveform and the period of the synced global frequency at phase zero
+    pub fn reset_phase(&mut self) {
+        self.time = 0f32;
+        self.sync_phase = 0f32;
+    }
+
     //pub fn set_frequency(&mut self, new_frequency: f32) {
     //    self.frequency = new_frequency;
     //}
 
-    pub fn set_wavetable(&mut self, wavetable: WaveTable) {
-        self.wavetable = wavetable;
+    /// Play another wavetable, returning the one it replaces
+    pub fn set_wavetable(&mut self, wavetable: WaveTable) -> WaveTable {
+        std::mem::replace(&mut self.wavetable, wavetable)
+    }
+
+    pub fn set_envelope(&mut self, envelope: Envelope) {
+        self.envelope.set_envelope(envelope);
+    }
+
+    /// Start the attack of the envelope
+    pub fn note_on(&mut self) {
+        self.envelope.note_on();
+    }
+
+    /// Start the release of the envelope
+    pub fn note_off(&mut self) {
+        self.envelope.note_off();
+    }
+
+    /// Whether the note of the oscillator is held, so it hasn't started its release
+    pub fn is_held(&self) -> bool {
+        self.envelope.is_held()
+    }
+
+    /// Whether the oscillator is silent because its envelope has fully released
+    pub fn is_silent(&self) -> bool {
+        self.envelope.is_idle()
     }
 }
 
@@ -110,11 +412,37 @@ impl Iterator for WaveTableOscillator {
     type Item = f32;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let sample = self
-            .wavetable
-            .get_interpolated_value(self.time * (WAVETABLE_SIZE as f32));
-        self.time += self.frequency.get() * self.sample_rate_recip;
+        self.update_targets();
+        if self.envelope.is_idle() {
+            return Some(0.0);
+        }
+        // only leave the log domain while smoothing, since the targets are known otherwise
+        let frequency = if self.pitch.is_moving() {
+            self.pitch.next_value().exp2()
+        } else {
+            self.targets.0
+        };
+        let volume_multiplier = if self.gain.is_moving() {
+            from_log_gain(self.gain.next_value())
+        } else {
+            self.targets.1
+        };
+
+        let phase_increment = frequency * self.sample_rate_recip;
+        let sample = self.wavetable.get_interpolated_value(
+            self.wavetable.table_index(phase_increment),
+            self.time * (WAVETABLE_SIZE as f32),
+        );
+        self.time += phase_increment;
         self.time %= WAVETABLE_SIZE as f32;
-        Some(sample * self.volume_multiplier.get())
+        if let Some(ratio) = self.sync_ratio {
+            self.sync_phase += phase_increment / ratio;
+            if self.sync_phase >= 1.0 {
+                // restart together with the global frequency, keeping the part of the sample past its period
+                self.sync_phase %= 1.0;
+                self.time = self.sync_phase * ratio;
+            }
+        }
+        Some(sample * volume_multiplier * self.envelope.next_level())
     }
 }
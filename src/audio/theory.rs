@@ -37,6 +37,24 @@ impl NoteName {
         }
     }
 
+    /// Get the number of half steps from C, the inverse of [NoteName::from_index]
+    pub fn index(&self) -> isize {
+        match self {
+            NoteName::C => 0,
+            NoteName::CSharp => 1,
+            NoteName::D => 2,
+            NoteName::DSharp => 3,
+            NoteName::E => 4,
+            NoteName::F => 5,
+            NoteName::FSharp => 6,
+            NoteName::G => 7,
+            NoteName::GSharp => 8,
+            NoteName::A => 9,
+            NoteName::ASharp => 10,
+            NoteName::B => 11,
+        }
+    }
+
     pub fn get_str(&self) -> &'static str {
         match self {
             NoteName::C => "C",
@@ -78,6 +96,17 @@ impl Note {
             cent_offset,
         }
     }
+
+    /// Get the MIDI note number of the closest note, where C4 is 60. It is outside 0 to 127 for
+    /// frequencies MIDI can't play
+    pub fn midi_note(&self) -> i32 {
+        (self.octave as i32 + 1) * 12 + self.note_name.index() as i32
+    }
+
+    /// Get how many cents the frequency is above the closest note, from -50 to 50
+    pub fn cent_offset(&self) -> f32 {
+        self.cent_offset
+    }
 }
 
 impl Display for Note {
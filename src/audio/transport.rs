@@ -0,0 +1,63 @@
+use std::collections::BTreeSet;
+
+use super::engine::SharedFrequency;
+
+/// A step of a timeline resolved to the oscillators of an engine, so it can be played on the audio thread
+pub struct TransportStep {
+    /// The duration of the step in frames, which is at least one
+    pub frames: usize,
+    /// The frequency every shared frequency is set to when the step starts
+    pub frequencies: Vec<(SharedFrequency, f32)>,
+    /// The ids of the oscillators which play during the step, where every other oscillator is silent
+    pub oscillators: BTreeSet<usize>,
+}
+
+/// What happened on a frame of a transport
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransportEvent {
+    /// The step with the index starts on this frame
+    StepStarted(usize),
+    /// The last step has ended
+    Finished,
+}
+
+/// Moves through the steps of a timeline one frame at a time, so steps start on the exact frame they should
+pub struct Transport {
+    steps: Vec<TransportStep>,
+    looping: bool,
+    step: usize,
+    /// The frame within the current step
+    frame: usize,
+}
+
+impl Transport {
+    pub fn new(steps: Vec<TransportStep>, looping: bool) -> Self {
+        Self {
+            steps,
+            looping,
+            step: 0,
+            frame: 0,
+        }
+    }
+
+    pub fn step(&self, index: usize) -> Option<&TransportStep> {
+        self.steps.get(index)
+    }
+
+    /// Advance by a frame, which may start the next step or finish the timeline
+    pub fn advance(&mut self) -> Option<TransportEvent> {
+        let Some(step) = self.steps.get(self.step) else {
+            return Some(TransportEvent::Finished);
+        };
+        let event = (self.frame == 0).then_some(TransportEvent::StepStarted(self.step));
+        self.frame += 1;
+        if self.frame >= step.frames.max(1) {
+            self.frame = 0;
+            self.step += 1;
+            if self.looping && self.step == self.steps.len() {
+                self.step = 0;
+            }
+        }
+        event
+    }
+}
@@ -0,0 +1,211 @@
+use std::{
+    io::{self, BufRead},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use color_eyre::eyre::{Context, Result, bail, eyre};
+use harmony_playground::{
+    audio::{
+        engine::{AudioEngine, EngineControl},
+        output::{AudioOutput, DEFAULT_SAMPLE_RATE},
+        render::{SampleFormat, render_to_wav},
+        theory::Note,
+    },
+    project::StateSave,
+    settings::Settings,
+};
+
+const USAGE: &str = "\
+Usage: harmony_playground [COMMAND | <file.harm>]
+
+Starts the gui when no command is given, opening the project file if one is given.
+Projects are read in the text format if their extension is .ron.
+
+Commands:
+  play <file.harm> [-d <seconds>]
+      Play a project on the output of the settings until enter is pressed, or for the given duration
+  render <file.harm> <output.wav> [-d <seconds>] [-f 16|24|float] [-r <sample rate>]
+      Render a project to a wav file (defaults to 10 seconds of 16-bit audio at 48000 Hz)
+  info <file.harm>
+      Print the frequencies of a project
+  help
+      Print this message";
+
+/// Options shared by the cli commands
+struct Options {
+    paths: Vec<PathBuf>,
+    duration: Option<Duration>,
+    format: SampleFormat,
+    sample_rate: usize,
+}
+
+impl Options {
+    fn parse(mut args: impl Iterator<Item = String>) -> Result<Self> {
+        let mut options = Self {
+            paths: Vec::new(),
+            duration: None,
+            format: SampleFormat::default(),
+            sample_rate: DEFAULT_SAMPLE_RATE,
+        };
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| eyre!("missing value for \"{arg}\""))
+            };
+            match arg.as_str() {
+                "-d" | "--duration" => {
+                    let seconds: f32 = value()?.parse().wrap_err("invalid duration")?;
+                    options.duration =
+                        Some(Duration::try_from_secs_f32(seconds).wrap_err("invalid duration")?);
+                }
+                "-f" | "--format" => {
+                    options.format = match value()?.as_str() {
+                        "16" => SampleFormat::Int16,
+                        "24" => SampleFormat::Int24,
+                        "float" => SampleFormat::Float32,
+                        format => bail!("unknown sample format \"{format}\""),
+                    };
+                }
+                "-r" | "--sample-rate" => {
+                    options.sample_rate = value()?.parse().wrap_err("invalid sample rate")?;
+                    if options.sample_rate == 0 {
+                        bail!("the sample rate must be positive");
+                    }
+                }
+                flag if flag.starts_with('-') => bail!("unknown option \"{flag}\"\n\n{USAGE}"),
+                _ => options.paths.push(PathBuf::from(arg)),
+            }
+        }
+
+        Ok(options)
+    }
+}
+
+/// Whether the argument is a command of the cli, rather than a project for the gui to open
+pub fn is_command(arg: &str) -> bool {
+    matches!(arg, "play" | "render" | "info" | "help" | "-h" | "--help")
+}
+
+/// Run the headless command line interface with the arguments given to the program, excluding its name
+pub fn run(args: Vec<String>) -> Result<()> {
+    let mut args = args.into_iter();
+    let command = args.next().unwrap_or_default();
+    match command.as_str() {
+        "play" => play(Options::parse(args)?),
+        "render" => render(Options::parse(args)?),
+        "info" => info(Options::parse(args)?),
+        "help" | "-h" | "--help" => {
+            println!("{USAGE}");
+            Ok(())
+        }
+        command => bail!("unknown command \"{command}\"\n\n{USAGE}"),
+    }
+}
+
+/// Load a save the same way the gui does
+fn load_save(path: &Path) -> Result<StateSave> {
+    let bytes = std::fs::read(path).wrap_err_with(|| format!("failed to read {path:?}"))?;
+    StateSave::from_file(path, &bytes).wrap_err_with(|| format!("failed to load {path:?}"))
+}
+
+fn play(options: Options) -> Result<()> {
+    let [path] = options.paths.as_slice() else {
+        bail!("expected a single project file\n\n{USAGE}");
+    };
+    let save = load_save(path)?;
+
+    let settings = Settings::load().unwrap_or_else(|error| {
+        eprintln!("failed to load settings, using the defaults: {error}");
+        Settings::default()
+    });
+    let (_output, mut engine) = AudioOutput::from_settings(&settings);
+    save.initialize_engine(&mut engine)
+        .wrap_err("failed to compile the graph")?;
+    engine.play();
+
+    match options.duration {
+        Some(duration) => std::thread::sleep(duration),
+        None => {
+            println!("Playing {path:?}, press enter to stop");
+            io::stdin().lock().read_line(&mut String::new())?;
+        }
+    }
+    Ok(())
+}
+
+fn render(options: Options) -> Result<()> {
+    let [input, output] = options.paths.as_slice() else {
+        bail!("expected a project file and an output file\n\n{USAGE}");
+    };
+    let save = load_save(input)?;
+
+    let mut engine = AudioEngine::new(options.sample_rate);
+    save.initialize_engine(&mut engine)
+        .wrap_err("failed to compile the graph")?;
+    engine.play();
+
+    let duration = options.duration.unwrap_or(Duration::from_secs(10));
+    render_to_wav(&mut engine, duration, options.format, output)
+        .wrap_err_with(|| format!("failed to render to {output:?}"))?;
+
+    println!(
+        "rendered {:.2}s of {} audio to {output:?}",
+        duration.as_secs_f32(),
+        options.format
+    );
+    Ok(())
+}
+
+fn info(options: Options) -> Result<()> {
+    let [path] = options.paths.as_slice() else {
+        bail!("expected a single project file\n\n{USAGE}");
+    };
+    let save = load_save(path)?;
+
+    println!("{path:?}");
+    println!("master volume: {}", save.volume.get());
+    println!("waveform: {}", save.waveform);
+
+    println!("global variables:");
+    for (id, global_frequency) in &save.global_frequencies {
+        println!(
+            "  g{id} {}: {}",
+            global_frequency.name(),
+            global_frequency.definition()
+        );
+    }
+
+    println!("relative frequencies:");
+    for relative_frequency in &save.relative_frequencies {
+        let ratio = relative_frequency.ratio();
+        let played = match save.played_frequency(relative_frequency) {
+            Some(frequency) => format!("{frequency:.2} Hz, {}", Note::from_frequency(frequency)),
+            None => String::from("not playing"),
+        };
+        println!(
+            "  {}/{} of {}: {played}, volume {}",
+            ratio.numerator,
+            ratio.denominator,
+            relative_frequency.absolute_frequency_id(),
+            relative_frequency.volume(),
+        );
+    }
+
+    println!("graph:");
+    for (id, node) in &save.graph.nodes {
+        let inputs: Vec<String> = (0..node.kind.inputs())
+            .map(|input| match save.graph.connection(*id, input) {
+                Some(connection) => connection.from.to_string(),
+                None => String::from("-"),
+            })
+            .collect();
+        if inputs.is_empty() {
+            println!("  {id} {}", node.kind);
+        } else {
+            println!("  {id} {} <- {}", node.kind, inputs.join(", "));
+        }
+    }
+    Ok(())
+}
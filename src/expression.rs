@@ -0,0 +1,554 @@
+use std::{fmt::Display, iter::Peekable, str::CharIndices};
+
+use serde::{Deserialize, Serialize};
+
+/// The largest exponent a rational number is raised to exactly, above which powers are calculated as real numbers
+const MAX_EXACT_EXPONENT: i64 = 64;
+
+/// A number an expression evaluates to, which is kept exact as long as only rational arithmetic is used
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum Number {
+    Rational(Rational),
+    Real(f64),
+}
+
+/// A fraction in lowest terms, with a positive denominator
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Rational {
+    numerator: i64,
+    denominator: i64,
+}
+
+impl Rational {
+    /// Create the fraction in lowest terms, or None if the denominator is zero or it overflows
+    pub fn new(numerator: i64, denominator: i64) -> Option<Self> {
+        if denominator == 0 {
+            return None;
+        }
+        let divisor = gcd(numerator.unsigned_abs(), denominator.unsigned_abs()) as i64;
+        let sign = denominator.signum();
+        Some(Self {
+            numerator: (numerator / divisor).checked_mul(sign)?,
+            denominator: (denominator / divisor).checked_mul(sign)?,
+        })
+    }
+
+    pub fn integer(integer: i64) -> Self {
+        Self {
+            numerator: integer,
+            denominator: 1,
+        }
+    }
+
+    pub fn numerator(&self) -> i64 {
+        self.numerator
+    }
+
+    pub fn denominator(&self) -> i64 {
+        self.denominator
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.numerator as f64 / self.denominator as f64
+    }
+
+    fn checked_add(self, other: Self) -> Option<Self> {
+        Self::new(
+            self.numerator
+                .checked_mul(other.denominator)?
+                .checked_add(other.numerator.checked_mul(self.denominator)?)?,
+            self.denominator.checked_mul(other.denominator)?,
+        )
+    }
+
+    fn checked_mul(self, other: Self) -> Option<Self> {
+        Self::new(
+            self.numerator.checked_mul(other.numerator)?,
+            self.denominator.checked_mul(other.denominator)?,
+        )
+    }
+
+    fn recip(self) -> Option<Self> {
+        Self::new(self.denominator, self.numerator)
+    }
+
+    fn checked_neg(self) -> Option<Self> {
+        Some(Self {
+            numerator: self.numerator.checked_neg()?,
+            ..self
+        })
+    }
+
+    /// Raise the fraction to a rational power, or None if the result isn't rational or it overflows
+    fn checked_pow(self, exponent: Self) -> Option<Self> {
+        if exponent.numerator.unsigned_abs() > MAX_EXACT_EXPONENT as u64
+            || exponent.denominator > MAX_EXACT_EXPONENT
+        {
+            return None;
+        }
+        let base = if exponent.denominator == 1 {
+            self
+        } else {
+            // only roots of fractions whose numerator and denominator are perfect powers are rational
+            let root = exponent.denominator as u32;
+            if self.numerator < 0 {
+                return None;
+            }
+            Self::new(
+                integer_root(self.numerator, root)?,
+                integer_root(self.denominator, root)?,
+            )?
+        };
+        let power = exponent.numerator.unsigned_abs() as u32;
+        let result = Self::new(
+            base.numerator.checked_pow(power)?,
+            base.denominator.checked_pow(power)?,
+        )?;
+        if exponent.numerator < 0 {
+            result.recip()
+        } else {
+            Some(result)
+        }
+    }
+}
+
+impl Display for Rational {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.denominator {
+            1 => write!(f, "{}", self.numerator),
+            denominator => write!(f, "{}/{denominator}", self.numerator),
+        }
+    }
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a.max(1)
+}
+
+/// Get the root of an integer, or None if it isn't a whole number
+fn integer_root(integer: i64, root: u32) -> Option<i64> {
+    let guess = (integer as f64).powf(1.0 / root as f64).round() as i64;
+    (guess.checked_pow(root)? == integer).then_some(guess)
+}
+
+impl Number {
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            Number::Rational(rational) => rational.to_f64(),
+            Number::Real(real) => *real,
+        }
+    }
+
+    /// Get the number of a value which isn't exact, like a frequency, as a rational number if it is whole
+    pub fn from_f64(value: f64) -> Self {
+        if value.fract() == 0.0 && value.abs() < i64::MAX as f64 {
+            Number::Rational(Rational::integer(value as i64))
+        } else {
+            Number::Real(value)
+        }
+    }
+
+    fn add(self, other: Self) -> Self {
+        match (self, other) {
+            (Number::Rational(a), Number::Rational(b)) => a
+                .checked_add(b)
+                .map(Number::Rational)
+                .unwrap_or(Number::Real(a.to_f64() + b.to_f64())),
+            (a, b) => Number::Real(a.to_f64() + b.to_f64()),
+        }
+    }
+
+    fn neg(self) -> Self {
+        match self {
+            Number::Rational(rational) => rational
+                .checked_neg()
+                .map(Number::Rational)
+                .unwrap_or(Number::Real(-rational.to_f64())),
+            Number::Real(real) => Number::Real(-real),
+        }
+    }
+
+    fn mul(self, other: Self) -> Self {
+        match (self, other) {
+            (Number::Rational(a), Number::Rational(b)) => a
+                .checked_mul(b)
+                .map(Number::Rational)
+                .unwrap_or(Number::Real(a.to_f64() * b.to_f64())),
+            (a, b) => Number::Real(a.to_f64() * b.to_f64()),
+        }
+    }
+
+    fn div(self, other: Self) -> Result<Self, ExpressionError> {
+        if other.to_f64() == 0.0 {
+            return Err(ExpressionError::DivisionByZero);
+        }
+        Ok(match (self, other) {
+            (Number::Rational(a), Number::Rational(b)) => b
+                .recip()
+                .and_then(|b| a.checked_mul(b))
+                .map(Number::Rational)
+                .unwrap_or(Number::Real(a.to_f64() / b.to_f64())),
+            (a, b) => Number::Real(a.to_f64() / b.to_f64()),
+        })
+    }
+
+    fn pow(self, exponent: Self) -> Result<Self, ExpressionError> {
+        if let (Number::Rational(base), Number::Rational(exponent)) = (self, exponent) {
+            if base.numerator == 0 && exponent.numerator < 0 {
+                return Err(ExpressionError::DivisionByZero);
+            }
+            if let Some(power) = base.checked_pow(exponent) {
+                return Ok(Number::Rational(power));
+            }
+        }
+        let power = self.to_f64().powf(exponent.to_f64());
+        if power.is_finite() {
+            Ok(Number::Real(power))
+        } else {
+            Err(ExpressionError::NotANumber)
+        }
+    }
+}
+
+impl Display for Number {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Number::Rational(rational) => write!(f, "{rational}"),
+            Number::Real(real) => write!(f, "{real}"),
+        }
+    }
+}
+
+/// An expression written by the user, like `3/2 * g1` or `2^(7/12)`, with the value it was last evaluated to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Expression {
+    source: String,
+    /// The value of the last evaluation which succeeded, if any
+    value: Option<Number>,
+    /// Why the last evaluation failed, if it did
+    #[serde(skip)]
+    error: Option<ExpressionError>,
+}
+
+impl Expression {
+    /// Create an expression which is yet to be evaluated
+    pub fn new(source: String) -> Self {
+        Self {
+            source,
+            value: None,
+            error: None,
+        }
+    }
+
+    /// Set the value the expression was last evaluated to, like when it is loaded
+    pub fn with_value(mut self, value: Option<Number>) -> Self {
+        self.value = value;
+        self
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Change the source of the expression, which keeps its value until it is evaluated again
+    pub fn set_source(&mut self, source: String) {
+        self.source = source;
+    }
+
+    /// Get the value the expression was last evaluated to, if it ever succeeded
+    pub fn value(&self) -> Option<Number> {
+        self.value
+    }
+
+    /// Get why the expression couldn't be evaluated the last time, if it couldn't
+    pub fn error(&self) -> Option<&ExpressionError> {
+        self.error.as_ref()
+    }
+
+    /// Get the ids of the variables the expression references, which are none if it can't be parsed
+    pub fn references(&self) -> Vec<usize> {
+        let mut references = Vec::new();
+        if let Ok(node) = parse(&self.source) {
+            node.references(&mut references);
+        }
+        references
+    }
+
+    /// Evaluate the expression with the value of every variable it references, which must be positive.
+    /// If it fails, the value of the last evaluation is kept
+    pub fn evaluate(&mut self, variable: impl Fn(usize) -> Option<Number>) {
+        let value = parse(&self.source)
+            .and_then(|node| node.evaluate(&variable))
+            .and_then(|value| {
+                if value.to_f64() > 0.0 {
+                    Ok(value)
+                } else {
+                    Err(ExpressionError::NotPositive)
+                }
+            });
+        match value {
+            Ok(value) => {
+                self.value = Some(value);
+                self.error = None;
+            }
+            Err(error) => self.error = Some(error),
+        }
+    }
+
+    /// Fail the evaluation with an error found outside of the expression, keeping the value of the last one which succeeded
+    pub fn set_error(&mut self, error: ExpressionError) {
+        self.error = Some(error);
+    }
+}
+
+/// An error in parsing or evaluating an expression, where positions are byte offsets into it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExpressionError {
+    UnexpectedCharacter(char, usize),
+    UnexpectedEnd,
+    /// A closing parenthesis is missing for the opening one at the position
+    UnclosedParenthesis(usize),
+    /// The referenced variable doesn't exist or has no numeric value
+    UnknownVariable(usize),
+    /// The referenced variable is a frequency, which a ratio can't be calculated from
+    FrequencyReference(usize),
+    DivisionByZero,
+    /// The expression evaluates to something which isn't a finite number, like the root of a negative number
+    NotANumber,
+    /// The expression evaluates to zero or a negative number, which can't be a frequency or ratio
+    NotPositive,
+}
+
+impl Display for ExpressionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExpressionError::UnexpectedCharacter(character, position) => {
+                write!(f, "unexpected '{character}' at {position}")
+            }
+            ExpressionError::UnexpectedEnd => write!(f, "unexpected end"),
+            ExpressionError::UnclosedParenthesis(position) => {
+                write!(f, "unclosed '(' at {position}")
+            }
+            ExpressionError::UnknownVariable(id) => write!(f, "g{id} has no value"),
+            ExpressionError::FrequencyReference(id) => write!(f, "g{id} is a frequency"),
+            ExpressionError::DivisionByZero => write!(f, "division by zero"),
+            ExpressionError::NotANumber => write!(f, "not a number"),
+            ExpressionError::NotPositive => write!(f, "not positive"),
+        }
+    }
+}
+
+impl std::error::Error for ExpressionError {}
+
+/// A parsed expression
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Number(Rational),
+    /// The value of the global variable with the id
+    Variable(usize),
+    Negate(Box<Node>),
+    Add(Box<Node>, Box<Node>),
+    Subtract(Box<Node>, Box<Node>),
+    Multiply(Box<Node>, Box<Node>),
+    Divide(Box<Node>, Box<Node>),
+    Power(Box<Node>, Box<Node>),
+}
+
+impl Node {
+    fn evaluate(
+        &self,
+        variable: &impl Fn(usize) -> Option<Number>,
+    ) -> Result<Number, ExpressionError> {
+        Ok(match self {
+            Node::Number(rational) => Number::Rational(*rational),
+            Node::Variable(id) => variable(*id).ok_or(ExpressionError::UnknownVariable(*id))?,
+            Node::Negate(node) => node.evaluate(variable)?.neg(),
+            Node::Add(a, b) => a.evaluate(variable)?.add(b.evaluate(variable)?),
+            Node::Subtract(a, b) => a.evaluate(variable)?.add(b.evaluate(variable)?.neg()),
+            Node::Multiply(a, b) => a.evaluate(variable)?.mul(b.evaluate(variable)?),
+            Node::Divide(a, b) => a.evaluate(variable)?.div(b.evaluate(variable)?)?,
+            Node::Power(a, b) => a.evaluate(variable)?.pow(b.evaluate(variable)?)?,
+        })
+    }
+
+    fn references(&self, references: &mut Vec<usize>) {
+        match self {
+            Node::Number(_) => {}
+            Node::Variable(id) => references.push(*id),
+            Node::Negate(node) => node.references(references),
+            Node::Add(a, b)
+            | Node::Subtract(a, b)
+            | Node::Multiply(a, b)
+            | Node::Divide(a, b)
+            | Node::Power(a, b) => {
+                a.references(references);
+                b.references(references);
+            }
+        }
+    }
+}
+
+/// Parse an expression of numbers, variables like `g1`, parentheses and the operators `+ - * / ^`,
+/// where `^` binds tightest and is right associative
+fn parse(source: &str) -> Result<Node, ExpressionError> {
+    let mut parser = Parser {
+        characters: source.char_indices().peekable(),
+    };
+    let node = parser.sum()?;
+    match parser.next() {
+        Some((position, character)) => {
+            Err(ExpressionError::UnexpectedCharacter(character, position))
+        }
+        None => Ok(node),
+    }
+}
+
+struct Parser<'a> {
+    characters: Peekable<CharIndices<'a>>,
+}
+
+impl Parser<'_> {
+    /// Get the next character which isn't whitespace, without consuming it
+    fn peek(&mut self) -> Option<(usize, char)> {
+        while self
+            .characters
+            .next_if(|(_, character)| character.is_whitespace())
+            .is_some()
+        {}
+        self.characters.peek().copied()
+    }
+
+    fn next(&mut self) -> Option<(usize, char)> {
+        self.peek()?;
+        self.characters.next()
+    }
+
+    fn sum(&mut self) -> Result<Node, ExpressionError> {
+        let mut node = self.product()?;
+        loop {
+            node = match self.peek() {
+                Some((_, '+')) => {
+                    self.next();
+                    Node::Add(Box::new(node), Box::new(self.product()?))
+                }
+                Some((_, '-')) => {
+                    self.next();
+                    Node::Subtract(Box::new(node), Box::new(self.product()?))
+                }
+                _ => return Ok(node),
+            };
+        }
+    }
+
+    fn product(&mut self) -> Result<Node, ExpressionError> {
+        let mut node = self.unary()?;
+        loop {
+            node = match self.peek() {
+                Some((_, '*')) => {
+                    self.next();
+                    Node::Multiply(Box::new(node), Box::new(self.unary()?))
+                }
+                Some((_, '/')) => {
+                    self.next();
+                    Node::Divide(Box::new(node), Box::new(self.unary()?))
+                }
+                _ => return Ok(node),
+            };
+        }
+    }
+
+    fn unary(&mut self) -> Result<Node, ExpressionError> {
+        if let Some((_, '-')) = self.peek() {
+            self.next();
+            return Ok(Node::Negate(Box::new(self.unary()?)));
+        }
+        self.power()
+    }
+
+    fn power(&mut self) -> Result<Node, ExpressionError> {
+        let base = self.atom()?;
+        if let Some((_, '^')) = self.peek() {
+            self.next();
+            return Ok(Node::Power(Box::new(base), Box::new(self.unary()?)));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<Node, ExpressionError> {
+        match self.next() {
+            Some((position, '(')) => {
+                let node = self.sum()?;
+                match self.next() {
+                    Some((_, ')')) => Ok(node),
+                    Some((position, character)) => {
+                        Err(ExpressionError::UnexpectedCharacter(character, position))
+                    }
+                    None => Err(ExpressionError::UnclosedParenthesis(position)),
+                }
+            }
+            Some((position, 'g')) => match self.digits() {
+                Some(digits) => digits
+                    .parse()
+                    .map(Node::Variable)
+                    .map_err(|_| ExpressionError::UnexpectedCharacter('g', position)),
+                None => Err(self.unexpected()),
+            },
+            Some((position, character)) if character.is_ascii_digit() => {
+                self.number(position, character)
+            }
+            Some((position, character)) => {
+                Err(ExpressionError::UnexpectedCharacter(character, position))
+            }
+            None => Err(ExpressionError::UnexpectedEnd),
+        }
+    }
+
+    /// Parse a number with an optional decimal part, which is kept as an exact fraction
+    fn number(&mut self, position: usize, first: char) -> Result<Node, ExpressionError> {
+        let mut whole = String::from(first);
+        whole.extend(self.digits());
+        let mut decimals = String::new();
+        if self
+            .characters
+            .next_if(|(_, character)| *character == '.')
+            .is_some()
+        {
+            decimals = self.digits().ok_or_else(|| self.unexpected())?;
+        }
+        let too_long = ExpressionError::UnexpectedCharacter(first, position);
+        let numerator = format!("{whole}{decimals}")
+            .parse()
+            .map_err(|_| too_long.clone())?;
+        let denominator = 10i64
+            .checked_pow(decimals.len() as u32)
+            .ok_or(too_long.clone())?;
+        Rational::new(numerator, denominator)
+            .map(Node::Number)
+            .ok_or(too_long)
+    }
+
+    /// Consume the digits directly at the current position, or None if there are none
+    fn digits(&mut self) -> Option<String> {
+        let mut digits = String::new();
+        while let Some((_, digit)) = self
+            .characters
+            .next_if(|(_, character)| character.is_ascii_digit())
+        {
+            digits.push(digit);
+        }
+        (!digits.is_empty()).then_some(digits)
+    }
+
+    /// The error of the character at the current position, or of the end
+    fn unexpected(&mut self) -> ExpressionError {
+        match self.characters.peek() {
+            Some((position, character)) => {
+                ExpressionError::UnexpectedCharacter(*character, *position)
+            }
+            None => ExpressionError::UnexpectedEnd,
+        }
+    }
+}
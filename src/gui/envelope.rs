@@ -0,0 +1,135 @@
+use iced::{
+    Border, Element, Length,
+    alignment::Horizontal,
+    border::Radius,
+    widget::{button, checkbox, column, container, row, text, vertical_slider},
+};
+
+use crate::audio::envelope::Envelope;
+
+#[derive(Debug, Clone, Copy)]
+pub enum EnvelopeMessage {
+    AttackUpdated(f32),
+    DecayUpdated(f32),
+    SustainUpdated(f32),
+    ReleaseUpdated(f32),
+}
+
+/// A row of sliders for editing the attack, decay, sustain and release of an envelope
+pub fn view<'a>(envelope: &Envelope) -> Element<'a, EnvelopeMessage> {
+    let slider = |label, range, value, message: fn(f32) -> EnvelopeMessage, step| {
+        column![
+            text(label),
+            vertical_slider(range, value, message).step(step)
+        ]
+        .align_x(Horizontal::Center)
+        .spacing(5)
+    };
+
+    row![
+        slider(
+            "A",
+            0.0..=2.0,
+            envelope.attack,
+            EnvelopeMessage::AttackUpdated,
+            0.005
+        ),
+        slider(
+            "D",
+            0.0..=2.0,
+            envelope.decay,
+            EnvelopeMessage::DecayUpdated,
+            0.005
+        ),
+        slider(
+            "S",
+            0.0..=1.0,
+            envelope.sustain,
+            EnvelopeMessage::SustainUpdated,
+            0.01
+        ),
+        slider(
+            "R",
+            0.0..=4.0,
+            envelope.release,
+            EnvelopeMessage::ReleaseUpdated,
+            0.005
+        ),
+    ]
+    .spacing(10)
+    .height(Length::Fill)
+    .into()
+}
+
+pub fn update(envelope: &mut Envelope, message: EnvelopeMessage) {
+    match message {
+        EnvelopeMessage::AttackUpdated(attack) => envelope.attack = attack,
+        EnvelopeMessage::DecayUpdated(decay) => envelope.decay = decay,
+        EnvelopeMessage::SustainUpdated(sustain) => envelope.sustain = sustain,
+        EnvelopeMessage::ReleaseUpdated(release) => envelope.release = release,
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum EnvelopeDialogMessage {
+    /// Whether the voice overrides the project envelope
+    OverrideToggled(bool),
+    EnvelopeUpdated(EnvelopeMessage),
+    ClosePressed,
+}
+
+/// A dialog for overriding the project envelope for a single voice
+pub struct EnvelopeDialog;
+
+impl EnvelopeDialog {
+    pub fn view<'a>(envelope: Option<Envelope>) -> Element<'a, EnvelopeDialogMessage> {
+        let editor: Element<'a, EnvelopeDialogMessage> = match envelope {
+            Some(envelope) => view(&envelope).map(EnvelopeDialogMessage::EnvelopeUpdated),
+            None => text("Using the project envelope").into(),
+        };
+
+        container(
+            column![
+                checkbox("Override the project envelope", envelope.is_some())
+                    .on_toggle(EnvelopeDialogMessage::OverrideToggled),
+                container(editor).height(150).center_x(Length::Fill),
+                button("Close")
+                    .width(Length::Fill)
+                    .on_press(EnvelopeDialogMessage::ClosePressed),
+            ]
+            .spacing(10)
+            .align_x(Horizontal::Center),
+        )
+        .max_width(350)
+        .style(|theme: &iced::Theme| iced::widget::container::Style {
+            background: Some(iced::Background::Color(theme.palette().background)),
+
+            border: Border {
+                radius: Radius::new(5),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .padding(10)
+        .into()
+    }
+
+    /// Apply a message to the envelope of a voice. The project envelope is used as the starting point when overriding
+    pub fn update(
+        envelope: &mut Option<Envelope>,
+        project_envelope: Envelope,
+        message: EnvelopeDialogMessage,
+    ) {
+        match message {
+            EnvelopeDialogMessage::OverrideToggled(is_overriding) => {
+                *envelope = is_overriding.then_some(envelope.unwrap_or(project_envelope));
+            }
+            EnvelopeDialogMessage::EnvelopeUpdated(message) => {
+                if let Some(envelope) = envelope {
+                    update(envelope, message);
+                }
+            }
+            EnvelopeDialogMessage::ClosePressed => {}
+        }
+    }
+}
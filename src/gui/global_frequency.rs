@@ -1,22 +1,49 @@
+use std::collections::BTreeMap;
+
 use iced::{
     alignment::Vertical,
-    widget::{container, horizontal_space, row, text},
-    Border, Element, Length,
+    widget::{column, container, horizontal_space, pick_list, row, text, text_input},
+    Border, Color, Element, Length,
 };
 use iced_aw::number_input;
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
-/// A struct for storing the gui element representing a global frequency
+use crate::{
+    expression::{Expression, Number, Rational},
+    variables::{Definition, DefinitionKind, VariableType},
+};
+
+use super::relative_frequency::{Ratio, RatioMessage};
+
+/// The frequency a variable gets when it is picked to be a frequency
+const DEFAULT_FREQUENCY: f32 = 220.0;
+
+/// The id no global variable has, since their ids start at one
+const NO_VARIABLE: usize = 0;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+/// A struct for storing the gui element representing a global variable, which relative frequencies are
+/// played relative to when it is a frequency
 pub struct GlobalFrequency {
     /// The id of the global frequency, used for showing the user which indexed id the global frequency has
     id: usize,
-    frequency: f32,
+    /// The name shown next to the id, which may be empty
+    name: String,
+    definition: Definition,
 }
 
 impl GlobalFrequency {
     pub fn new(id: usize, frequency: f32) -> Self {
-        Self { id, frequency }
+        Self {
+            id,
+            name: String::new(),
+            definition: Definition::Frequency(frequency),
+        }
+    }
+
+    pub fn with_name(mut self, name: String) -> Self {
+        self.name = name;
+        self
     }
 
     /// Get the id of the global frequency
@@ -24,17 +51,87 @@ impl GlobalFrequency {
         self.id
     }
 
-    pub fn frequency(&self) -> f32 {
-        self.frequency
+    pub fn name(&self) -> &str {
+        &self.name
     }
 
-    pub fn view(&self) -> Element<GlobalFrequencyMessage> {
-        container(
-            row![
-                text(format!("id: {}", self.id)),
+    pub fn definition(&self) -> &Definition {
+        &self.definition
+    }
+
+    /// Replace the definition, like when it is evaluated
+    pub fn set_definition(&mut self, definition: Definition) {
+        self.definition = definition;
+    }
+
+    /// Get the frequency of the variable as last evaluated, or None if it isn't a frequency
+    pub fn frequency(&self) -> Option<f32> {
+        match self.definition {
+            Definition::Frequency(frequency) | Definition::Interval { frequency, .. } => {
+                Some(frequency)
+            }
+            Definition::Expression(ref expression) => {
+                expression.value().map(|value| value.to_f64() as f32)
+            }
+            Definition::Integer(_) | Definition::Ratio(_) => None,
+        }
+    }
+
+    /// Get the value of the variable as last evaluated, as it is used in expressions
+    pub fn number(&self) -> Option<Number> {
+        match self.definition {
+            Definition::Frequency(frequency) | Definition::Interval { frequency, .. } => {
+                Some(Number::from_f64(frequency as f64))
+            }
+            Definition::Integer(integer) => {
+                Some(Number::Rational(Rational::integer(integer as i64)))
+            }
+            Definition::Ratio(ratio) => {
+                Rational::new(ratio.numerator as i64, ratio.denominator as i64)
+                    .map(Number::Rational)
+            }
+            Definition::Expression(ref expression) => expression.value(),
+        }
+    }
+
+    /// Get the integer of the variable, or None if it isn't an integer
+    pub fn integer(&self) -> Option<u32> {
+        match self.definition {
+            Definition::Integer(integer) => Some(integer),
+            _ => None,
+        }
+    }
+
+    /// Get the ratio of the variable as last evaluated, or None if it isn't a ratio
+    pub fn ratio(&self) -> Option<Ratio> {
+        match self.definition {
+            Definition::Ratio(ratio) => Some(ratio),
+            _ => None,
+        }
+    }
+
+    /// Set the frequency of the variable if it is written out as a frequency, returning whether it is
+    pub fn set_frequency(&mut self, frequency: f32) -> bool {
+        match &mut self.definition {
+            Definition::Frequency(current) => {
+                *current = frequency;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// View the card, where the variables are those the definition can reference
+    pub fn view(
+        &self,
+        variables: &BTreeMap<usize, GlobalFrequency>,
+    ) -> Element<GlobalFrequencyMessage> {
+        let definition: Element<GlobalFrequencyMessage> = match &self.definition {
+            Definition::Frequency(frequency) => row![
+                text("Hz").size(12),
                 horizontal_space().width(Length::Fill),
                 number_input(
-                    &self.frequency,
+                    frequency,
                     1f32..=20000f32,
                     GlobalFrequencyMessage::FrequencyUpdated
                 )
@@ -42,10 +139,111 @@ impl GlobalFrequency {
                 .step(1.0),
             ]
             .align_y(Vertical::Center)
-            .spacing(10),
+            .spacing(10)
+            .into(),
+            Definition::Interval {
+                base,
+                ratio,
+                frequency,
+            } => {
+                let choices = |variable_type| {
+                    VariableChoice::all(variables, variable_type)
+                        .filter(|choice| choice.0 != Some(self.id))
+                        .collect::<Vec<VariableChoice>>()
+                };
+                row![
+                    pick_list(
+                        choices(VariableType::Frequency),
+                        variables
+                            .contains_key(base)
+                            .then_some(VariableChoice(Some(*base))),
+                        |choice| GlobalFrequencyMessage::BaseUpdated(choice.0.unwrap_or(*base))
+                    )
+                    .placeholder("base")
+                    .text_size(12)
+                    .padding([1, 5]),
+                    text("*").size(12),
+                    pick_list(
+                        choices(VariableType::Ratio),
+                        variables
+                            .contains_key(ratio)
+                            .then_some(VariableChoice(Some(*ratio))),
+                        |choice| GlobalFrequencyMessage::IntervalRatioUpdated(
+                            choice.0.unwrap_or(*ratio)
+                        )
+                    )
+                    .placeholder("ratio")
+                    .text_size(12)
+                    .padding([1, 5]),
+                    horizontal_space().width(Length::Fill),
+                    text(format!("{frequency:.2} Hz")).size(12),
+                ]
+                .align_y(Vertical::Center)
+                .spacing(5)
+                .into()
+            }
+            Definition::Integer(integer) => row![
+                horizontal_space().width(Length::Fill),
+                number_input(
+                    integer,
+                    1..=u32::MAX,
+                    GlobalFrequencyMessage::IntegerUpdated
+                )
+                .width(100),
+            ]
+            .into(),
+            Definition::Ratio(ratio) => row![
+                horizontal_space().width(Length::Fill),
+                ratio
+                    .view(&VariableChoice::terms(variables))
+                    .map(GlobalFrequencyMessage::RatioUpdated),
+            ]
+            .into(),
+            Definition::Expression(expression) => column![row![
+                text_input("g1 * 3/2", expression.source())
+                    .on_input(GlobalFrequencyMessage::ExpressionUpdated)
+                    .size(12)
+                    .padding([1, 5]),
+                text(match expression.value() {
+                    Some(value) => format!("{:.2} Hz", value.to_f64()),
+                    None => String::new(),
+                })
+                .size(12),
+            ]
+            .align_y(Vertical::Center)
+            .spacing(5),]
+            .push_maybe(expression.error().map(|error| {
+                text(error.to_string())
+                    .size(10)
+                    .color(Color::from_rgb(0.8, 0.3, 0.3))
+            }))
+            .into(),
+        };
+
+        container(
+            column![
+                row![
+                    text(format!("g{}", self.id)),
+                    text_input("name", &self.name)
+                        .on_input(GlobalFrequencyMessage::NameUpdated)
+                        .size(12)
+                        .padding([1, 5]),
+                    pick_list(
+                        DefinitionKind::ALL,
+                        Some(self.definition.kind()),
+                        GlobalFrequencyMessage::KindUpdated
+                    )
+                    .text_size(12)
+                    .padding([1, 5]),
+                ]
+                .align_y(Vertical::Center)
+                .spacing(5),
+                definition,
+            ]
+            .spacing(5),
         )
         .padding(10)
-        .width(200)
+        .width(240)
         .style(|theme: &iced::Theme| {
             iced::widget::container::Style::default().border(
                 Border::default()
@@ -60,7 +258,56 @@ impl GlobalFrequency {
     pub fn update(&mut self, message: GlobalFrequencyMessage) {
         match message {
             GlobalFrequencyMessage::FrequencyUpdated(frequency) => {
-                self.frequency = frequency;
+                self.set_frequency(frequency);
+            }
+            GlobalFrequencyMessage::NameUpdated(name) => {
+                self.name = name;
+            }
+            GlobalFrequencyMessage::KindUpdated(kind) => {
+                if kind == self.definition.kind() {
+                    return;
+                }
+                // a frequency keeps sounding the same when it is picked to be defined another way
+                let frequency = self.frequency().unwrap_or(DEFAULT_FREQUENCY);
+                self.definition = match kind {
+                    DefinitionKind::Frequency => Definition::Frequency(frequency),
+                    // the variables are picked afterwards
+                    DefinitionKind::Interval => Definition::Interval {
+                        base: NO_VARIABLE,
+                        ratio: NO_VARIABLE,
+                        frequency,
+                    },
+                    DefinitionKind::Integer => Definition::Integer(1),
+                    DefinitionKind::Ratio => Definition::Ratio(Ratio::new(1, 1)),
+                    DefinitionKind::Expression => {
+                        Definition::Expression(Expression::new(frequency.to_string()))
+                    }
+                };
+            }
+            GlobalFrequencyMessage::BaseUpdated(id) => {
+                if let Definition::Interval { base, .. } = &mut self.definition {
+                    *base = id;
+                }
+            }
+            GlobalFrequencyMessage::IntervalRatioUpdated(id) => {
+                if let Definition::Interval { ratio, .. } = &mut self.definition {
+                    *ratio = id;
+                }
+            }
+            GlobalFrequencyMessage::IntegerUpdated(integer) => {
+                if let Definition::Integer(current) = &mut self.definition {
+                    *current = integer;
+                }
+            }
+            GlobalFrequencyMessage::RatioUpdated(message) => {
+                if let Definition::Ratio(ratio) = &mut self.definition {
+                    ratio.update(message);
+                }
+            }
+            GlobalFrequencyMessage::ExpressionUpdated(source) => {
+                if let Definition::Expression(expression) = &mut self.definition {
+                    expression.set_source(source);
+                }
             }
         }
     }
@@ -69,6 +316,48 @@ impl GlobalFrequency {
 #[derive(Debug, Clone)]
 pub enum GlobalFrequencyMessage {
     FrequencyUpdated(f32),
+    NameUpdated(String),
+    KindUpdated(DefinitionKind),
+    /// The frequency variable an interval multiplies was picked
+    BaseUpdated(usize),
+    /// The ratio variable an interval multiplies by was picked
+    IntervalRatioUpdated(usize),
+    IntegerUpdated(u32),
+    RatioUpdated(RatioMessage),
+    ExpressionUpdated(String),
+}
+
+/// A choice of global variable to reference, where None writes a number out instead
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VariableChoice(pub Option<usize>);
+
+impl VariableChoice {
+    /// Every variable of a type as a choice
+    pub fn all(
+        variables: &BTreeMap<usize, GlobalFrequency>,
+        variable_type: VariableType,
+    ) -> impl Iterator<Item = VariableChoice> + '_ {
+        variables
+            .iter()
+            .filter(move |(_, variable)| variable.definition.variable_type() == variable_type)
+            .map(|(id, _)| VariableChoice(Some(*id)))
+    }
+
+    /// The choices of the numerator or denominator of a ratio, which are a number or an integer variable
+    pub fn terms(variables: &BTreeMap<usize, GlobalFrequency>) -> Vec<VariableChoice> {
+        std::iter::once(VariableChoice(None))
+            .chain(Self::all(variables, VariableType::Integer))
+            .collect()
+    }
+}
+
+impl std::fmt::Display for VariableChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Some(id) => write!(f, "g{id}"),
+            None => write!(f, "#"),
+        }
+    }
 }
 
 //#[derive(Clone)]
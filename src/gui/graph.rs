@@ -0,0 +1,514 @@
+use iced::{
+    Alignment::Center,
+    Border, Element, Length, Point, Rectangle, Renderer, Size, Theme, Vector,
+    alignment::{Horizontal, Vertical},
+    mouse,
+    widget::{
+        button, canvas,
+        canvas::{Event, Frame, Geometry, Path, Stroke, Text},
+        column, container, horizontal_space, pick_list, row, text,
+    },
+};
+use iced_aw::number_input;
+
+use crate::{
+    audio::graph::{Connection, Filter, FilterMode, Graph, GraphError, NodeKind},
+    icon,
+};
+
+use super::{envelope as envelope_editor, icon_button};
+
+const NODE_WIDTH: f32 = 130.0;
+const HEADER_HEIGHT: f32 = 20.0;
+/// The vertical distance between the inputs of a node
+const PORT_SPACING: f32 = 18.0;
+const PORT_RADIUS: f32 = 5.0;
+
+#[derive(Debug, Clone, Copy)]
+pub enum GraphMessage {
+    /// Add a node of the given kind next to the others
+    NodeAdded(NodeKind),
+    NodeDeleted(usize),
+    /// Show the parameters of a node, or of none
+    NodeSelected(Option<usize>),
+    /// A node was dragged to a new position
+    NodeMoved(usize, f32, f32),
+    NodeUpdated(usize, NodeKind),
+    Connected(Connection),
+    /// Remove the connection of an input, given as the id of the node and the index of the input
+    Disconnected(usize, usize),
+}
+
+/// Apply a message to the graph, leaving it unchanged if a connection would make a cycle
+pub fn update(graph: &mut Graph, message: GraphMessage) -> Result<(), GraphError> {
+    match message {
+        GraphMessage::NodeAdded(kind) => {
+            // new nodes are stacked diagonally, so they don't hide each other
+            let offset = (graph.nodes.len() % 8) as f32 * 15.0;
+            graph.add_node(kind, 160.0 + offset, 10.0 + offset);
+        }
+        GraphMessage::NodeDeleted(id) => graph.remove_node(id),
+        GraphMessage::NodeSelected(_) => {}
+        GraphMessage::NodeMoved(id, x, y) => {
+            if let Some(node) = graph.nodes.get_mut(&id) {
+                node.x = x;
+                node.y = y;
+            }
+        }
+        GraphMessage::NodeUpdated(id, kind) => graph.set_kind(id, kind),
+        GraphMessage::Connected(connection) => graph.connect(connection)?,
+        GraphMessage::Disconnected(to, input) => graph.disconnect(to, input),
+    }
+    Ok(())
+}
+
+/// A node editor of the graph next to the parameters of the selected node
+pub fn view(graph: &Graph, selected: Option<usize>) -> Element<GraphMessage> {
+    let add_buttons = row(NodeKind::addable().into_iter().map(|kind| {
+        button(text(kind.to_string()).size(10))
+            .padding([1, 5])
+            .style(button::secondary)
+            .on_press(GraphMessage::NodeAdded(kind))
+            .into()
+    }))
+    .spacing(5);
+
+    let parameters = match selected.and_then(|id| Some((id, graph.nodes.get(&id)?.kind))) {
+        Some((id, kind)) => column![
+            row![
+                text(format!("{kind} {id}")),
+                horizontal_space().width(Length::Fill),
+            ]
+            .push_maybe((kind != NodeKind::Output).then(|| {
+                icon_button(icon::cancel(), 10)
+                    .on_press(GraphMessage::NodeDeleted(id))
+                    .style(button::danger)
+            }))
+            .align_y(Center),
+            parameters_view(kind).map(move |kind| GraphMessage::NodeUpdated(id, kind)),
+        ]
+        .spacing(5)
+        .into(),
+        None => Element::from(text("Select a node to edit it").size(12)),
+    };
+
+    column![
+        row![
+            text("Sound"),
+            horizontal_space().width(Length::Fill),
+            add_buttons
+        ]
+        .spacing(10)
+        .align_y(Center),
+        row![
+            container(
+                canvas(Editor { graph, selected })
+                    .width(Length::Fill)
+                    .height(Length::Fill)
+            )
+            .style(|theme: &Theme| {
+                container::Style::default().border(
+                    Border::default()
+                        .width(1)
+                        .rounded(2)
+                        .color(theme.palette().background.inverse().scale_alpha(0.1)),
+                )
+            }),
+            container(parameters).width(220).height(Length::Fill),
+        ]
+        .spacing(10),
+    ]
+    .spacing(5)
+    .into()
+}
+
+/// The widgets for editing the parameters of a node, producing the node with the new parameters
+fn parameters_view<'a>(kind: NodeKind) -> Element<'a, NodeKind> {
+    let labeled = |label, input: Element<'a, NodeKind>| {
+        row![
+            text(label).size(12),
+            horizontal_space().width(Length::Fill),
+            input
+        ]
+        .spacing(10)
+        .align_y(Vertical::Center)
+    };
+
+    match kind {
+        NodeKind::Oscillator => text("Every voice, panned and summed").size(12).into(),
+        NodeKind::Gain(gain) => labeled(
+            "Gain",
+            number_input(&gain, -24f32..=12f32, NodeKind::Gain)
+                .step(0.5)
+                .width(100)
+                .into(),
+        )
+        .into(),
+        NodeKind::Mixer(inputs) => labeled(
+            "Inputs",
+            number_input(&inputs, 1..=8, NodeKind::Mixer)
+                .width(100)
+                .into(),
+        )
+        .into(),
+        NodeKind::Filter(filter) => column![
+            labeled(
+                "Mode",
+                pick_list(FilterMode::ALL, Some(filter.mode), move |mode| {
+                    NodeKind::Filter(Filter { mode, ..filter })
+                })
+                .text_size(12)
+                .padding([1, 5])
+                .into(),
+            ),
+            labeled(
+                "Cutoff Hz",
+                number_input(&filter.cutoff, 10f32..=20000f32, move |cutoff| {
+                    NodeKind::Filter(Filter { cutoff, ..filter })
+                })
+                .step(10.0)
+                .width(100)
+                .into(),
+            ),
+            labeled(
+                "Resonance",
+                number_input(&filter.resonance, 0.1f32..=20f32, move |resonance| {
+                    NodeKind::Filter(Filter {
+                        resonance,
+                        ..filter
+                    })
+                })
+                .step(0.1)
+                .width(100)
+                .into(),
+            ),
+        ]
+        .spacing(5)
+        .into(),
+        NodeKind::Envelope(envelope) => envelope_editor::view(&envelope).map(move |message| {
+            let mut envelope = envelope;
+            envelope_editor::update(&mut envelope, message);
+            NodeKind::Envelope(envelope)
+        }),
+        NodeKind::Output => text("Played at the master volume").size(12).into(),
+    }
+}
+
+/// The canvas program drawing the nodes and their connections
+struct Editor<'a> {
+    graph: &'a Graph,
+    selected: Option<usize>,
+}
+
+/// What the mouse is doing in the node editor
+#[derive(Debug, Default)]
+enum Interaction {
+    #[default]
+    None,
+    /// Dragging a node, held at an offset from its top left corner
+    Dragging {
+        node: usize,
+        grab: Vector,
+        position: Point,
+    },
+    /// Dragging a connection out of the output of a node
+    Connecting { from: usize, cursor: Point },
+}
+
+/// A part of a node under the mouse
+enum Hit {
+    Output(usize),
+    /// An input, given as the id of the node and the index of the input
+    Input(usize, usize),
+    Node(usize),
+}
+
+impl Editor<'_> {
+    /// Get the position of a node, which is where it is dragged to while it is being dragged
+    fn position(&self, interaction: &Interaction, id: usize) -> Option<Point> {
+        match interaction {
+            Interaction::Dragging { node, position, .. } if *node == id => Some(*position),
+            _ => self
+                .graph
+                .nodes
+                .get(&id)
+                .map(|node| Point::new(node.x, node.y)),
+        }
+    }
+
+    fn size(kind: NodeKind) -> Size {
+        Size::new(
+            NODE_WIDTH,
+            HEADER_HEIGHT + kind.inputs().max(1) as f32 * PORT_SPACING + 4.0,
+        )
+    }
+
+    fn input_position(position: Point, input: usize) -> Point {
+        position + Vector::new(0.0, HEADER_HEIGHT + PORT_SPACING * (input as f32 + 0.5))
+    }
+
+    fn output_position(position: Point) -> Point {
+        position + Vector::new(NODE_WIDTH, HEADER_HEIGHT + PORT_SPACING * 0.5)
+    }
+
+    /// Find the part of a node under the cursor, where ports are hit before the node they are on
+    fn hit(&self, interaction: &Interaction, cursor: Point) -> Option<Hit> {
+        let is_near = |port: Point| port.distance(cursor) <= PORT_RADIUS * 2.0;
+        // nodes drawn last are on top, so they are hit first
+        self.graph.nodes.iter().rev().find_map(|(id, node)| {
+            let position = self.position(interaction, *id)?;
+            if node.kind.has_output() && is_near(Self::output_position(position)) {
+                return Some(Hit::Output(*id));
+            }
+            if let Some(input) = (0..node.kind.inputs())
+                .find(|input| is_near(Self::input_position(position, *input)))
+            {
+                return Some(Hit::Input(*id, input));
+            }
+            Rectangle::new(position, Self::size(node.kind))
+                .contains(cursor)
+                .then_some(Hit::Node(*id))
+        })
+    }
+
+    /// A curve leaving an output to the right and entering an input from the left
+    fn wire(from: Point, to: Point) -> Path {
+        let bend = ((to.x - from.x).abs() / 2.0).max(30.0);
+        Path::new(|builder| {
+            builder.move_to(from);
+            builder.bezier_curve_to(
+                from + Vector::new(bend, 0.0),
+                to - Vector::new(bend, 0.0),
+                to,
+            );
+        })
+    }
+}
+
+impl canvas::Program<GraphMessage> for Editor<'_> {
+    type State = Interaction;
+
+    fn update(
+        &self,
+        interaction: &mut Interaction,
+        event: Event,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> (canvas::event::Status, Option<GraphMessage>) {
+        let Event::Mouse(event) = event else {
+            return (canvas::event::Status::Ignored, None);
+        };
+        let position = cursor.position_in(bounds);
+        match event {
+            mouse::Event::ButtonPressed(mouse::Button::Left) => {
+                let Some(position) = position else {
+                    return (canvas::event::Status::Ignored, None);
+                };
+                let message = match self.hit(interaction, position) {
+                    Some(Hit::Output(from)) => {
+                        *interaction = Interaction::Connecting {
+                            from,
+                            cursor: position,
+                        };
+                        None
+                    }
+                    // pulling a connection off an input moves it, so it can be connected somewhere else
+                    Some(Hit::Input(to, input)) => {
+                        self.graph.connection(to, input).map(|connection| {
+                            *interaction = Interaction::Connecting {
+                                from: connection.from,
+                                cursor: position,
+                            };
+                            GraphMessage::Disconnected(to, input)
+                        })
+                    }
+                    Some(Hit::Node(node)) => {
+                        let corner = self.position(interaction, node).unwrap_or(position);
+                        *interaction = Interaction::Dragging {
+                            node,
+                            grab: position - corner,
+                            position: corner,
+                        };
+                        Some(GraphMessage::NodeSelected(Some(node)))
+                    }
+                    None => Some(GraphMessage::NodeSelected(None)),
+                };
+                (canvas::event::Status::Captured, message)
+            }
+            mouse::Event::CursorMoved { .. } => {
+                match (interaction, cursor.position_from(bounds.position())) {
+                    (Interaction::Dragging { grab, position, .. }, Some(cursor)) => {
+                        let corner = cursor - *grab;
+                        *position = Point::new(corner.x.max(0.0), corner.y.max(0.0));
+                        (canvas::event::Status::Captured, None)
+                    }
+                    (Interaction::Connecting { cursor: end, .. }, Some(cursor)) => {
+                        *end = cursor;
+                        (canvas::event::Status::Captured, None)
+                    }
+                    _ => (canvas::event::Status::Ignored, None),
+                }
+            }
+            mouse::Event::ButtonReleased(mouse::Button::Left) => {
+                let message = match std::mem::take(interaction) {
+                    Interaction::None => return (canvas::event::Status::Ignored, None),
+                    Interaction::Dragging { node, position, .. } => {
+                        self.graph.nodes.get(&node).and_then(|moved| {
+                            (Point::new(moved.x, moved.y) != position)
+                                .then_some(GraphMessage::NodeMoved(node, position.x, position.y))
+                        })
+                    }
+                    Interaction::Connecting { from, .. } => position
+                        .and_then(|position| self.hit(&Interaction::None, position))
+                        .and_then(|hit| match hit {
+                            Hit::Input(to, input) if to != from => {
+                                Some(GraphMessage::Connected(Connection { from, to, input }))
+                            }
+                            _ => None,
+                        }),
+                };
+                (canvas::event::Status::Captured, message)
+            }
+            _ => (canvas::event::Status::Ignored, None),
+        }
+    }
+
+    fn draw(
+        &self,
+        interaction: &Interaction,
+        renderer: &Renderer,
+        theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<Geometry> {
+        let mut frame = Frame::new(renderer, bounds.size());
+        let palette = theme.extended_palette();
+        let wire_stroke = Stroke::default()
+            .with_width(2.0)
+            .with_color(palette.primary.base.color);
+
+        for connection in &self.graph.connections {
+            let (Some(from), Some(to)) = (
+                self.position(interaction, connection.from),
+                self.position(interaction, connection.to),
+            ) else {
+                continue;
+            };
+            frame.stroke(
+                &Self::wire(
+                    Self::output_position(from),
+                    Self::input_position(to, connection.input),
+                ),
+                wire_stroke,
+            );
+        }
+        if let Interaction::Connecting { from, cursor } = interaction
+            && let Some(from) = self.position(interaction, *from)
+        {
+            frame.stroke(
+                &Self::wire(Self::output_position(from), *cursor),
+                wire_stroke,
+            );
+        }
+
+        for (id, node) in &self.graph.nodes {
+            let Some(position) = self.position(interaction, *id) else {
+                continue;
+            };
+            let size = Self::size(node.kind);
+            let is_selected = self.selected == Some(*id);
+            let body = Path::rounded_rectangle(position, size, 4.0.into());
+            frame.fill(&body, palette.background.weak.color);
+            frame.stroke(
+                &body,
+                Stroke::default()
+                    .with_width(if is_selected { 2.0 } else { 1.0 })
+                    .with_color(if is_selected {
+                        palette.primary.strong.color
+                    } else {
+                        palette.background.strong.color
+                    }),
+            );
+            frame.fill_text(Text {
+                content: format!("{} {id}", node.kind),
+                position: position + Vector::new(8.0, HEADER_HEIGHT / 2.0),
+                color: palette.background.weak.text,
+                size: 12.into(),
+                vertical_alignment: Vertical::Center,
+                ..Text::default()
+            });
+            frame.fill_text(Text {
+                content: summary(node.kind),
+                position: position
+                    + Vector::new(NODE_WIDTH - 12.0, HEADER_HEIGHT + PORT_SPACING / 2.0),
+                color: palette.background.weak.text.scale_alpha(0.7),
+                size: 10.into(),
+                horizontal_alignment: Horizontal::Right,
+                vertical_alignment: Vertical::Center,
+                ..Text::default()
+            });
+
+            let port_color = |is_connected: bool| {
+                if is_connected {
+                    palette.primary.base.color
+                } else {
+                    palette.background.strong.color
+                }
+            };
+            for input in 0..node.kind.inputs() {
+                frame.fill(
+                    &Path::circle(Self::input_position(position, input), PORT_RADIUS),
+                    port_color(self.graph.connection(*id, input).is_some()),
+                );
+            }
+            if node.kind.has_output() {
+                let is_connected = self
+                    .graph
+                    .connections
+                    .iter()
+                    .any(|connection| connection.from == *id);
+                frame.fill(
+                    &Path::circle(Self::output_position(position), PORT_RADIUS),
+                    port_color(is_connected),
+                );
+            }
+        }
+
+        vec![frame.into_geometry()]
+    }
+
+    fn mouse_interaction(
+        &self,
+        interaction: &Interaction,
+        bounds: Rectangle,
+        cursor: mouse::Cursor,
+    ) -> mouse::Interaction {
+        match interaction {
+            Interaction::Dragging { .. } => mouse::Interaction::Grabbing,
+            Interaction::Connecting { .. } => mouse::Interaction::Crosshair,
+            Interaction::None => match cursor
+                .position_in(bounds)
+                .and_then(|position| self.hit(interaction, position))
+            {
+                Some(Hit::Output(_) | Hit::Input(_, _)) => mouse::Interaction::Pointer,
+                Some(Hit::Node(_)) => mouse::Interaction::Grab,
+                None => mouse::Interaction::default(),
+            },
+        }
+    }
+}
+
+/// A short description of the parameters of a node, shown on it in the node editor
+fn summary(kind: NodeKind) -> String {
+    match kind {
+        NodeKind::Oscillator => String::from("voices"),
+        NodeKind::Gain(gain) => format!("x{:.2}", gain.exp2()),
+        NodeKind::Mixer(inputs) => format!("{inputs} inputs"),
+        NodeKind::Filter(filter) => format!("{} {:.0} Hz", filter.mode, filter.cutoff),
+        NodeKind::Envelope(envelope) => format!(
+            "{:.2} {:.2} {:.2} {:.2}",
+            envelope.attack, envelope.decay, envelope.sustain, envelope.release
+        ),
+        NodeKind::Output => String::new(),
+    }
+}
@@ -0,0 +1,129 @@
+use iced::{
+    Border, Element, Length,
+    alignment::Horizontal,
+    border::Radius,
+    widget::{
+        button, column, container, row, scrollable,
+        scrollable::{Direction, Scrollbar},
+        text, vertical_slider,
+    },
+};
+use iced_aw::number_input;
+
+use crate::audio::synthesizer::Harmonic;
+
+/// The most harmonics which can be edited
+const MAX_EDITABLE_HARMONICS: usize = 32;
+
+/// The harmonics of the custom waveform of a new project, a saw wave with its first eight harmonics
+pub fn default_harmonics() -> Vec<Harmonic> {
+    (1..=8)
+        .map(|n| Harmonic::new((n as f32).recip(), 0.0))
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum HarmonicsMessage {
+    AmplitudeUpdated(usize, f32),
+    PhaseUpdated(usize, f32),
+    CountUpdated(usize),
+}
+
+/// A row of bars for editing the amplitude and phase of every harmonic, from the fundamental upwards
+pub fn view<'a>(harmonics: &[Harmonic]) -> Element<'a, HarmonicsMessage> {
+    let bars = row(harmonics.iter().enumerate().map(|(index, harmonic)| {
+        column![
+            text(index + 1).size(10),
+            vertical_slider(0.0..=1.0, harmonic.amplitude, move |amplitude| {
+                HarmonicsMessage::AmplitudeUpdated(index, amplitude)
+            })
+            .step(0.01)
+            .height(120),
+            vertical_slider(0.0..=1.0, harmonic.phase, move |phase| {
+                HarmonicsMessage::PhaseUpdated(index, phase)
+            })
+            .step(0.01)
+            .height(50),
+        ]
+        .align_x(Horizontal::Center)
+        .spacing(5)
+        .width(20)
+        .into()
+    }))
+    .spacing(2);
+
+    column![
+        row![
+            text("Harmonics"),
+            number_input(
+                &harmonics.len(),
+                1..=MAX_EDITABLE_HARMONICS,
+                HarmonicsMessage::CountUpdated
+            )
+            .width(50),
+        ]
+        .spacing(10),
+        scrollable(bars).direction(Direction::Horizontal(Scrollbar::new())),
+        text("Amplitude above, phase below").size(10),
+    ]
+    .align_x(Horizontal::Center)
+    .spacing(10)
+    .into()
+}
+
+pub fn update(harmonics: &mut Vec<Harmonic>, message: HarmonicsMessage) {
+    match message {
+        HarmonicsMessage::AmplitudeUpdated(index, amplitude) => {
+            if let Some(harmonic) = harmonics.get_mut(index) {
+                harmonic.amplitude = amplitude;
+            }
+        }
+        HarmonicsMessage::PhaseUpdated(index, phase) => {
+            if let Some(harmonic) = harmonics.get_mut(index) {
+                harmonic.phase = phase;
+            }
+        }
+        HarmonicsMessage::CountUpdated(count) => {
+            harmonics.resize(
+                count.clamp(1, MAX_EDITABLE_HARMONICS),
+                Harmonic::new(0.0, 0.0),
+            );
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum HarmonicsDialogMessage {
+    HarmonicsUpdated(HarmonicsMessage),
+    ClosePressed,
+}
+
+/// A dialog for designing the custom waveform of a project
+pub struct HarmonicsDialog;
+
+impl HarmonicsDialog {
+    pub fn view<'a>(harmonics: &[Harmonic]) -> Element<'a, HarmonicsDialogMessage> {
+        container(
+            column![
+                view(harmonics).map(HarmonicsDialogMessage::HarmonicsUpdated),
+                button("Close")
+                    .width(Length::Fill)
+                    .on_press(HarmonicsDialogMessage::ClosePressed),
+            ]
+            .spacing(10)
+            .align_x(Horizontal::Center),
+        )
+        .max_width(700)
+        .style(|theme: &iced::Theme| iced::widget::container::Style {
+            background: Some(iced::Background::Color(theme.palette().background)),
+
+            border: Border {
+                radius: Radius::new(5),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .padding(10)
+        .into()
+    }
+}
@@ -0,0 +1,194 @@
+use iced::{
+    Border, Element, Length,
+    alignment::{Horizontal, Vertical},
+    border::Radius,
+    widget::{
+        button, checkbox, column, container, horizontal_space, pick_list, row, scrollable, text,
+    },
+};
+use iced_aw::number_input;
+
+use crate::midi::{
+    self, MAX_BEND_RANGE, MAX_MEMBER_CHANNELS, MAX_NOTE, MidiMapping, MidiOutputSettings, MpeZone,
+};
+
+use super::relative_frequency::RelativeFrequency;
+
+/// A dialog for enabling MIDI input and mapping notes to voices and global frequencies,
+/// and for sending the voices to a MIDI output
+pub struct MidiDialog;
+
+#[derive(Clone, Debug)]
+pub enum MidiDialogMessage {
+    InputToggled(bool),
+    TransposedFrequencyUpdated(FrequencyChoice),
+    ReferenceNoteUpdated(u8),
+    /// The MIDI note of the relative frequency with the id was picked
+    VoiceNoteUpdated(usize, NoteChoice),
+    OutputToggled(bool),
+    ZoneUpdated(MpeZone),
+    MemberChannelsUpdated(u8),
+    BendRangeUpdated(u8),
+    ClosePressed,
+}
+
+impl MidiDialog {
+    pub fn view<'a>(
+        is_input_enabled: bool,
+        mapping: &MidiMapping,
+        is_output_enabled: bool,
+        output_settings: &MidiOutputSettings,
+        global_frequency_ids: impl Iterator<Item = usize>,
+        relative_frequencies: impl Iterator<Item = (usize, &'a RelativeFrequency)>,
+    ) -> Element<'a, MidiDialogMessage> {
+        let frequency_choices: Vec<FrequencyChoice> = std::iter::once(FrequencyChoice(None))
+            .chain(global_frequency_ids.map(Some).map(FrequencyChoice))
+            .collect();
+        let note_choices: Vec<NoteChoice> = std::iter::once(NoteChoice(None))
+            .chain((0..=MAX_NOTE).map(Some).map(NoteChoice))
+            .collect();
+
+        let voices = column(relative_frequencies.map(|(id, relative_frequency)| {
+            let ratio = relative_frequency.ratio();
+            row![
+                text(format!(
+                    "{}/{} of {}",
+                    ratio.numerator,
+                    ratio.denominator,
+                    relative_frequency.absolute_frequency_id()
+                )),
+                horizontal_space().width(Length::Fill),
+                pick_list(
+                    note_choices.clone(),
+                    Some(NoteChoice(relative_frequency.midi_note())),
+                    move |note| MidiDialogMessage::VoiceNoteUpdated(id, note)
+                )
+                .width(120),
+            ]
+            .align_y(Vertical::Center)
+            .into()
+        }))
+        .spacing(5)
+        .padding(iced::Padding::ZERO.right(15));
+
+        container(
+            column![
+                text("MIDI"),
+                checkbox(
+                    format!("Input on the \"{}\" port", midi::PORT_NAME),
+                    is_input_enabled
+                )
+                .on_toggle(MidiDialogMessage::InputToggled),
+                row![
+                    text("Transposed frequency"),
+                    horizontal_space().width(Length::Fill),
+                    pick_list(
+                        frequency_choices,
+                        Some(FrequencyChoice(mapping.transposed_frequency)),
+                        MidiDialogMessage::TransposedFrequencyUpdated
+                    )
+                    .width(120),
+                ]
+                .align_y(Vertical::Center),
+                row![
+                    text(format!(
+                        "Reference note ({})",
+                        midi::note_name(mapping.reference_note)
+                    )),
+                    horizontal_space().width(Length::Fill),
+                    number_input(
+                        &mapping.reference_note,
+                        0..=MAX_NOTE,
+                        MidiDialogMessage::ReferenceNoteUpdated
+                    )
+                    .width(120),
+                ]
+                .align_y(Vertical::Center),
+                text("Voice notes"),
+                scrollable(voices).height(Length::Shrink),
+                checkbox(
+                    format!("Output on the \"{}\" port", midi::PORT_NAME),
+                    is_output_enabled
+                )
+                .on_toggle(MidiDialogMessage::OutputToggled),
+                row![
+                    text("MPE zone"),
+                    horizontal_space().width(Length::Fill),
+                    pick_list(
+                        MpeZone::ALL,
+                        Some(output_settings.zone),
+                        MidiDialogMessage::ZoneUpdated
+                    )
+                    .width(120),
+                ]
+                .align_y(Vertical::Center),
+                row![
+                    text("Member channels"),
+                    horizontal_space().width(Length::Fill),
+                    number_input(
+                        &output_settings.member_channels,
+                        1..=MAX_MEMBER_CHANNELS,
+                        MidiDialogMessage::MemberChannelsUpdated
+                    )
+                    .width(120),
+                ]
+                .align_y(Vertical::Center),
+                row![
+                    text("Bend range (semitones)"),
+                    horizontal_space().width(Length::Fill),
+                    number_input(
+                        &output_settings.bend_range,
+                        1..=MAX_BEND_RANGE,
+                        MidiDialogMessage::BendRangeUpdated
+                    )
+                    .width(120),
+                ]
+                .align_y(Vertical::Center),
+                button("Close")
+                    .width(Length::Fill)
+                    .on_press(MidiDialogMessage::ClosePressed),
+            ]
+            .spacing(10)
+            .align_x(Horizontal::Center),
+        )
+        .max_width(400)
+        .max_height(500)
+        .style(|theme: &iced::Theme| iced::widget::container::Style {
+            background: Some(iced::Background::Color(theme.palette().background)),
+
+            border: Border {
+                radius: Radius::new(5),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .padding(10)
+        .into()
+    }
+}
+
+/// A choice of global frequency to transpose, where None transposes nothing
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FrequencyChoice(pub Option<usize>);
+
+impl std::fmt::Display for FrequencyChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Some(id) => write!(f, "id: {id}"),
+            None => write!(f, "None"),
+        }
+    }
+}
+
+/// A choice of MIDI note for a voice, where None leaves it unmapped
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct NoteChoice(pub Option<u8>);
+
+impl std::fmt::Display for NoteChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Some(note) => write!(f, "{note} {}", midi::note_name(note)),
+            None => write!(f, "None"),
+        }
+    }
+}
@@ -3,10 +3,20 @@ use iced::{
     widget::{Button, Text},
 };
 
+pub mod envelope;
 pub mod global_frequency;
+pub mod graph;
+pub mod harmonics;
+pub mod midi_dialog;
+pub mod mts_dialog;
 pub mod relative_frequency;
+pub mod render_dialog;
 pub mod save_dialog;
+pub mod scope;
+pub mod settings_dialog;
+pub mod smoothing;
 pub mod theme;
+pub mod timeline;
 
 pub fn icon_button<Message>(icon: Text, size: impl Into<iced::Pixels>) -> Button<Message> {
     iced::widget::button(
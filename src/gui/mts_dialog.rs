@@ -0,0 +1,101 @@
+use iced::{
+    Border, Element, Length,
+    alignment::{Horizontal, Vertical},
+    border::Radius,
+    widget::{button, column, container, horizontal_space, radio, row, text},
+};
+use iced_aw::number_input;
+
+use crate::{midi::MAX_NOTE, mts::MtsMessage};
+
+/// The settings used when exporting the voices as MIDI Tuning Standard SysEx
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MtsDialog {
+    message: MtsMessage,
+    /// The tuning program of the synthesizer which is tuned
+    program: u8,
+}
+
+#[derive(Clone, Debug, Copy)]
+pub enum MtsDialogMessage {
+    MessageUpdated(MtsMessage),
+    ProgramUpdated(u8),
+    CancelPressed,
+    ExportPressed,
+}
+
+impl MtsDialog {
+    pub fn message(&self) -> MtsMessage {
+        self.message
+    }
+
+    pub fn program(&self) -> u8 {
+        self.program
+    }
+
+    pub fn view(&self) -> Element<MtsDialogMessage> {
+        let message_selection = column(MtsMessage::ALL.into_iter().map(|message| {
+            radio(
+                message.to_string(),
+                message,
+                Some(self.message),
+                MtsDialogMessage::MessageUpdated,
+            )
+            .into()
+        }))
+        .spacing(5);
+
+        container(
+            column![
+                text("Export MIDI tuning to syx file"),
+                message_selection,
+                row![
+                    text("Tuning program"),
+                    horizontal_space().width(Length::Fill),
+                    number_input(
+                        &self.program,
+                        0..=MAX_NOTE,
+                        MtsDialogMessage::ProgramUpdated
+                    )
+                    .width(100),
+                ]
+                .align_y(Vertical::Center),
+                row![
+                    button("Cancel")
+                        .width(Length::Fill)
+                        .on_press(MtsDialogMessage::CancelPressed),
+                    button("Export")
+                        .width(Length::Fill)
+                        .on_press(MtsDialogMessage::ExportPressed),
+                ]
+                .spacing(10)
+            ]
+            .spacing(10)
+            .align_x(Horizontal::Center),
+        )
+        .max_width(350)
+        .style(|theme: &iced::Theme| iced::widget::container::Style {
+            background: Some(iced::Background::Color(theme.palette().background)),
+
+            border: Border {
+                radius: Radius::new(5),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .padding(10)
+        .into()
+    }
+
+    pub fn update(&mut self, message: MtsDialogMessage) {
+        match message {
+            MtsDialogMessage::MessageUpdated(message) => {
+                self.message = message;
+            }
+            MtsDialogMessage::ProgramUpdated(program) => {
+                self.program = program;
+            }
+            MtsDialogMessage::CancelPressed | MtsDialogMessage::ExportPressed => {}
+        }
+    }
+}
@@ -1,22 +1,53 @@
+use std::collections::BTreeMap;
+
 use iced::{
     Alignment::Center,
     Border, Color, Element, Length,
     alignment::Horizontal,
-    widget::{button, column, container, row, text, vertical_slider, vertical_space},
+    widget::{
+        button, column, container, pick_list, row, slider, text, text_input, vertical_slider,
+        vertical_space,
+    },
 };
 use iced_aw::number_input;
 use serde::{Deserialize, Serialize};
 
-use crate::{audio::theory::Note, icon};
+use crate::{
+    audio::{
+        envelope::Envelope,
+        synthesizer::{Harmonic, WaveForm},
+        theory::Note,
+    },
+    expression::{Expression, ExpressionError, Number},
+    icon, scala,
+    variables::VariableType,
+};
 
-use super::icon_button;
+use super::{
+    global_frequency::{GlobalFrequency, VariableChoice},
+    icon_button,
+};
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 /// A struct for storing a gui element representing a frequency relative to a global frequency
 pub struct RelativeFrequency {
     absolute_frequency_id: usize,
     ratio: Ratio,
     volume: f32,
+    /// Overrides the project envelope if set
+    envelope: Option<Envelope>,
+    /// Overrides the project waveform if set
+    waveform: Option<WaveForm>,
+    /// Whether the voice restarts its period every period of its global frequency
+    hard_sync: bool,
+    /// The position in the stereo field, from -1 for left to 1 for right
+    pan: f32,
+    /// The MIDI note which gates the voice, if any
+    #[serde(default)]
+    midi_note: Option<u8>,
+    /// The expression the ratio is calculated from, if any
+    #[serde(default)]
+    expression: Option<Expression>,
 }
 
 impl RelativeFrequency {
@@ -25,6 +56,12 @@ impl RelativeFrequency {
             absolute_frequency_id,
             ratio,
             volume,
+            envelope: None,
+            waveform: None,
+            hard_sync: false,
+            pan: 0.0,
+            midi_note: None,
+            expression: None,
         }
     }
 
@@ -36,11 +73,142 @@ impl RelativeFrequency {
         self.absolute_frequency_id
     }
 
+    /// Get the ratio, which approximates the expression if it isn't rational
     pub fn ratio(&self) -> Ratio {
         self.ratio
     }
 
-    pub fn view(&self, max_id: usize, played_frequency: f32) -> Element<RelativeFrequencyMessage> {
+    /// Get the expression the ratio is calculated from, if any
+    pub fn expression(&self) -> Option<&Expression> {
+        self.expression.as_ref()
+    }
+
+    /// Get the multiplicand of the global frequency the voice is played at, which is exactly
+    /// the value of the expression if there is one
+    pub fn multiplicand(&self) -> f32 {
+        self.expression
+            .as_ref()
+            .and_then(Expression::value)
+            .map(|value| value.to_f64() as f32)
+            .unwrap_or(self.ratio.multiplicand())
+    }
+
+    /// Get the multiplicand the voice would be played at if the variables its ratio references had other values,
+    /// like while a MIDI note transposes them, leaving its own ratio unchanged
+    pub fn multiplicand_with(&self, variables: &BTreeMap<usize, GlobalFrequency>) -> f32 {
+        match &self.expression {
+            Some(expression) => {
+                let mut expression = expression.clone();
+                evaluate_expression(&mut expression, variables);
+                expression
+                    .value()
+                    .map(|value| value.to_f64() as f32)
+                    .unwrap_or(self.ratio.multiplicand())
+            }
+            None => self.ratio.evaluated(variables).multiplicand(),
+        }
+    }
+
+    /// Get the ids of the global variables the ratio references
+    pub fn references(&self) -> Vec<usize> {
+        match &self.expression {
+            Some(expression) => expression.references(),
+            None => self.ratio.references().collect(),
+        }
+    }
+
+    /// Get the envelope overriding the project envelope, if any
+    pub fn envelope(&self) -> Option<Envelope> {
+        self.envelope
+    }
+
+    /// Get the waveform overriding the project waveform, if any
+    pub fn waveform(&self) -> Option<WaveForm> {
+        self.waveform.clone()
+    }
+
+    pub fn pan(&self) -> f32 {
+        self.pan
+    }
+
+    /// Get the MIDI note which gates the voice, if any
+    pub fn midi_note(&self) -> Option<u8> {
+        self.midi_note
+    }
+
+    /// Override the project envelope, or use it with None
+    pub fn with_envelope(mut self, envelope: Option<Envelope>) -> Self {
+        self.envelope = envelope;
+        self
+    }
+
+    /// Override the project waveform, or use it with None
+    pub fn with_waveform(mut self, waveform: Option<WaveForm>) -> Self {
+        self.waveform = waveform;
+        self
+    }
+
+    pub fn with_hard_sync(mut self, hard_sync: bool) -> Self {
+        self.hard_sync = hard_sync;
+        self
+    }
+
+    pub fn with_pan(mut self, pan: f32) -> Self {
+        self.pan = pan;
+        self
+    }
+
+    /// Gate the voice with a MIDI note, or with none
+    pub fn with_midi_note(mut self, midi_note: Option<u8>) -> Self {
+        self.midi_note = midi_note;
+        self
+    }
+
+    /// Calculate the ratio from an expression, or set it directly with None
+    pub fn with_expression(mut self, expression: Option<Expression>) -> Self {
+        self.expression = expression;
+        self
+    }
+
+    /// Evaluate the integer variables the ratio references, or the expression if there is one.
+    /// The ratio is set to the value of the expression, approximated if it isn't rational.
+    /// If the expression fails, the ratio keeps the value of the last evaluation which succeeded
+    pub fn evaluate_ratio(&mut self, variables: &BTreeMap<usize, GlobalFrequency>) {
+        let Some(expression) = &mut self.expression else {
+            self.ratio = self.ratio.evaluated(variables);
+            return;
+        };
+        evaluate_expression(expression, variables);
+        if expression.error().is_some() {
+            return;
+        }
+        self.ratio = match expression.value() {
+            Some(Number::Rational(rational)) => match (
+                u32::try_from(rational.numerator()),
+                u32::try_from(rational.denominator()),
+            ) {
+                (Ok(numerator), Ok(denominator)) => Ratio::new(numerator, denominator),
+                _ => scala::approximate_ratio(rational.to_f64()),
+            },
+            Some(Number::Real(real)) => scala::approximate_ratio(real),
+            None => self.ratio,
+        };
+    }
+
+    /// Get the ratio of the played frequency to the global frequency, if the voice is hard synced to it
+    pub fn sync_ratio(&self) -> Option<f32> {
+        self.hard_sync.then(|| self.multiplicand())
+    }
+
+    /// View the card, where the custom harmonics are those of the custom waveform the voice can pick
+    /// and the integers are the choices of the numerator and denominator of the ratio
+    pub fn view(
+        &self,
+        max_id: usize,
+        played_frequency: f32,
+        custom_harmonics: &[Harmonic],
+        integers: &[VariableChoice],
+    ) -> Element<RelativeFrequencyMessage> {
         let note = Note::from_frequency(played_frequency);
 
         let delete_button = icon_button(icon::cancel(), 12)
@@ -88,18 +256,25 @@ impl RelativeFrequency {
                             .width(40),
                         ])
                         .width(75),
-                        container(
-                            row![
+                        container(match &self.expression {
+                            Some(expression) => self.expression_view(expression),
+                            None => row![
                                 text("ratio").width(Length::Shrink),
                                 iced::widget::Space::new(Length::Fill, Length::Shrink),
                                 self.ratio
-                                    .view()
+                                    .view(integers)
                                     .map(RelativeFrequencyMessage::RatioUpdated)
                             ]
                             .align_y(Center)
                             .spacing(5)
-                        )
-                        .width(75),
+                            .into(),
+                        })
+                        // the pickers of integer variables make the ratio wider
+                        .width(if integers.len() > 1 {
+                            Length::Shrink
+                        } else {
+                            Length::Fixed(75.0)
+                        }),
                     ]
                     .align_x(Center)
                     .spacing(20),
@@ -113,12 +288,55 @@ impl RelativeFrequency {
                 })
                 .color(Color::from_rgb(0.5, 0.5, 0.5))
                 .size(10),
+                pick_list(
+                    WaveFormChoice::all(custom_harmonics),
+                    Some(WaveFormChoice(self.waveform.clone())),
+                    |choice| RelativeFrequencyMessage::WaveFormUpdated(choice.0)
+                )
+                .text_size(10)
+                .padding([1, 5]),
+                row![
+                    text("L").size(10),
+                    slider(-1.0..=1.0, self.pan, RelativeFrequencyMessage::PanUpdated).step(0.01),
+                    text("R").size(10),
+                ]
+                .spacing(5)
+                .align_y(Center),
+                row![
+                    button(text("ADSR").size(10))
+                        .padding([1, 5])
+                        .on_press(RelativeFrequencyMessage::EnvelopePressed)
+                        .style(if self.envelope.is_some() {
+                            button::primary
+                        } else {
+                            button::secondary
+                        }),
+                    button(text("Sync").size(10))
+                        .padding([1, 5])
+                        .on_press(RelativeFrequencyMessage::HardSyncToggled(!self.hard_sync))
+                        .style(if self.hard_sync {
+                            button::primary
+                        } else {
+                            button::secondary
+                        }),
+                    button(text("Expr").size(10))
+                        .padding([1, 5])
+                        .on_press(RelativeFrequencyMessage::ExpressionToggled(
+                            self.expression.is_none()
+                        ))
+                        .style(if self.expression.is_some() {
+                            button::primary
+                        } else {
+                            button::secondary
+                        }),
+                ]
+                .spacing(5),
             ]
             .spacing(2)
             .align_x(Horizontal::Center),
         )
         .padding(10)
-        .height(180)
+        .height(245)
         .style(|theme: &iced::Theme| {
             iced::widget::container::Style::default().border(
                 Border::default()
@@ -130,6 +348,29 @@ impl RelativeFrequency {
         .into()
     }
 
+    /// View the expression in place of the ratio, with the error of its last evaluation below it
+    fn expression_view<'a>(
+        &self,
+        expression: &'a Expression,
+    ) -> Element<'a, RelativeFrequencyMessage> {
+        column![
+            text_input("3/2", expression.source())
+                .on_input(RelativeFrequencyMessage::ExpressionUpdated)
+                .size(12)
+                .padding([1, 3]),
+            text(format!("{:.4}", self.multiplicand()))
+                .size(10)
+                .color(Color::from_rgb(0.5, 0.5, 0.5)),
+        ]
+        .push_maybe(expression.error().map(|error| {
+            text(error.to_string())
+                .size(10)
+                .color(Color::from_rgb(0.8, 0.3, 0.3))
+        }))
+        .spacing(2)
+        .into()
+    }
+
     pub fn update(
         &mut self,
         message: RelativeFrequencyMessage,
@@ -147,7 +388,39 @@ impl RelativeFrequency {
                 self.volume = new_volume;
                 Some(RelativeFrequencyStateUpdate::VolumeUpdated)
             }
-            RelativeFrequencyMessage::Deleted => None,
+            RelativeFrequencyMessage::EnvelopeUpdated(envelope) => {
+                self.envelope = envelope;
+                Some(RelativeFrequencyStateUpdate::EnvelopeUpdated)
+            }
+            RelativeFrequencyMessage::WaveFormUpdated(waveform) => {
+                self.waveform = waveform;
+                Some(RelativeFrequencyStateUpdate::WaveFormUpdated)
+            }
+            RelativeFrequencyMessage::PanUpdated(pan) => {
+                self.pan = pan;
+                Some(RelativeFrequencyStateUpdate::PanUpdated)
+            }
+            RelativeFrequencyMessage::HardSyncToggled(hard_sync) => {
+                self.hard_sync = hard_sync;
+                Some(RelativeFrequencyStateUpdate::SyncUpdated)
+            }
+            RelativeFrequencyMessage::MidiNoteUpdated(midi_note) => {
+                self.midi_note = midi_note;
+                None
+            }
+            RelativeFrequencyMessage::ExpressionToggled(is_expression) => {
+                // the expression starts out as the ratio, and the ratio keeps the value of the expression
+                self.expression = is_expression.then(|| Expression::new(self.ratio.to_string()));
+                self.ratio = Ratio::new(self.ratio.numerator, self.ratio.denominator);
+                Some(RelativeFrequencyStateUpdate::FrequencyUpdated)
+            }
+            RelativeFrequencyMessage::ExpressionUpdated(source) => {
+                if let Some(expression) = &mut self.expression {
+                    expression.set_source(source);
+                }
+                Some(RelativeFrequencyStateUpdate::FrequencyUpdated)
+            }
+            RelativeFrequencyMessage::Deleted | RelativeFrequencyMessage::EnvelopePressed => None,
         }
     }
 }
@@ -156,6 +429,10 @@ impl RelativeFrequency {
 pub enum RelativeFrequencyStateUpdate {
     FrequencyUpdated,
     VolumeUpdated,
+    EnvelopeUpdated,
+    WaveFormUpdated,
+    SyncUpdated,
+    PanUpdated,
 }
 
 #[derive(Debug, Clone)]
@@ -163,14 +440,72 @@ pub enum RelativeFrequencyMessage {
     AbsoluteFrequencyIdUpdated(usize),
     RatioUpdated(RatioMessage),
     VolumeUpdated(f32),
+    EnvelopeUpdated(Option<Envelope>),
+    /// Open the dialog for editing the envelope
+    EnvelopePressed,
+    WaveFormUpdated(Option<WaveForm>),
+    HardSyncToggled(bool),
+    PanUpdated(f32),
+    MidiNoteUpdated(Option<u8>),
+    /// Calculate the ratio from an expression or not
+    ExpressionToggled(bool),
+    ExpressionUpdated(String),
     Deleted,
 }
 
+/// Evaluate the expression of a ratio with the values of the variables it references. A ratio can't reference
+/// a frequency, since it would make the voice depend on the frequency it is played relative to
+fn evaluate_expression(expression: &mut Expression, variables: &BTreeMap<usize, GlobalFrequency>) {
+    let frequency = expression.references().into_iter().find(|id| {
+        variables.get(id).is_some_and(|variable| {
+            variable.definition().variable_type() == VariableType::Frequency
+        })
+    });
+    match frequency {
+        Some(id) => expression.set_error(ExpressionError::FrequencyReference(id)),
+        None => expression.evaluate(|id| variables.get(&id)?.number()),
+    }
+}
+
+/// A choice in the waveform picker of a relative frequency, where None is the project waveform
+#[derive(Debug, Clone, PartialEq)]
+struct WaveFormChoice(Option<WaveForm>);
+
+impl WaveFormChoice {
+    /// Every choice, which are the project waveform, the builtin waveforms and the custom waveform
+    fn all(custom_harmonics: &[Harmonic]) -> Vec<WaveFormChoice> {
+        std::iter::once(None)
+            .chain(WaveForm::ALL.map(Some))
+            .chain(std::iter::once(Some(WaveForm::Custom(
+                custom_harmonics.to_vec(),
+            ))))
+            .map(Self)
+            .collect()
+    }
+}
+
+impl std::fmt::Display for WaveFormChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.0 {
+            Some(waveform) => write!(f, "{waveform}"),
+            None => write!(f, "Project"),
+        }
+    }
+}
+
 /// A struct for storing a mathematical ratio
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub struct Ratio {
+    /// The numerator, as last evaluated if it is an integer variable
     pub numerator: u32,
+    /// The denominator, as last evaluated if it is an integer variable
     pub denominator: u32,
+    /// The id of the integer variable the numerator is, if any
+    #[serde(default)]
+    pub numerator_variable: Option<usize>,
+    /// The id of the integer variable the denominator is, if any
+    #[serde(default)]
+    pub denominator_variable: Option<usize>,
 }
 
 impl Ratio {
@@ -178,6 +513,27 @@ impl Ratio {
         Self {
             numerator,
             denominator,
+            numerator_variable: None,
+            denominator_variable: None,
+        }
+    }
+
+    /// Get the ids of the integer variables the ratio references
+    pub fn references(&self) -> impl Iterator<Item = usize> {
+        self.numerator_variable
+            .into_iter()
+            .chain(self.denominator_variable)
+    }
+
+    /// Evaluate the integer variables the ratio references. Variables which don't exist or aren't
+    /// integers keep the value they were last evaluated to
+    pub fn evaluated(&self, variables: &BTreeMap<usize, GlobalFrequency>) -> Self {
+        let integer =
+            |variable: Option<usize>| variables.get(&variable?).and_then(GlobalFrequency::integer);
+        Self {
+            numerator: integer(self.numerator_variable).unwrap_or(self.numerator),
+            denominator: integer(self.denominator_variable).unwrap_or(self.denominator),
+            ..*self
         }
     }
 
@@ -194,31 +550,93 @@ impl Ratio {
             RatioMessage::DenominatorUpdated(n) => {
                 self.denominator = n;
             }
+            RatioMessage::NumeratorVariableUpdated(id) => {
+                self.numerator_variable = id;
+            }
+            RatioMessage::DenominatorVariableUpdated(id) => {
+                self.denominator_variable = id;
+            }
         }
     }
 
-    pub fn view(&self) -> Element<RatioMessage> {
+    /// View the ratio, where the integers are the choices of the numerator and denominator.
+    /// The choices are only shown if there are integer variables to pick
+    pub fn view(&self, integers: &[VariableChoice]) -> Element<RatioMessage> {
         column![
-            number_input(
-                &self.numerator,
-                1..=u32::MAX,
+            term(
+                self.numerator,
+                self.numerator_variable,
+                integers,
                 RatioMessage::NumeratorUpdated,
-            )
-            .width(40),
+                RatioMessage::NumeratorVariableUpdated,
+            ),
             text("-----"),
-            number_input(
-                &self.denominator,
-                1..=u32::MAX,
+            term(
+                self.denominator,
+                self.denominator_variable,
+                integers,
                 RatioMessage::DenominatorUpdated,
-            )
-            .width(40),
+                RatioMessage::DenominatorVariableUpdated,
+            ),
         ]
         .into()
     }
 }
 
+/// View the numerator or denominator of a ratio, which is a number input unless it is an integer variable
+fn term<'a>(
+    value: u32,
+    variable: Option<usize>,
+    integers: &[VariableChoice],
+    on_value: fn(u32) -> RatioMessage,
+    on_variable: fn(Option<usize>) -> RatioMessage,
+) -> Element<'a, RatioMessage> {
+    let input: Element<RatioMessage> = match variable {
+        Some(_) => text(value).width(40).into(),
+        None => number_input(&value, 1..=u32::MAX, on_value)
+            .width(40)
+            .into(),
+    };
+    if integers.len() <= 1 {
+        return input;
+    }
+    row![
+        input,
+        pick_list(
+            integers.to_vec(),
+            Some(VariableChoice(variable)),
+            move |choice| { on_variable(choice.0) }
+        )
+        .text_size(10)
+        .padding([1, 3]),
+    ]
+    .spacing(2)
+    .align_y(Center)
+    .into()
+}
+
+impl std::fmt::Display for Ratio {
+    /// Write the ratio as an expression, where the terms which are integer variables are referenced
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let term = |value: u32, variable: Option<usize>| match variable {
+            Some(id) => format!("g{id}"),
+            None => value.to_string(),
+        };
+        write!(
+            f,
+            "{}/{}",
+            term(self.numerator, self.numerator_variable),
+            term(self.denominator, self.denominator_variable)
+        )
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum RatioMessage {
     NumeratorUpdated(u32),
     DenominatorUpdated(u32),
+    /// The integer variable the numerator is was picked, where None writes it out
+    NumeratorVariableUpdated(Option<usize>),
+    /// The integer variable the denominator is was picked, where None writes it out
+    DenominatorVariableUpdated(Option<usize>),
 }
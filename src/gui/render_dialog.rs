@@ -0,0 +1,111 @@
+use iced::{
+    Border, Element, Length,
+    alignment::{Horizontal, Vertical},
+    border::Radius,
+    widget::{button, column, container, horizontal_space, radio, row, text},
+};
+use iced_aw::number_input;
+
+use crate::audio::render::SampleFormat;
+
+/// The settings used when rendering the project to a wav file
+#[derive(Debug, Clone, Copy)]
+pub struct RenderDialog {
+    /// The duration of the render in seconds
+    duration: f32,
+    format: SampleFormat,
+}
+
+#[derive(Clone, Debug, Copy)]
+pub enum RenderDialogMessage {
+    DurationUpdated(f32),
+    FormatUpdated(SampleFormat),
+    CancelPressed,
+    RenderPressed,
+}
+
+impl RenderDialog {
+    pub fn duration(&self) -> f32 {
+        self.duration
+    }
+
+    pub fn format(&self) -> SampleFormat {
+        self.format
+    }
+
+    pub fn view(&self) -> Element<RenderDialogMessage> {
+        let format_selection = column(SampleFormat::ALL.into_iter().map(|format| {
+            radio(
+                format.to_string(),
+                format,
+                Some(self.format),
+                RenderDialogMessage::FormatUpdated,
+            )
+            .into()
+        }))
+        .spacing(5);
+
+        container(
+            column![
+                text("Render to wav file"),
+                row![
+                    text("Duration (s)"),
+                    horizontal_space().width(Length::Fill),
+                    number_input(
+                        &self.duration,
+                        0.1f32..=600f32,
+                        RenderDialogMessage::DurationUpdated
+                    )
+                    .width(100)
+                    .step(1.0),
+                ]
+                .align_y(Vertical::Center),
+                format_selection,
+                row![
+                    button("Cancel")
+                        .width(Length::Fill)
+                        .on_press(RenderDialogMessage::CancelPressed),
+                    button("Render")
+                        .width(Length::Fill)
+                        .on_press(RenderDialogMessage::RenderPressed),
+                ]
+                .spacing(10)
+            ]
+            .spacing(10)
+            .align_x(Horizontal::Center),
+        )
+        .max_width(350)
+        .style(|theme: &iced::Theme| iced::widget::container::Style {
+            background: Some(iced::Background::Color(theme.palette().background)),
+
+            border: Border {
+                radius: Radius::new(5),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .padding(10)
+        .into()
+    }
+
+    pub fn update(&mut self, message: RenderDialogMessage) {
+        match message {
+            RenderDialogMessage::DurationUpdated(duration) => {
+                self.duration = duration;
+            }
+            RenderDialogMessage::FormatUpdated(format) => {
+                self.format = format;
+            }
+            RenderDialogMessage::CancelPressed | RenderDialogMessage::RenderPressed => {}
+        }
+    }
+}
+
+impl Default for RenderDialog {
+    fn default() -> Self {
+        Self {
+            duration: 10.0,
+            format: SampleFormat::default(),
+        }
+    }
+}
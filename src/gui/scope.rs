@@ -0,0 +1,249 @@
+use std::collections::BTreeMap;
+
+use iced::{
+    Alignment::Center,
+    Element, Length, Point, Rectangle, Renderer, Theme, mouse,
+    widget::{
+        canvas,
+        canvas::{Frame, Geometry, Path, Stroke},
+        checkbox, column, horizontal_space, pick_list, row, text,
+    },
+};
+
+use crate::audio::scope::ScopeSnapshot;
+
+use super::{global_frequency::GlobalFrequency, relative_frequency::RelativeFrequency};
+
+/// The amount of frames copied from the engine every time the scope is drawn, which is enough to trigger on
+/// fundamentals down to a few Hz
+pub const SNAPSHOT_FRAMES: usize = 16384;
+
+/// The amount of frames shown when there is no fundamental to show two periods of
+const DEFAULT_WINDOW: usize = 1024;
+const MIN_WINDOW: usize = 256;
+const MAX_WINDOW: usize = 4096;
+
+#[derive(Debug, Clone, Copy)]
+pub enum ScopeMessage {
+    /// Show a voice next to the mixed output, given as the id of the relative frequency, or show none
+    VoiceSelected(VoiceChoice),
+    TriggerToggled(bool),
+}
+
+/// A choice of voice shown on its own, where None shows only the mixed output
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VoiceChoice {
+    pub id: Option<usize>,
+    /// The ratio of the relative frequency and the id of its global frequency, shown in the list
+    label: Option<(u32, u32, usize)>,
+}
+
+impl VoiceChoice {
+    fn new(id: usize, relative_frequency: &RelativeFrequency) -> Self {
+        let ratio = relative_frequency.ratio();
+        Self {
+            id: Some(id),
+            label: Some((
+                ratio.numerator,
+                ratio.denominator,
+                relative_frequency.absolute_frequency_id(),
+            )),
+        }
+    }
+
+    const NONE: Self = Self {
+        id: None,
+        label: None,
+    };
+}
+
+impl std::fmt::Display for VoiceChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.label {
+            Some((numerator, denominator, global_frequency)) => {
+                write!(f, "{numerator}/{denominator} of {global_frequency}")
+            }
+            None => write!(f, "Mix only"),
+        }
+    }
+}
+
+/// Get the fundamental frequency of the voices, which every voice is a whole multiple of. Voices are only
+/// related by their ratios when they share a global frequency, so there is none for voices of several
+fn fundamental(
+    voices: &[(usize, &RelativeFrequency)],
+    global_frequencies: &BTreeMap<usize, GlobalFrequency>,
+) -> Option<f32> {
+    let (_, first) = voices.first()?;
+    let global_frequency_id = first.absolute_frequency_id();
+    if voices
+        .iter()
+        .any(|(_, voice)| voice.absolute_frequency_id() != global_frequency_id)
+    {
+        return None;
+    }
+    let frequency = global_frequencies.get(&global_frequency_id)?.frequency()?;
+    // the fundamental of the ratios n/d is gcd(n)/lcm(d) of the global frequency, where approximated
+    // irrational ratios can make the lcm too large to have one
+    let (numerator, denominator) =
+        voices
+            .iter()
+            .try_fold((0u64, 1u64), |(numerator, denominator), (_, voice)| {
+                let ratio = voice.ratio();
+                let (voice_numerator, voice_denominator) =
+                    (ratio.numerator as u64, ratio.denominator as u64);
+                Some((
+                    gcd(numerator, voice_numerator),
+                    (denominator / gcd(denominator, voice_denominator))
+                        .checked_mul(voice_denominator)?,
+                ))
+            })?;
+    (numerator != 0).then(|| frequency * numerator as f32 / denominator as f32)
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// A scope drawing the latest output of the engine, and the selected voice on top of it. When triggered, the
+/// drawing starts at a whole number of periods of the fundamental of the voices, so just intonation chords stand still
+pub fn view<'a>(
+    snapshot: &ScopeSnapshot,
+    sample_rate: usize,
+    voices: &[(usize, &RelativeFrequency)],
+    global_frequencies: &BTreeMap<usize, GlobalFrequency>,
+    selected_voice: Option<usize>,
+    is_triggered: bool,
+) -> Element<'a, ScopeMessage> {
+    let fundamental = fundamental(voices, global_frequencies);
+    let period = fundamental.map(|fundamental| sample_rate as f64 / fundamental as f64);
+    // two periods are shown, so the repetition can be seen
+    let window = period
+        .map(|period| ((period * 2.0).ceil() as usize).clamp(MIN_WINDOW, MAX_WINDOW))
+        .unwrap_or(DEFAULT_WINDOW);
+    let start = if is_triggered {
+        snapshot.trigger(period, window)
+    } else {
+        snapshot.mixed.len().checked_sub(window)
+    };
+    let (mixed, voice) = match start {
+        Some(start) => (
+            snapshot.mixed[start..start + window].to_vec(),
+            selected_voice.map(|_| snapshot.voice[start..start + window].to_vec()),
+        ),
+        None => (Vec::new(), None),
+    };
+
+    let voice_choices: Vec<VoiceChoice> = std::iter::once(VoiceChoice::NONE)
+        .chain(
+            voices
+                .iter()
+                .map(|(id, relative_frequency)| VoiceChoice::new(*id, relative_frequency)),
+        )
+        .collect();
+    let selected_choice = voice_choices
+        .iter()
+        .find(|choice| choice.id == selected_voice)
+        .copied()
+        .unwrap_or(VoiceChoice::NONE);
+
+    column![
+        row![
+            text("Scope"),
+            horizontal_space().width(Length::Fill),
+            text(match fundamental {
+                Some(fundamental) => format!("fundamental {fundamental:.2} Hz"),
+                None => String::from("no common fundamental"),
+            })
+            .size(12),
+            checkbox("Trigger", is_triggered)
+                .on_toggle(ScopeMessage::TriggerToggled)
+                .text_size(12)
+                .size(14),
+            pick_list(
+                voice_choices,
+                Some(selected_choice),
+                ScopeMessage::VoiceSelected
+            )
+            .text_size(12)
+            .padding([1, 5]),
+        ]
+        .spacing(10)
+        .align_y(Center),
+        canvas(Scope { mixed, voice })
+            .width(Length::Fill)
+            .height(Length::Fill),
+    ]
+    .spacing(5)
+    .into()
+}
+
+/// The canvas program drawing the frames of the scope
+struct Scope {
+    mixed: Vec<f32>,
+    voice: Option<Vec<f32>>,
+}
+
+impl Scope {
+    /// A line through the samples across the width of the bounds, where -1 is the bottom and 1 the top
+    fn trace(samples: &[f32], bounds: Rectangle) -> Path {
+        let step = bounds.width / (samples.len().max(2) - 1) as f32;
+        let half_height = bounds.height / 2.0;
+        Path::new(|builder| {
+            for (index, sample) in samples.iter().enumerate() {
+                let point = Point::new(
+                    index as f32 * step,
+                    half_height - sample.clamp(-1.0, 1.0) * half_height,
+                );
+                if index == 0 {
+                    builder.move_to(point);
+                } else {
+                    builder.line_to(point);
+                }
+            }
+        })
+    }
+}
+
+impl canvas::Program<ScopeMessage> for Scope {
+    type State = ();
+
+    fn draw(
+        &self,
+        _state: &(),
+        renderer: &Renderer,
+        theme: &Theme,
+        bounds: Rectangle,
+        _cursor: mouse::Cursor,
+    ) -> Vec<Geometry> {
+        let mut frame = Frame::new(renderer, bounds.size());
+        let palette = theme.extended_palette();
+        frame.stroke(
+            &Path::line(
+                Point::new(0.0, bounds.height / 2.0),
+                Point::new(bounds.width, bounds.height / 2.0),
+            ),
+            Stroke::default()
+                .with_width(1.0)
+                .with_color(palette.background.strong.color),
+        );
+        frame.stroke(
+            &Self::trace(&self.mixed, bounds),
+            Stroke::default()
+                .with_width(1.5)
+                .with_color(palette.primary.base.color),
+        );
+        if let Some(voice) = &self.voice {
+            frame.stroke(
+                &Self::trace(voice, bounds),
+                Stroke::default()
+                    .with_width(1.0)
+                    .with_color(palette.success.base.color),
+            );
+        }
+        vec![frame.into_geometry()]
+    }
+}
@@ -0,0 +1,189 @@
+use std::path::PathBuf;
+
+use iced::{
+    Border, Element, Length,
+    alignment::{Horizontal, Vertical},
+    border::Radius,
+    widget::{button, column, container, horizontal_space, pick_list, row, text},
+};
+
+use crate::{audio::output, settings::Settings};
+
+/// A dialog for choosing the output device and sample rate
+#[derive(Debug, Clone, Default)]
+pub struct SettingsDialog {
+    settings: Settings,
+    devices: Vec<String>,
+    sample_rates: Vec<u32>,
+}
+
+#[derive(Clone, Debug)]
+pub enum SettingsDialogMessage {
+    DeviceUpdated(DeviceChoice),
+    SampleRateUpdated(SampleRateChoice),
+    /// Pick the file the null backend writes to, which needs a file dialog opened by the caller
+    NullOutputFilePressed,
+    /// The file the null backend writes to was picked, or None if the file dialog was closed
+    NullOutputFileChosen(Option<PathBuf>),
+    NullOutputFileCleared,
+    CancelPressed,
+    ApplyPressed,
+}
+
+impl SettingsDialog {
+    /// Open the dialog on the current settings, listing the devices and sample rates which are available right now
+    pub fn new(settings: Settings) -> Self {
+        Self {
+            devices: output::output_devices(),
+            sample_rates: output::supported_sample_rates(settings.output_device.as_deref()),
+            settings,
+        }
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    pub fn view(&self) -> Element<SettingsDialogMessage> {
+        let device_choices: Vec<DeviceChoice> = std::iter::once(DeviceChoice(None))
+            .chain(self.devices.iter().cloned().map(Some).map(DeviceChoice))
+            .collect();
+        let sample_rate_choices: Vec<SampleRateChoice> = std::iter::once(SampleRateChoice(None))
+            .chain(
+                self.sample_rates
+                    .iter()
+                    .copied()
+                    .map(Some)
+                    .map(SampleRateChoice),
+            )
+            .collect();
+
+        container(
+            column![
+                text("Audio settings"),
+                row![
+                    text("Output device"),
+                    horizontal_space().width(Length::Fill),
+                    pick_list(
+                        device_choices,
+                        Some(DeviceChoice(self.settings.output_device.clone())),
+                        SettingsDialogMessage::DeviceUpdated
+                    )
+                    .width(200),
+                ]
+                .align_y(Vertical::Center),
+                row![
+                    text("Sample rate"),
+                    horizontal_space().width(Length::Fill),
+                    pick_list(
+                        sample_rate_choices,
+                        Some(SampleRateChoice(self.settings.sample_rate)),
+                        SettingsDialogMessage::SampleRateUpdated
+                    )
+                    .width(200),
+                ]
+                .align_y(Vertical::Center),
+                row![
+                    text("Null output file"),
+                    horizontal_space().width(Length::Fill),
+                    button(
+                        text(
+                            self.settings
+                                .null_output_file
+                                .as_ref()
+                                .and_then(|path| path.file_name())
+                                .map(|name| name.to_string_lossy().into_owned())
+                                .unwrap_or(String::from("None"))
+                        )
+                        .size(14)
+                    )
+                    .width(150)
+                    .style(button::secondary)
+                    .on_press(SettingsDialogMessage::NullOutputFilePressed),
+                    button(text("Clear").size(14))
+                        .style(button::secondary)
+                        .on_press(SettingsDialogMessage::NullOutputFileCleared),
+                ]
+                .spacing(5)
+                .align_y(Vertical::Center),
+                text("Used when no output device is available").size(10),
+                row![
+                    button("Cancel")
+                        .width(Length::Fill)
+                        .on_press(SettingsDialogMessage::CancelPressed),
+                    button("Apply")
+                        .width(Length::Fill)
+                        .on_press(SettingsDialogMessage::ApplyPressed),
+                ]
+                .spacing(10)
+            ]
+            .spacing(10)
+            .align_x(Horizontal::Center),
+        )
+        .max_width(400)
+        .style(|theme: &iced::Theme| iced::widget::container::Style {
+            background: Some(iced::Background::Color(theme.palette().background)),
+
+            border: Border {
+                radius: Radius::new(5),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .padding(10)
+        .into()
+    }
+
+    pub fn update(&mut self, message: SettingsDialogMessage) {
+        match message {
+            SettingsDialogMessage::DeviceUpdated(DeviceChoice(device)) => {
+                self.sample_rates = output::supported_sample_rates(device.as_deref());
+                // keep the sample rate only if the new device supports it
+                self.settings.sample_rate = self
+                    .settings
+                    .sample_rate
+                    .filter(|sample_rate| self.sample_rates.contains(sample_rate));
+                self.settings.output_device = device;
+            }
+            SettingsDialogMessage::SampleRateUpdated(SampleRateChoice(sample_rate)) => {
+                self.settings.sample_rate = sample_rate;
+            }
+            SettingsDialogMessage::NullOutputFileChosen(Some(path)) => {
+                self.settings.null_output_file = Some(path);
+            }
+            SettingsDialogMessage::NullOutputFileCleared => {
+                self.settings.null_output_file = None;
+            }
+            SettingsDialogMessage::NullOutputFileChosen(None)
+            | SettingsDialogMessage::NullOutputFilePressed
+            | SettingsDialogMessage::CancelPressed
+            | SettingsDialogMessage::ApplyPressed => {}
+        }
+    }
+}
+
+/// A choice of output device, where None is the default device
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeviceChoice(Option<String>);
+
+impl std::fmt::Display for DeviceChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.0 {
+            Some(device) => write!(f, "{device}"),
+            None => write!(f, "Default device"),
+        }
+    }
+}
+
+/// A choice of sample rate, where None is the default sample rate of the device
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SampleRateChoice(Option<u32>);
+
+impl std::fmt::Display for SampleRateChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Some(sample_rate) => write!(f, "{sample_rate} Hz"),
+            None => write!(f, "Device default"),
+        }
+    }
+}
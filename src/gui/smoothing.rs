@@ -0,0 +1,54 @@
+use iced::{
+    Element, Length,
+    alignment::Horizontal,
+    widget::{checkbox, column, row, text, vertical_slider},
+};
+
+use crate::audio::smoothing::Smoothing;
+
+#[derive(Debug, Clone, Copy)]
+pub enum SmoothingMessage {
+    TimeUpdated(f32),
+    PortamentoToggled(bool),
+    PortamentoTimeUpdated(f32),
+}
+
+/// Sliders for the smoothing time and the portamento time, with a toggle for portamento
+pub fn view<'a>(smoothing: &Smoothing) -> Element<'a, SmoothingMessage> {
+    column![
+        row![
+            column![
+                text("Smooth"),
+                vertical_slider(0.0..=0.2, smoothing.time, SmoothingMessage::TimeUpdated)
+                    .step(0.001)
+            ]
+            .align_x(Horizontal::Center)
+            .spacing(5),
+            column![
+                text("Glide"),
+                vertical_slider(
+                    0.0..=2.0,
+                    smoothing.portamento_time,
+                    SmoothingMessage::PortamentoTimeUpdated
+                )
+                .step(0.01)
+            ]
+            .align_x(Horizontal::Center)
+            .spacing(5),
+        ]
+        .spacing(10)
+        .height(Length::Fill),
+        checkbox("Portamento", smoothing.portamento).on_toggle(SmoothingMessage::PortamentoToggled),
+    ]
+    .align_x(Horizontal::Center)
+    .spacing(10)
+    .into()
+}
+
+pub fn update(smoothing: &mut Smoothing, message: SmoothingMessage) {
+    match message {
+        SmoothingMessage::TimeUpdated(time) => smoothing.time = time,
+        SmoothingMessage::PortamentoToggled(portamento) => smoothing.portamento = portamento,
+        SmoothingMessage::PortamentoTimeUpdated(time) => smoothing.portamento_time = time,
+    }
+}
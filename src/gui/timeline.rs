@@ -0,0 +1,143 @@
+use iced::{
+    Alignment::Center,
+    Border, Element, Length,
+    widget::{
+        button, checkbox, column, container, horizontal_space, row, scrollable,
+        scrollable::{Direction, Scrollbar},
+        text,
+    },
+};
+use iced_aw::number_input;
+
+use crate::{icon, timeline::Timeline};
+
+use super::{icon_button, relative_frequency::RelativeFrequency};
+
+#[derive(Debug, Clone, Copy)]
+pub enum TimelineMessage {
+    /// Add a step at the end with a snapshot of the current chord
+    StepAdded,
+    StepDeleted(usize),
+    StepDurationUpdated(usize, f32),
+    /// Make a relative frequency play during a step or not, given as the index of the step and the id of the voice
+    StepVoiceToggled(usize, usize, bool),
+    /// Replace the snapshot of a step with the current chord
+    StepCaptured(usize),
+    /// Set the global frequencies to the snapshot of a step
+    StepRecalled(usize),
+    LoopToggled(bool),
+    PlayPressed,
+    StopPressed,
+}
+
+/// A strip of the steps of a timeline, where the step being played is highlighted
+pub fn view<'a>(
+    timeline: &'a Timeline,
+    voices: &[(usize, &RelativeFrequency)],
+    playing_step: Option<usize>,
+) -> Element<'a, TimelineMessage> {
+    let steps = row(timeline
+        .steps
+        .iter()
+        .enumerate()
+        .map(|(index, step)| {
+            let voice_toggles = column(voices.iter().map(|&(id, relative_frequency)| {
+                let ratio = relative_frequency.ratio();
+                checkbox(
+                    format!(
+                        "{}/{} of {}",
+                        ratio.numerator,
+                        ratio.denominator,
+                        relative_frequency.absolute_frequency_id()
+                    ),
+                    step.active_voices.contains(&id),
+                )
+                .on_toggle(move |is_active| TimelineMessage::StepVoiceToggled(index, id, is_active))
+                .text_size(10)
+                .size(12)
+                .into()
+            }))
+            .spacing(2);
+
+            let is_playing = playing_step == Some(index);
+            container(
+                column![
+                    row![
+                        text(format!("Step {}", index + 1)).size(12),
+                        horizontal_space().width(Length::Fill),
+                        icon_button(icon::cancel(), 10)
+                            .on_press(TimelineMessage::StepDeleted(index))
+                            .style(button::danger),
+                    ]
+                    .align_y(Center),
+                    row![
+                        text("s").size(10),
+                        number_input(&step.duration, 0.01f32..=600f32, move |duration| {
+                            TimelineMessage::StepDurationUpdated(index, duration)
+                        })
+                        .step(0.1)
+                        .width(Length::Fill),
+                    ]
+                    .spacing(5)
+                    .align_y(Center),
+                    scrollable(voice_toggles).height(Length::Fill),
+                    row![
+                        button(text("Capture").size(10))
+                            .padding([1, 5])
+                            .style(button::secondary)
+                            .on_press(TimelineMessage::StepCaptured(index)),
+                        button(text("Recall").size(10))
+                            .padding([1, 5])
+                            .style(button::secondary)
+                            .on_press(TimelineMessage::StepRecalled(index)),
+                    ]
+                    .spacing(5),
+                ]
+                .spacing(5),
+            )
+            .padding(5)
+            .width(130)
+            .height(Length::Fill)
+            .style(move |theme: &iced::Theme| {
+                let palette = theme.palette();
+                iced::widget::container::Style::default().border(
+                    Border::default()
+                        .width(if is_playing { 2 } else { 1 })
+                        .rounded(2)
+                        .color(if is_playing {
+                            palette.primary
+                        } else {
+                            palette.background.inverse().scale_alpha(0.1)
+                        }),
+                )
+            })
+            .into()
+        })
+        .chain(std::iter::once(
+            icon_button(icon::plus(), 14)
+                .on_press(TimelineMessage::StepAdded)
+                .height(Length::Fill)
+                .into(),
+        )))
+    .spacing(1);
+
+    column![
+        row![
+            text("Timeline"),
+            text(format!("{:.2} s", timeline.duration())).size(12),
+            horizontal_space().width(Length::Fill),
+            checkbox("Loop", timeline.looping).on_toggle(TimelineMessage::LoopToggled),
+            icon_button(icon::play(), 14).on_press_maybe(
+                (!timeline.steps.is_empty()).then_some(TimelineMessage::PlayPressed)
+            ),
+            icon_button(icon::stop(), 14).on_press(TimelineMessage::StopPressed),
+        ]
+        .spacing(10)
+        .align_y(Center),
+        scrollable(steps)
+            .direction(Direction::Horizontal(Scrollbar::new()))
+            .height(Length::Fill),
+    ]
+    .spacing(5)
+    .into()
+}
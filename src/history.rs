@@ -0,0 +1,149 @@
+use std::time::{Duration, Instant};
+
+use crate::{
+    audio::{
+        engine::Volume,
+        envelope::Envelope,
+        graph::Graph,
+        smoothing::Smoothing,
+        synthesizer::{Harmonic, WaveForm},
+    },
+    gui::{global_frequency::GlobalFrequency, relative_frequency::RelativeFrequency},
+    midi::MidiMapping,
+    timeline::Timeline,
+};
+
+/// Edits of the same target closer together than this are undone as one, so dragging a slider is a single edit
+const MERGE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// A part of the project which can be edited
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Target {
+    GlobalFrequency(usize),
+    RelativeFrequency(usize),
+    Volume,
+    WaveForm,
+    Envelope,
+    Smoothing,
+    PhaseReset,
+    CustomHarmonics,
+    Midi,
+    Timeline,
+    Graph,
+}
+
+/// The value of a target, where None means a frequency doesn't exist
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    GlobalFrequency(usize, Option<GlobalFrequency>),
+    RelativeFrequency(usize, Option<RelativeFrequency>),
+    Volume(Volume),
+    WaveForm(WaveForm),
+    Envelope(Envelope),
+    Smoothing(Smoothing),
+    PhaseReset(bool),
+    CustomHarmonics(Vec<Harmonic>),
+    Midi(MidiMapping),
+    Timeline(Timeline),
+    Graph(Graph),
+}
+
+impl Value {
+    /// Get the target this is a value of
+    pub fn target(&self) -> Target {
+        match self {
+            Value::GlobalFrequency(id, _) => Target::GlobalFrequency(*id),
+            Value::RelativeFrequency(id, _) => Target::RelativeFrequency(*id),
+            Value::Volume(_) => Target::Volume,
+            Value::WaveForm(_) => Target::WaveForm,
+            Value::Envelope(_) => Target::Envelope,
+            Value::Smoothing(_) => Target::Smoothing,
+            Value::PhaseReset(_) => Target::PhaseReset,
+            Value::CustomHarmonics(_) => Target::CustomHarmonics,
+            Value::Midi(_) => Target::Midi,
+            Value::Timeline(_) => Target::Timeline,
+            Value::Graph(_) => Target::Graph,
+        }
+    }
+}
+
+/// An edit of targets from one value each to another, which can be applied in both directions.
+/// Values are set in order when redoing and in reverse order when undoing
+#[derive(Debug, Clone)]
+pub struct Edit {
+    pub before: Vec<Value>,
+    pub after: Vec<Value>,
+}
+
+impl Edit {
+    fn targets(&self) -> impl Iterator<Item = Target> {
+        self.after.iter().map(Value::target)
+    }
+}
+
+/// The edits of the project which can be undone and redone
+#[derive(Debug, Default)]
+pub struct History {
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+    /// When the last edit was pushed, or None if it can't be merged with, because it was undone or redone since
+    last_push: Option<Instant>,
+}
+
+impl History {
+    /// Record an edit, forgetting every undone edit. Edits which don't change anything are ignored
+    pub fn push(&mut self, edit: Edit) {
+        self.push_at(edit, Instant::now());
+    }
+
+    /// Record an edit made at the given time, which decides whether it is merged with the last edit
+    pub fn push_at(&mut self, edit: Edit, now: Instant) {
+        let merge = self
+            .last_push
+            .is_some_and(|last_push| now.duration_since(last_push) < MERGE_INTERVAL)
+            && self
+                .undo
+                .last()
+                .is_some_and(|last| last.targets().eq(edit.targets()));
+        let edit = if merge {
+            let last = self.undo.pop().expect("merging requires a last edit");
+            Edit {
+                before: last.before,
+                ..edit
+            }
+        } else {
+            edit
+        };
+        if edit.before == edit.after {
+            self.last_push = None;
+            return;
+        }
+        self.undo.push(edit);
+        self.redo.clear();
+        self.last_push = Some(now);
+    }
+
+    /// Take the last edit to revert, which is then available to redo
+    pub fn undo(&mut self) -> Option<Edit> {
+        let edit = self.undo.pop()?;
+        self.redo.push(edit.clone());
+        self.last_push = None;
+        Some(edit)
+    }
+
+    /// Take the last undone edit to apply again
+    pub fn redo(&mut self) -> Option<Edit> {
+        let edit = self.redo.pop()?;
+        self.undo.push(edit.clone());
+        self.last_push = None;
+        Some(edit)
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+}
@@ -1,4 +1,13 @@
 pub mod audio;
+pub mod expression;
 pub mod gui;
+pub mod history;
+pub mod midi;
+pub mod mts;
+pub mod project;
+pub mod scala;
+pub mod settings;
+pub mod timeline;
+pub mod variables;
 // autogenerated by iced_fontello
 pub mod icon;
@@ -1,54 +1,86 @@
 use iced_aw::iced_fonts;
-use postcard::to_allocvec;
-use serde::{Deserialize, Serialize};
-
-use std::{
-    collections::BTreeMap,
-    io,
-    iter::once,
-    path::PathBuf,
-    sync::{Arc, Mutex},
-};
 
+use std::{collections::BTreeMap, io, iter::once, path::PathBuf, sync::Arc, time::Duration};
+
+use harmony_playground::history::{Edit, History, Target, Value};
 use harmony_playground::{
     audio::{
-        engine::{AudioEngine, SharedFrequency, SharedVolumeMultiplier, Volume},
-        synthesizer::WaveForm,
+        engine::{
+            AudioEngine, EngineControl, EngineController, SharedFrequency, SharedVolumeMultiplier,
+            Volume,
+        },
+        envelope::Envelope,
+        graph::{Graph, GraphError},
+        output::AudioOutput,
+        render::{SampleFormat, render_to_wav},
+        scope::ScopeSnapshot,
+        smoothing::Smoothing,
+        synthesizer::{Harmonic, WaveForm},
+        transport::TransportStep,
     },
     gui::{
-        global_frequency::{GlobalFrequency, GlobalFrequencyMessage},
+        envelope::{
+            self as envelope_editor, EnvelopeDialog, EnvelopeDialogMessage, EnvelopeMessage,
+        },
+        global_frequency::{GlobalFrequency, GlobalFrequencyMessage, VariableChoice},
+        graph::{self as graph_editor, GraphMessage},
+        harmonics::{self as harmonics_editor, HarmonicsDialog, HarmonicsDialogMessage},
         icon_button,
+        midi_dialog::{FrequencyChoice, MidiDialog, MidiDialogMessage, NoteChoice},
+        mts_dialog::{MtsDialog, MtsDialogMessage},
         relative_frequency::{
             Ratio, RelativeFrequency, RelativeFrequencyMessage, RelativeFrequencyStateUpdate,
         },
+        render_dialog::{RenderDialog, RenderDialogMessage},
         save_dialog::{SaveDialog, SaveDialogMessage, modal},
+        scope::{self as scope_view, ScopeMessage},
+        settings_dialog::{SettingsDialog, SettingsDialogMessage},
+        smoothing::{self as smoothing_editor, SmoothingMessage},
+        timeline::{self as timeline_editor, TimelineMessage},
     },
     icon,
+    midi::{MidiError, MidiEvent, MidiInputPort, MidiMapping, MidiOutputPort, MidiOutputSettings},
+    mts::Tuning,
+    project::{self, FileFormat, LoadError, StateSave, TEXT_EXTENSION},
+    scala::{KeyboardMapping, ScalaError, ScalaImport, Scale},
+    settings::{Settings, SettingsError},
+    timeline::{Step, Timeline},
+    variables::{self, VariableError, VariableType},
 };
 use iced::{
-    Element, Length, Task,
+    Element, Length, Subscription, Task,
     alignment::Horizontal,
+    keyboard,
     widget::{
-        button, column, combo_box, container, horizontal_space, radio, row,
+        button, checkbox, column, combo_box, container, horizontal_space, radio, row,
         scrollable::{Direction, Scrollbar},
         text, vertical_slider, vertical_space,
     },
 };
-use rodio::{OutputStream, Source};
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
-struct StateSave {
-    volume: Volume,
-    waveform: WaveForm,
-    global_frequencies: BTreeMap<usize, GlobalFrequency>,
-    relative_frequencies: Vec<RelativeFrequency>,
-}
+mod cli;
 
 struct State {
-    engine: Arc<Mutex<AudioEngine>>,
-    // Will never be None
-    waveform: Option<WaveForm>,
+    engine: EngineController,
+    /// The output the engine plays on, which is only None while it is being reopened
+    output: Option<AudioOutput>,
+    waveform: WaveForm,
+    /// The harmonics of the custom waveform, which the project and every relative frequency can pick
+    custom_harmonics: Vec<Harmonic>,
     volume: Volume,
+    /// The envelope of every relative frequency which doesn't override it
+    envelope: Envelope,
+    smoothing: Smoothing,
+    /// Whether every voice restarts at phase zero when playback starts
+    phase_reset: bool,
+    /// How incoming MIDI notes play the project
+    midi: MidiMapping,
+    /// The harmonic progression of the project, where steps refer to relative frequencies by their id
+    timeline: Timeline,
+    /// The graph the voices are processed by
+    graph: Graph,
+    /// The node of the graph whose parameters are shown
+    selected_node: Option<usize>,
 
     global_frequencies: BTreeMap<usize, GlobalFrequency>,
     /// Stores the relative frequency, its corresponding oscillator id for future possible deletion,
@@ -69,21 +101,66 @@ struct State {
     file: (Option<PathBuf>, bool),
     current_error: Option<Error>,
     show_save_confirmation: Option<Message>,
+    render_dialog: RenderDialog,
+    show_render_dialog: bool,
+    mts_dialog: MtsDialog,
+    show_mts_dialog: bool,
+    /// The id of the relative frequency whose envelope is being edited
+    editing_envelope: Option<usize>,
+    show_harmonics_dialog: bool,
+    settings_dialog: Option<SettingsDialog>,
+    /// The edits of the project which can be undone and redone
+    history: History,
+    /// Whether MIDI input is received, which is kept when another project is opened
+    midi_input_enabled: bool,
+    /// The MIDI notes held down which transpose the mapped global frequency, where the last one is played
+    held_notes: Vec<u8>,
+    /// The MIDI output every playing voice is sent to, if it is enabled
+    midi_output: Option<MidiOutputPort>,
+    /// How voices are sent to the MIDI output, loaded from the settings
+    midi_output_settings: MidiOutputSettings,
+    /// Whether the engine is playing, which the MIDI output follows
+    is_playing: bool,
+    /// Whether the engine is playing the timeline
+    is_timeline_playing: bool,
+    /// The step of the timeline the engine is playing, polled while the timeline plays
+    playing_step: Option<usize>,
+    show_midi_dialog: bool,
+    /// Whether the scope is shown, which polls the engine for its latest frames
+    show_scope: bool,
+    /// The latest frames of the engine, polled while the scope is shown
+    scope: ScopeSnapshot,
+    /// The id of the relative frequency the scope shows next to the mixed output
+    scope_voice: Option<usize>,
+    /// Whether the scope is triggered on the fundamental of the voices
+    is_scope_triggered: bool,
 }
 
 impl State {
-    pub fn new(engine: Arc<Mutex<AudioEngine>>) -> Self {
-        let volume = engine.lock().unwrap().get_volume();
+    pub fn new(mut engine: EngineController, output: Option<AudioOutput>) -> Self {
+        let volume = engine.get_volume();
         let waveform = WaveForm::default();
-        {
-            let mut engine = engine.lock().unwrap();
-            engine.clear_oscillators();
-            engine.set_waveform(waveform);
-        }
+        let smoothing = Smoothing::default();
+        let graph = Graph::default();
+        engine.clear_oscillators();
+        engine.set_waveform(waveform.clone());
+        engine.set_smoothing(smoothing);
+        engine
+            .set_graph(&graph)
+            .expect("the default graph has a single output");
         Self {
             engine,
-            waveform: Some(waveform),
+            output,
+            waveform,
+            custom_harmonics: harmonics_editor::default_harmonics(),
             volume,
+            envelope: Envelope::default(),
+            smoothing,
+            phase_reset: false,
+            midi: MidiMapping::default(),
+            timeline: Timeline::default(),
+            graph,
+            selected_node: None,
             global_frequencies: BTreeMap::new(),
             relative_frequencies: BTreeMap::new(),
             theme: iced::Theme::Dark,
@@ -92,19 +169,53 @@ impl State {
             file: (None, true),
             current_error: None,
             show_save_confirmation: None,
+            render_dialog: RenderDialog::default(),
+            show_render_dialog: false,
+            mts_dialog: MtsDialog::default(),
+            show_mts_dialog: false,
+            editing_envelope: None,
+            show_harmonics_dialog: false,
+            settings_dialog: None,
+            history: History::default(),
+            midi_input_enabled: false,
+            held_notes: Vec::new(),
+            midi_output: None,
+            midi_output_settings: MidiOutputSettings::default(),
+            is_playing: false,
+            is_timeline_playing: false,
+            playing_step: None,
+            show_midi_dialog: false,
+            show_scope: false,
+            scope: ScopeSnapshot::default(),
+            scope_voice: None,
+            is_scope_triggered: true,
         }
     }
 
     pub fn to_save(&self) -> StateSave {
+        // relative frequencies are saved in order, so steps refer to them by their index
+        let indices: BTreeMap<usize, usize> = self
+            .relative_frequencies
+            .keys()
+            .enumerate()
+            .map(|(index, id)| (*id, index))
+            .collect();
         StateSave {
             volume: self.volume,
-            waveform: self.waveform.unwrap_or(WaveForm::Sine),
+            waveform: self.waveform.clone(),
+            custom_harmonics: self.custom_harmonics.clone(),
+            envelope: self.envelope,
+            smoothing: self.smoothing,
+            phase_reset: self.phase_reset,
             global_frequencies: self.global_frequencies.clone(),
             relative_frequencies: self
                 .relative_frequencies
                 .iter()
                 .map(|(_, (relative_frequency, _, _, _))| relative_frequency.clone())
                 .collect(),
+            midi: self.midi,
+            timeline: self.timeline.map_voices(|id| indices.get(&id).copied()),
+            graph: self.graph.clone(),
         }
     }
 
@@ -112,7 +223,16 @@ impl State {
     pub fn set_error(&mut self, error: Error) {
         match error {
             Error::FileDialogClosed => {}
-            Error::IO(_) | Error::Postcard(_) => {
+            Error::IO(_)
+            | Error::Postcard(_)
+            | Error::Ron(_)
+            | Error::Load(_)
+            | Error::Wav(_)
+            | Error::Scala(_)
+            | Error::Midi(_)
+            | Error::Variable(_)
+            | Error::Graph(_)
+            | Error::Settings(_) => {
                 self.current_error = Some(error);
             }
         };
@@ -130,70 +250,38 @@ impl State {
 
     /// Returns the oscillator id and shared frequency if it succeeds to initialize, else None
     pub fn initialize_oscillator(
-        engine: &Arc<Mutex<AudioEngine>>,
+        engine: &mut EngineController,
         global_frequencies: &BTreeMap<usize, GlobalFrequency>,
+        project_envelope: Envelope,
         relative_frequency: &RelativeFrequency,
     ) -> (Option<(usize, SharedFrequency)>, SharedVolumeMultiplier) {
-        let shared_volume_multiplier =
-            SharedVolumeMultiplier::new(Volume::new(relative_frequency.volume()).multiple());
-        // if global frequency doesn't exist, don't create an oscillator
-        let Some(global_frequency) =
-            global_frequencies.get(&relative_frequency.absolute_frequency_id())
-        else {
-            return (None, shared_volume_multiplier);
-        };
-        let shared_frequency = SharedFrequency::new(
-            global_frequency.frequency() * relative_frequency.ratio().multiplicand(),
-        );
-        let oscillator_id = engine
-            .lock()
-            .unwrap()
-            // this initializes a shared channel for updating the frequency of the oscillator remotely
-            //  so you don't have to lock the entire audio engine for that
-            .add_oscillator(shared_frequency.clone(), shared_volume_multiplier.clone());
-        (
-            Some((oscillator_id, shared_frequency)),
-            shared_volume_multiplier,
+        project::initialize_oscillator(
+            engine,
+            global_frequencies,
+            project_envelope,
+            relative_frequency,
         )
     }
 
     pub fn from_save(
-        engine: Arc<Mutex<AudioEngine>>,
+        mut engine: EngineController,
+        output: Option<AudioOutput>,
         save: Arc<StateSave>,
         file_path: Option<PathBuf>,
         theme: iced::Theme,
     ) -> Self {
         let save = Arc::unwrap_or_clone(save);
-        {
-            let mut engine = engine.lock().unwrap();
-            engine.clear_oscillators();
-            engine.set_volume(save.volume);
-            engine.set_waveform(save.waveform);
-            engine.stop();
-        }
+        let oscillators = save
+            .initialize_engine(&mut engine)
+            .expect("loaded saves have a valid graph");
 
         let relative_frequencies = save
             .relative_frequencies
             .into_iter()
+            .zip(oscillators)
             .enumerate()
-            .map(|(index, relative_frequency)| {
-                let (oscillator_id, shared_frequency, shared_volume_multiplier) =
-                    match Self::initialize_oscillator(
-                        &engine,
-                        &save.global_frequencies,
-                        &relative_frequency,
-                    ) {
-                        (Some((oscillator_id, shared_frequency)), shared_volume_multiplier) => (
-                            Some(oscillator_id),
-                            shared_frequency,
-                            shared_volume_multiplier,
-                        ),
-                        (None, shared_volume_multiplier) => {
-                            println!("failed to initialize oscillator");
-                            (None, SharedFrequency::new(220.0), shared_volume_multiplier)
-                        }
-                    };
-
+            .map(|(index, (relative_frequency, oscillator))| {
+                let (oscillator_id, shared_frequency, shared_volume_multiplier) = oscillator;
                 (
                     index,
                     (
@@ -208,8 +296,18 @@ impl State {
 
         Self {
             engine,
+            output,
             volume: save.volume,
-            waveform: Some(save.waveform),
+            waveform: save.waveform,
+            custom_harmonics: save.custom_harmonics,
+            envelope: save.envelope,
+            smoothing: save.smoothing,
+            phase_reset: save.phase_reset,
+            midi: save.midi,
+            // the relative frequencies get their index in the save as their id
+            timeline: save.timeline,
+            graph: save.graph,
+            selected_node: None,
             global_frequencies: save.global_frequencies,
             relative_frequencies,
             theme,
@@ -218,25 +316,64 @@ impl State {
             file: (file_path, true),
             current_error: None,
             show_save_confirmation: None,
+            render_dialog: RenderDialog::default(),
+            show_render_dialog: false,
+            mts_dialog: MtsDialog::default(),
+            show_mts_dialog: false,
+            editing_envelope: None,
+            show_harmonics_dialog: false,
+            settings_dialog: None,
+            history: History::default(),
+            midi_input_enabled: false,
+            held_notes: Vec::new(),
+            midi_output: None,
+            midi_output_settings: MidiOutputSettings::default(),
+            is_playing: false,
+            is_timeline_playing: false,
+            playing_step: None,
+            show_midi_dialog: false,
+            show_scope: false,
+            scope: ScopeSnapshot::default(),
+            scope_voice: None,
+            is_scope_triggered: true,
         }
     }
 
-    pub fn add_global_frequency(&mut self, frequency: f32) {
-        let latest_id = self
-            .global_frequencies
+    /// Get the id the next added global frequency gets
+    fn next_global_frequency_id(&self) -> usize {
+        self.global_frequencies
             .last_key_value()
             .map(|(id, _)| id + 1)
-            .unwrap_or(1);
+            .unwrap_or(1)
+    }
+
+    /// Get the id the next added relative frequency gets
+    fn next_relative_frequency_id(&self) -> usize {
+        self.relative_frequencies
+            .last_key_value()
+            .map(|(id, _)| id + 1)
+            .unwrap_or(0)
+    }
+
+    pub fn add_global_frequency(&mut self, frequency: f32) {
+        let latest_id = self.next_global_frequency_id();
 
         self.global_frequencies
             .insert(latest_id, GlobalFrequency::new(latest_id, frequency));
     }
 
     pub fn add_relative_frequency(&mut self, relative_frequency: RelativeFrequency) {
+        self.insert_relative_frequency(self.next_relative_frequency_id(), relative_frequency);
+    }
+
+    /// Insert a relative frequency with the given id, initializing its oscillator
+    fn insert_relative_frequency(&mut self, id: usize, mut relative_frequency: RelativeFrequency) {
+        relative_frequency.evaluate_ratio(&self.global_frequencies);
         let (oscillator_id, shared_frequency, shared_volume_multiplier) =
             match Self::initialize_oscillator(
-                &self.engine,
+                &mut self.engine,
                 &self.global_frequencies,
+                self.envelope,
                 &relative_frequency,
             ) {
                 (Some((oscillator_id, shared_frequency)), shared_volume_multiplier) => (
@@ -249,14 +386,8 @@ impl State {
                 }
             };
 
-        let latest_id = self
-            .relative_frequencies
-            .last_key_value()
-            .map(|(id, _)| id + 1)
-            .unwrap_or(0);
-
         self.relative_frequencies.insert(
-            latest_id,
+            id,
             (
                 relative_frequency,
                 oscillator_id,
@@ -264,11 +395,38 @@ impl State {
                 shared_volume_multiplier,
             ),
         );
+        // like the oscillator, the voice starts playing if the engine is playing
+        if self.is_playing {
+            self.send_midi_note_on(id);
+        }
     }
 
     pub fn set_waveform(&mut self, waveform: WaveForm) {
-        self.engine.lock().unwrap().set_waveform(waveform);
-        self.waveform = Some(waveform);
+        self.engine.set_waveform(waveform.clone());
+        self.waveform = waveform;
+    }
+
+    /// Set the harmonics of the custom waveform, updating the project and every relative frequency which plays it
+    pub fn set_custom_harmonics(&mut self, harmonics: Vec<Harmonic>) {
+        let waveform = WaveForm::Custom(harmonics.clone());
+        self.custom_harmonics = harmonics;
+        if let WaveForm::Custom(_) = self.waveform {
+            self.set_waveform(waveform.clone());
+        }
+        let ids: Vec<usize> = self
+            .relative_frequencies
+            .iter()
+            .filter(|(_, (relative_frequency, _, _, _))| {
+                matches!(relative_frequency.waveform(), Some(WaveForm::Custom(_)))
+            })
+            .map(|(id, _)| *id)
+            .collect();
+        for id in ids {
+            self.update_relative_frequency(
+                id,
+                RelativeFrequencyMessage::WaveFormUpdated(Some(waveform.clone())),
+            );
+        }
     }
 
     pub fn set_volume(&mut self, mut volume: f32) {
@@ -276,22 +434,47 @@ impl State {
             volume = f32::NEG_INFINITY
         }
         let volume = Volume::new(volume);
-        self.engine.lock().unwrap().set_volume(volume);
+        self.engine.set_volume(volume);
         self.volume = volume;
     }
 
+    /// Update a global variable, recomputing every variable and relative frequency which depends on it.
+    /// Updates which make variables reference each other in a cycle are rejected with an error
     pub fn update_global_frequency(&mut self, id: usize, message: GlobalFrequencyMessage) {
-        let Some(global_frequency) = self.global_frequencies.get_mut(&id) else {
+        let Some(global_frequency) = self.global_frequencies.get(&id) else {
             return;
         };
+        let mut global_frequency = global_frequency.clone();
         global_frequency.update(message);
 
-        for (relative_frequency, _, shared_frequency, _) in self.relative_frequencies.values_mut() {
-            if relative_frequency.absolute_frequency_id() != id {
-                continue;
-            }
-            shared_frequency
-                .set(global_frequency.frequency() * relative_frequency.ratio().multiplicand());
+        let mut global_frequencies = self.global_frequencies.clone();
+        global_frequencies.insert(id, global_frequency);
+        if let Err(error) = variables::evaluate(&mut global_frequencies) {
+            self.set_error(Error::Variable(error));
+            return;
+        }
+        self.global_frequencies = global_frequencies;
+        self.sync_dependents(id);
+    }
+
+    /// Make every relative frequency which depends on the global variable with the given id play it,
+    /// whether it is played relative to it or its ratio references it, directly or through other variables
+    fn sync_dependents(&mut self, id: usize) {
+        let dependents = variables::dependents(&self.global_frequencies, id);
+        let ids: Vec<usize> = self
+            .relative_frequencies
+            .iter()
+            .filter(|(_, (relative_frequency, _, _, _))| {
+                dependents.contains(&relative_frequency.absolute_frequency_id())
+                    || relative_frequency
+                        .references()
+                        .iter()
+                        .any(|reference| dependents.contains(reference))
+            })
+            .map(|(id, _)| *id)
+            .collect();
+        for id in ids {
+            self.sync_relative_frequency(id);
         }
     }
 
@@ -309,17 +492,23 @@ impl State {
         let oscillator_id = match oscillator_id_option {
             Some(oscillator_id) => *oscillator_id,
             None => {
-                let oscillator_id = self
-                    .engine
-                    .lock()
-                    .unwrap()
-                    .add_oscillator(shared_frequency.clone(), shared_volume_multiplier.clone());
+                let oscillator_id = self.engine.add_oscillator(
+                    shared_frequency.clone(),
+                    shared_volume_multiplier.clone(),
+                    relative_frequency.envelope().unwrap_or(self.envelope),
+                    relative_frequency.waveform(),
+                );
+                self.engine
+                    .set_oscillator_pan(&oscillator_id, relative_frequency.pan());
+                self.engine
+                    .set_oscillator_sync(&oscillator_id, relative_frequency.sync_ratio());
                 *oscillator_id_option = Some(oscillator_id);
                 oscillator_id
             }
         };
         // update the ui element representing the relative frequency
         let state_update = relative_frequency.update(message);
+        relative_frequency.evaluate_ratio(&self.global_frequencies);
 
         // update the audio engine to reflect these changes
         match state_update {
@@ -328,19 +517,17 @@ impl State {
                 match self
                     .global_frequencies
                     .get(&relative_frequency.absolute_frequency_id())
+                    .and_then(GlobalFrequency::frequency)
                 {
-                    Some(global_frequency) => {
-                        shared_frequency.set(
-                            global_frequency.frequency()
-                                * relative_frequency.ratio().multiplicand(),
-                        );
+                    Some(frequency) => {
+                        shared_frequency.set(frequency * relative_frequency.multiplicand());
+                        self.engine
+                            .set_oscillator_sync(&oscillator_id, relative_frequency.sync_ratio());
                     }
-                    // if referencing an invalid global frequency, remove the oscillator so no sound is produced
+                    // if referencing an invalid global frequency or another type of variable, remove the
+                    // oscillator so no sound is produced
                     None => {
-                        self.engine
-                            .lock()
-                            .unwrap()
-                            .remove_oscillator(&oscillator_id);
+                        self.engine.remove_oscillator(&oscillator_id);
                         *oscillator_id_option = None;
                     }
                 }
@@ -349,22 +536,713 @@ impl State {
                 let volume_multiplier = Volume::new(relative_frequency.volume()).multiple();
                 shared_volume_multiplier.set(volume_multiplier)
             }
+            Some(RelativeFrequencyStateUpdate::EnvelopeUpdated) => {
+                self.engine.set_oscillator_envelope(
+                    &oscillator_id,
+                    relative_frequency.envelope().unwrap_or(self.envelope),
+                );
+            }
+            Some(RelativeFrequencyStateUpdate::PanUpdated) => {
+                self.engine
+                    .set_oscillator_pan(&oscillator_id, relative_frequency.pan());
+            }
+            Some(RelativeFrequencyStateUpdate::SyncUpdated) => {
+                self.engine
+                    .set_oscillator_sync(&oscillator_id, relative_frequency.sync_ratio());
+            }
+            Some(RelativeFrequencyStateUpdate::WaveFormUpdated) => {
+                self.engine
+                    .set_oscillator_waveform(&oscillator_id, relative_frequency.waveform());
+            }
             None => {}
         }
     }
 
+    /// Set the project envelope, updating every oscillator which doesn't override it
+    pub fn set_envelope(&mut self, envelope: Envelope) {
+        self.envelope = envelope;
+        for (relative_frequency, oscillator_id, _, _) in self.relative_frequencies.values() {
+            if let (None, Some(oscillator_id)) = (relative_frequency.envelope(), oscillator_id) {
+                self.engine.set_oscillator_envelope(oscillator_id, envelope);
+            }
+        }
+    }
+
+    pub fn set_smoothing(&mut self, smoothing: Smoothing) {
+        self.engine.set_smoothing(smoothing);
+        self.smoothing = smoothing;
+    }
+
+    pub fn set_phase_reset(&mut self, phase_reset: bool) {
+        self.engine.set_phase_reset(phase_reset);
+        self.phase_reset = phase_reset;
+    }
+
+    /// Open the output of the settings, moving the project to a new engine playing on it, keeping its history and view.
+    /// The current output is closed first, since some devices can only be opened once
+    pub fn reopen_output(&mut self, settings: &Settings) {
+        // notes held on the old engine are released, since it stops with the old output
+        self.release_midi();
+        self.output = None;
+        let (output, mut engine) = AudioOutput::from_settings(settings);
+        let save = self.to_save();
+        let oscillators = match save.initialize_engine(&mut engine) {
+            Ok(oscillators) => oscillators,
+            // the graph can't be compiled, so the new engine plays the voices through the default graph like it
+            // would have kept the graph of the old engine
+            Err(error) => {
+                self.set_error(Error::Graph(error));
+                StateSave {
+                    graph: Graph::default(),
+                    ..save
+                }
+                .initialize_engine(&mut engine)
+                .expect("the default graph has a single output")
+            }
+        };
+        // the voices are initialized in the order of their ids, like when saving, so only their oscillators change
+        for ((_, oscillator_id, shared_frequency, shared_volume_multiplier), oscillator) in
+            self.relative_frequencies.values_mut().zip(oscillators)
+        {
+            (*oscillator_id, *shared_frequency, *shared_volume_multiplier) = oscillator;
+        }
+        self.engine = engine;
+        self.output = Some(output);
+        // the new engine isn't playing yet
+        self.is_playing = false;
+        self.is_timeline_playing = false;
+        self.playing_step = None;
+        if let Some(midi_output) = &mut self.midi_output {
+            midi_output.all_notes_off();
+        }
+    }
+
+    /// Replace the project with another one, keeping the MIDI ports and whether the engine plays
+    fn replace_project(&mut self, mut state: Self) {
+        // the timeline refers to the voices of the old project
+        if self.is_timeline_playing {
+            self.engine.stop();
+            self.is_playing = false;
+        }
+        state.midi_input_enabled = self.midi_input_enabled;
+        state.midi_output = self.midi_output.take();
+        state.midi_output_settings = self.midi_output_settings;
+        state.is_playing = self.is_playing;
+        *self = state;
+
+        if let Some(midi_output) = &mut self.midi_output {
+            midi_output.all_notes_off();
+        }
+        if self.is_playing {
+            self.send_midi_play();
+        }
+    }
+
+    /// Set how incoming MIDI notes play the project, releasing the notes held down under the old mapping
+    pub fn set_midi_mapping(&mut self, midi: MidiMapping) {
+        self.release_midi();
+        self.midi = midi;
+    }
+
+    /// Play an incoming MIDI note, gating the voices mapped to it and transposing the mapped global frequency
+    pub fn play_midi_event(&mut self, event: MidiEvent) {
+        let (note, gate) = match event {
+            MidiEvent::NoteOn { note, .. } => (note, true),
+            MidiEvent::NoteOff { note } => (note, false),
+        };
+        let ids: Vec<usize> = self
+            .relative_frequencies
+            .iter()
+            .filter(|(_, (relative_frequency, _, _, _))| {
+                relative_frequency.midi_note() == Some(note)
+            })
+            .map(|(id, _)| *id)
+            .collect();
+        for id in ids {
+            self.gate_voice(id, gate);
+        }
+
+        let Some(global_frequency_id) = self.midi.transposed_frequency else {
+            return;
+        };
+        // the last note held down is played, like on a monophonic synthesizer
+        self.held_notes.retain(|held_note| *held_note != note);
+        if gate {
+            self.held_notes.push(note);
+        }
+        match self.held_notes.last() {
+            Some(&played_note) => {
+                self.transpose_global_frequency(
+                    global_frequency_id,
+                    self.midi.transposition(played_note),
+                );
+                if gate {
+                    self.gate_global_frequency(global_frequency_id, true);
+                }
+            }
+            None => self.gate_global_frequency(global_frequency_id, false),
+        }
+    }
+
+    /// Stop every note held down on the transposed global frequency, moving it back to its own frequency
+    fn release_midi(&mut self) {
+        if let (Some(global_frequency_id), false) =
+            (self.midi.transposed_frequency, self.held_notes.is_empty())
+        {
+            self.gate_global_frequency(global_frequency_id, false);
+            self.transpose_global_frequency(global_frequency_id, 1.0);
+        }
+        self.held_notes.clear();
+    }
+
+    /// Play the voices of a global frequency at the global frequency multiplied by the transposition,
+    /// without changing the project. Voices of global frequencies defined from it move along
+    fn transpose_global_frequency(&mut self, global_frequency_id: usize, transposition: f32) {
+        let dependents = variables::dependents(&self.global_frequencies, global_frequency_id);
+        let transposed =
+            variables::transposed(&self.global_frequencies, global_frequency_id, transposition);
+        for (id, (relative_frequency, _, shared_frequency, _)) in &self.relative_frequencies {
+            let global_frequency_id = relative_frequency.absolute_frequency_id();
+            if !dependents.contains(&global_frequency_id)
+                && !relative_frequency
+                    .references()
+                    .iter()
+                    .any(|reference| dependents.contains(reference))
+            {
+                continue;
+            }
+            let Some(frequency) = transposed
+                .get(&global_frequency_id)
+                .and_then(GlobalFrequency::frequency)
+            else {
+                continue;
+            };
+            // ratios referencing the transposed variables move along with them
+            shared_frequency.set(frequency * relative_frequency.multiplicand_with(&transposed));
+            if let Some(midi_output) = &mut self.midi_output {
+                midi_output.retune(*id, shared_frequency.get());
+            }
+        }
+    }
+
+    /// Get the global frequency transposed by the MIDI note held down and its transposition, if any
+    fn transposition(&self) -> Option<(usize, f32)> {
+        let global_frequency_id = self.midi.transposed_frequency?;
+        let note = self.held_notes.last()?;
+        Some((global_frequency_id, self.midi.transposition(*note)))
+    }
+
+    fn gate_global_frequency(&mut self, id: usize, gate: bool) {
+        let ids: Vec<usize> = self
+            .relative_frequencies
+            .iter()
+            .filter(|(_, (relative_frequency, _, _, _))| {
+                relative_frequency.absolute_frequency_id() == id
+            })
+            .map(|(id, _)| *id)
+            .collect();
+        for id in ids {
+            self.gate_voice(id, gate);
+        }
+    }
+
+    /// Start or stop playing the voice of a relative frequency on its own, both on the engine and the MIDI output
+    fn gate_voice(&mut self, id: usize, gate: bool) {
+        let Some((_, Some(oscillator_id), _, _)) = self.relative_frequencies.get(&id) else {
+            return;
+        };
+        self.engine.set_oscillator_gate(oscillator_id, gate);
+        if gate {
+            self.send_midi_note_on(id);
+        } else if let Some(midi_output) = &mut self.midi_output {
+            midi_output.note_off(id);
+        }
+    }
+
+    /// Send the voice of a relative frequency to the MIDI output at the frequency it plays at,
+    /// with its volume as the velocity
+    fn send_midi_note_on(&mut self, id: usize) {
+        let (Some(midi_output), Some((relative_frequency, Some(_), shared_frequency, _))) =
+            (&mut self.midi_output, self.relative_frequencies.get(&id))
+        else {
+            return;
+        };
+        let velocity = Volume::new(relative_frequency.volume()).multiple() * 127.0;
+        midi_output.note_on(id, shared_frequency.get(), velocity.round() as u8);
+    }
+
+    /// Send every voice to the MIDI output, like the engine plays every oscillator
+    fn send_midi_play(&mut self) {
+        let ids: Vec<usize> = self.relative_frequencies.keys().copied().collect();
+        for id in ids {
+            self.send_midi_note_on(id);
+        }
+    }
+
+    /// Open or close the MIDI output, sending every voice to it if the engine is playing
+    fn set_midi_output_enabled(&mut self, enabled: bool) {
+        self.midi_output = None;
+        if !enabled {
+            return;
+        }
+        match MidiOutputPort::open(self.midi_output_settings) {
+            Ok(midi_output) => {
+                self.midi_output = Some(midi_output);
+                if self.is_playing {
+                    self.send_midi_play();
+                }
+            }
+            Err(error) => self.set_error(Error::Midi(error)),
+        }
+    }
+
+    /// Change how voices are sent to the MIDI output, resending the playing voices with the new settings
+    fn set_midi_output_settings(&mut self, settings: MidiOutputSettings) {
+        self.midi_output_settings = settings;
+        if let Some(midi_output) = &mut self.midi_output {
+            midi_output.configure(settings);
+            if self.is_playing {
+                self.send_midi_play();
+            }
+        }
+        // unreadable settings are left as they are rather than overwritten with the defaults
+        let mut persisted = match Settings::load() {
+            Ok(persisted) => persisted,
+            Err(error) => {
+                self.set_error(Error::Settings(error));
+                return;
+            }
+        };
+        persisted.midi_output = settings;
+        if let Err(error) = persisted.save() {
+            self.set_error(Error::IO(error.kind()));
+        }
+    }
+
     pub fn delete_relative_frequency(&mut self, id: usize) {
         let Some((_, oscillator_id_option, _, _)) = self.relative_frequencies.remove(&id) else {
             println!("deleted non-existent relative frequency");
             return;
         };
         if let Some(oscillator_id) = oscillator_id_option {
-            self.engine
-                .lock()
-                .unwrap()
-                .remove_oscillator(&oscillator_id);
+            self.engine.remove_oscillator(&oscillator_id);
+        }
+        if let Some(midi_output) = &mut self.midi_output {
+            midi_output.note_off(id);
+        }
+    }
+
+    /// Make the oscillator of a relative frequency play all of its parameters,
+    /// adding or removing the oscillator depending on whether its global frequency exists
+    fn sync_relative_frequency(&mut self, id: usize) {
+        let transposition = self.transposition();
+        let Some((
+            relative_frequency,
+            oscillator_id_option,
+            shared_frequency,
+            shared_volume_multiplier,
+        )) = self.relative_frequencies.get_mut(&id)
+        else {
+            return;
+        };
+        relative_frequency.evaluate_ratio(&self.global_frequencies);
+        // a voice synced while a MIDI note is held keeps playing transposed
+        let global_frequency_id = relative_frequency.absolute_frequency_id();
+        let (played_frequency, multiplicand) = match transposition {
+            Some((transposed_id, transposition)) => {
+                let transposed =
+                    variables::transposed(&self.global_frequencies, transposed_id, transposition);
+                (
+                    transposed
+                        .get(&global_frequency_id)
+                        .and_then(GlobalFrequency::frequency),
+                    relative_frequency.multiplicand_with(&transposed),
+                )
+            }
+            None => (
+                self.global_frequencies
+                    .get(&global_frequency_id)
+                    .and_then(GlobalFrequency::frequency),
+                relative_frequency.multiplicand(),
+            ),
+        };
+        let Some(frequency) = played_frequency else {
+            if let Some(oscillator_id) = oscillator_id_option.take() {
+                self.engine.remove_oscillator(&oscillator_id);
+            }
+            if let Some(midi_output) = &mut self.midi_output {
+                midi_output.note_off(id);
+            }
+            return;
+        };
+        shared_frequency.set(frequency * multiplicand);
+        if let Some(midi_output) = &mut self.midi_output {
+            midi_output.retune(id, shared_frequency.get());
+        }
+        shared_volume_multiplier.set(Volume::new(relative_frequency.volume()).multiple());
+
+        let envelope = relative_frequency.envelope().unwrap_or(self.envelope);
+        let is_added = oscillator_id_option.is_none();
+        let oscillator_id = match oscillator_id_option {
+            Some(oscillator_id) => {
+                self.engine.set_oscillator_envelope(oscillator_id, envelope);
+                self.engine
+                    .set_oscillator_waveform(oscillator_id, relative_frequency.waveform());
+                *oscillator_id
+            }
+            None => {
+                let oscillator_id = self.engine.add_oscillator(
+                    shared_frequency.clone(),
+                    shared_volume_multiplier.clone(),
+                    envelope,
+                    relative_frequency.waveform(),
+                );
+                *oscillator_id_option = Some(oscillator_id);
+                oscillator_id
+            }
+        };
+        self.engine
+            .set_oscillator_pan(&oscillator_id, relative_frequency.pan());
+        self.engine
+            .set_oscillator_sync(&oscillator_id, relative_frequency.sync_ratio());
+        // like the oscillator, an added voice starts playing if the engine is playing
+        if is_added && self.is_playing {
+            self.send_midi_note_on(id);
+        }
+    }
+
+    /// Replace or remove the global variable with the given id, updating every variable and relative frequency
+    /// which depends on it
+    fn set_global_frequency(&mut self, id: usize, global_frequency: Option<GlobalFrequency>) {
+        match global_frequency {
+            Some(global_frequency) => self.global_frequencies.insert(id, global_frequency),
+            None => self.global_frequencies.remove(&id),
+        };
+        if let Err(error) = variables::evaluate(&mut self.global_frequencies) {
+            self.set_error(Error::Variable(error));
+        }
+        self.sync_dependents(id);
+    }
+
+    /// Replace, insert or remove the relative frequency with the given id, keeping its oscillator in sync
+    fn set_relative_frequency(&mut self, id: usize, relative_frequency: Option<RelativeFrequency>) {
+        match relative_frequency {
+            Some(relative_frequency) => match self.relative_frequencies.get_mut(&id) {
+                Some((current, _, _, _)) => {
+                    *current = relative_frequency;
+                    self.sync_relative_frequency(id);
+                }
+                None => self.insert_relative_frequency(id, relative_frequency),
+            },
+            None => self.delete_relative_frequency(id),
+        }
+    }
+
+    /// Get the current value of a part of the project
+    fn value(&self, target: Target) -> Value {
+        match target {
+            Target::GlobalFrequency(id) => {
+                Value::GlobalFrequency(id, self.global_frequencies.get(&id).cloned())
+            }
+            Target::RelativeFrequency(id) => Value::RelativeFrequency(
+                id,
+                self.relative_frequencies
+                    .get(&id)
+                    .map(|(relative_frequency, _, _, _)| relative_frequency.clone()),
+            ),
+            Target::Volume => Value::Volume(self.volume),
+            Target::WaveForm => Value::WaveForm(self.waveform.clone()),
+            Target::Envelope => Value::Envelope(self.envelope),
+            Target::Smoothing => Value::Smoothing(self.smoothing),
+            Target::PhaseReset => Value::PhaseReset(self.phase_reset),
+            Target::CustomHarmonics => Value::CustomHarmonics(self.custom_harmonics.clone()),
+            Target::Midi => Value::Midi(self.midi),
+            Target::Timeline => Value::Timeline(self.timeline.clone()),
+            Target::Graph => Value::Graph(self.graph.clone()),
+        }
+    }
+
+    /// Set a part of the project to a value, updating the audio engine to match it
+    fn set_value(&mut self, value: Value) {
+        match value {
+            Value::GlobalFrequency(id, global_frequency) => {
+                self.set_global_frequency(id, global_frequency)
+            }
+            Value::RelativeFrequency(id, relative_frequency) => {
+                self.set_relative_frequency(id, relative_frequency)
+            }
+            Value::Volume(volume) => {
+                self.engine.set_volume(volume);
+                self.volume = volume;
+            }
+            Value::WaveForm(waveform) => self.set_waveform(waveform),
+            Value::Envelope(envelope) => self.set_envelope(envelope),
+            Value::Smoothing(smoothing) => self.set_smoothing(smoothing),
+            Value::PhaseReset(phase_reset) => self.set_phase_reset(phase_reset),
+            Value::CustomHarmonics(harmonics) => self.set_custom_harmonics(harmonics),
+            Value::Midi(midi) => self.set_midi_mapping(midi),
+            Value::Timeline(timeline) => self.timeline = timeline,
+            Value::Graph(graph) => self.set_graph(graph),
+        }
+    }
+
+    /// Change a part of the project with the closure, recording the change so it can be undone
+    fn edit(&mut self, target: Target, edit: impl FnOnce(&mut Self)) {
+        self.edit_many(&[target], edit);
+    }
+
+    /// Change several parts of the project with the closure, recording the change so it can be undone as one
+    fn edit_many(&mut self, targets: &[Target], edit: impl FnOnce(&mut Self)) {
+        let before = targets.iter().map(|target| self.value(*target)).collect();
+        edit(self);
+        let after = targets.iter().map(|target| self.value(*target)).collect();
+        self.history.push(Edit { before, after });
+        self.unsave();
+    }
+
+    pub fn undo(&mut self) {
+        if let Some(edit) = self.history.undo() {
+            for value in edit.before.into_iter().rev() {
+                self.set_value(value);
+            }
+            self.unsave();
+        }
+    }
+
+    pub fn redo(&mut self) {
+        if let Some(edit) = self.history.redo() {
+            for value in edit.after {
+                self.set_value(value);
+            }
+            self.unsave();
+        }
+    }
+
+    /// Get the ratios of the relative frequencies of every global frequency as a scale, by the global frequency id
+    pub fn scala_scales(&self) -> Vec<(usize, Scale)> {
+        self.global_frequencies
+            .iter()
+            .filter_map(|(id, global_frequency)| {
+                let frequency = global_frequency.frequency()?;
+                let ratios: Vec<Ratio> = self
+                    .relative_frequencies
+                    .values()
+                    .filter(|(relative_frequency, _, _, _)| {
+                        relative_frequency.absolute_frequency_id() == *id
+                    })
+                    .map(|(relative_frequency, _, _, _)| relative_frequency.ratio())
+                    .collect();
+                (!ratios.is_empty()).then(|| {
+                    let description =
+                        format!("Harmony playground ratios of frequency {id} ({frequency} Hz)");
+                    (*id, Scale::from_ratios(description, ratios))
+                })
+            })
+            .collect()
+    }
+
+    /// Get the tuning of the voices, at the frequencies the engine plays them at. Voices are tuned on
+    /// the note they are mapped to for MIDI input, if any
+    pub fn mts_tuning(&self) -> Tuning {
+        let name = self
+            .file
+            .0
+            .as_ref()
+            .and_then(|path| path.file_stem())
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or(String::from("Harmony playground"));
+        Tuning::from_voices(
+            name,
+            self.relative_frequencies
+                .values()
+                .filter_map(|(relative_frequency, _, _, _)| {
+                    let frequency = self
+                        .global_frequencies
+                        .get(&relative_frequency.absolute_frequency_id())?
+                        .frequency()?;
+                    Some((
+                        relative_frequency.midi_note(),
+                        frequency * relative_frequency.multiplicand(),
+                    ))
+                }),
+        )
+    }
+
+    /// Take a snapshot of the current chord as a timeline step, where every voice is active
+    fn capture_step(&self) -> Step {
+        Step::new(
+            self.global_frequencies
+                .iter()
+                .filter_map(|(id, global_frequency)| Some((*id, global_frequency.frequency()?)))
+                .collect(),
+            self.relative_frequencies.keys().copied().collect(),
+        )
+    }
+
+    /// Resolve the steps of the timeline to the oscillators of the engine
+    fn transport_steps(&self) -> Vec<TransportStep> {
+        let sample_rate = self.engine.sample_rate() as f32;
+        self.timeline
+            .steps
+            .iter()
+            .map(|step| TransportStep {
+                frames: (step.duration * sample_rate).round().max(1.0) as usize,
+                frequencies: self
+                    .relative_frequencies
+                    .values()
+                    .filter_map(|(relative_frequency, _, shared_frequency, _)| {
+                        let id = relative_frequency.absolute_frequency_id();
+                        let frequency = step.global_frequency(id).or_else(|| {
+                            self.global_frequencies
+                                .get(&id)
+                                .and_then(GlobalFrequency::frequency)
+                        })?;
+                        Some((
+                            shared_frequency.clone(),
+                            frequency * relative_frequency.multiplicand(),
+                        ))
+                    })
+                    .collect(),
+                oscillators: self
+                    .relative_frequencies
+                    .iter()
+                    .filter(|(id, _)| step.active_voices.contains(id))
+                    .filter_map(|(_, (_, oscillator_id, _, _))| *oscillator_id)
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// Play the timeline from its first step. Edits made while it plays are heard the next time it is played
+    fn play_timeline(&mut self) {
+        if let Some(midi_output) = &mut self.midi_output {
+            midi_output.all_notes_off();
+        }
+        self.is_playing = false;
+        self.engine
+            .play_timeline(self.transport_steps(), self.timeline.looping);
+        self.is_timeline_playing = true;
+        self.playing_step = None;
+    }
+
+    /// Forget the playing timeline, moving every voice back to the frequency of the project
+    fn finish_timeline(&mut self) {
+        if !self.is_timeline_playing {
+            return;
+        }
+        self.is_timeline_playing = false;
+        self.playing_step = None;
+        let ids: Vec<usize> = self.relative_frequencies.keys().copied().collect();
+        for id in ids {
+            self.sync_relative_frequency(id);
+        }
+    }
+
+    fn update_timeline(&mut self, message: TimelineMessage) {
+        match message {
+            TimelineMessage::StepAdded => {
+                let step = self.capture_step();
+                self.edit(Target::Timeline, |state| state.timeline.steps.push(step));
+            }
+            TimelineMessage::StepDeleted(index) => {
+                self.edit(Target::Timeline, |state| {
+                    state.timeline.steps.remove(index);
+                });
+            }
+            TimelineMessage::StepDurationUpdated(index, duration) => {
+                self.edit(Target::Timeline, |state| {
+                    state.timeline.steps[index].duration = duration
+                });
+            }
+            TimelineMessage::StepVoiceToggled(index, id, is_active) => {
+                self.edit(Target::Timeline, |state| {
+                    let active_voices = &mut state.timeline.steps[index].active_voices;
+                    if is_active {
+                        active_voices.insert(id);
+                    } else {
+                        active_voices.remove(&id);
+                    }
+                });
+            }
+            TimelineMessage::StepCaptured(index) => {
+                let step = Step {
+                    duration: self.timeline.steps[index].duration,
+                    ..self.capture_step()
+                };
+                self.edit(Target::Timeline, |state| state.timeline.steps[index] = step);
+            }
+            TimelineMessage::StepRecalled(index) => {
+                // only frequencies which are written out are set, since the others follow them
+                let global_frequencies: Vec<(usize, GlobalFrequency)> = self.timeline.steps[index]
+                    .global_frequencies
+                    .iter()
+                    .filter_map(|(id, frequency)| {
+                        let mut global_frequency = self.global_frequencies.get(id)?.clone();
+                        global_frequency
+                            .set_frequency(*frequency)
+                            .then_some((*id, global_frequency))
+                    })
+                    .collect();
+                let targets: Vec<Target> = global_frequencies
+                    .iter()
+                    .map(|(id, _)| Target::GlobalFrequency(*id))
+                    .collect();
+                self.edit_many(&targets, |state| {
+                    for (id, global_frequency) in global_frequencies {
+                        state.set_global_frequency(id, Some(global_frequency));
+                    }
+                });
+            }
+            TimelineMessage::LoopToggled(looping) => {
+                self.edit(Target::Timeline, |state| state.timeline.looping = looping);
+            }
+            TimelineMessage::PlayPressed => self.play_timeline(),
+            TimelineMessage::StopPressed => {
+                self.engine.stop();
+                self.finish_timeline();
+            }
         }
     }
+
+    /// Set the graph the voices are processed by, keeping the graph the engine plays if it can't be compiled
+    fn set_graph(&mut self, graph: Graph) {
+        if let Err(error) = self.engine.set_graph(&graph) {
+            self.set_error(Error::Graph(error));
+        }
+        self.graph = graph;
+    }
+
+    fn update_graph(&mut self, message: GraphMessage) {
+        if let GraphMessage::NodeSelected(id) = message {
+            self.selected_node = id;
+            return;
+        }
+        let mut graph = self.graph.clone();
+        match graph_editor::update(&mut graph, message) {
+            Ok(()) => self.edit(Target::Graph, |state| state.set_graph(graph)),
+            // connections making a cycle are rejected
+            Err(error) => self.set_error(Error::Graph(error)),
+        }
+    }
+
+    /// Add the reference frequency of an imported scale as a global frequency, with a relative frequency per degree
+    pub fn import_scala(&mut self, import: ScalaImport) {
+        let global_frequency_id = self.next_global_frequency_id();
+        let first_id = self.next_relative_frequency_id();
+        let targets: Vec<Target> = once(Target::GlobalFrequency(global_frequency_id))
+            .chain((first_id..first_id + import.ratios.len()).map(Target::RelativeFrequency))
+            .collect();
+        self.edit_many(&targets, |state| {
+            state.add_global_frequency(import.reference_frequency);
+            for ratio in import.ratios {
+                state.add_relative_frequency(RelativeFrequency::new(
+                    global_frequency_id,
+                    ratio,
+                    -2.0,
+                ));
+            }
+        });
+    }
 }
 
 /// The main error type
@@ -376,6 +1254,30 @@ enum Error {
     /// Errors related to serialization and deserialization of the postcard binary format
     #[allow(dead_code)]
     Postcard(postcard::Error),
+    /// Errors related to serialization of the RON text format
+    #[allow(dead_code)]
+    Ron(ron::Error),
+    /// Errors related to loading project files, including files of unsupported format versions
+    #[allow(dead_code)]
+    Load(LoadError),
+    /// Errors related to writing rendered audio to wav files
+    #[allow(dead_code)]
+    Wav(Arc<hound::Error>),
+    /// Errors related to parsing malformed Scala scale and keyboard mapping files
+    #[allow(dead_code)]
+    Scala(ScalaError),
+    /// Errors related to opening a MIDI port
+    #[allow(dead_code)]
+    Midi(MidiError),
+    /// Errors related to global variables referencing each other in a cycle
+    #[allow(dead_code)]
+    Variable(VariableError),
+    /// Errors related to nodes of the graph being connected in a cycle
+    #[allow(dead_code)]
+    Graph(GraphError),
+    /// Errors related to reading the persisted settings
+    #[allow(dead_code)]
+    Settings(SettingsError),
 }
 
 impl std::fmt::Display for Error {
@@ -387,6 +1289,14 @@ impl std::fmt::Display for Error {
                 Error::FileDialogClosed => String::from("File dialog closed"),
                 Error::IO(error_kind) => error_kind.to_string(),
                 Error::Postcard(error) => error.to_string(),
+                Error::Ron(error) => error.to_string(),
+                Error::Load(error) => error.to_string(),
+                Error::Wav(error) => error.to_string(),
+                Error::Scala(error) => error.to_string(),
+                Error::Midi(error) => error.to_string(),
+                Error::Variable(error) => error.to_string(),
+                Error::Graph(error) => error.to_string(),
+                Error::Settings(error) => error.to_string(),
             }
         )
     }
@@ -407,6 +1317,17 @@ enum Message {
     AddRelativeFrequency,
     WaveFormUpdated(WaveForm),
     VolumeUpdated(f32),
+    EnvelopeUpdated(EnvelopeMessage),
+    SmoothingUpdated(SmoothingMessage),
+    PhaseResetToggled(bool),
+    Undo,
+    Redo,
+    /// Open the dialog for editing the envelope of a relative frequency
+    EditEnvelope(usize),
+    EnvelopeDialogUpdated(EnvelopeDialogMessage),
+    /// Open the dialog for designing the custom waveform
+    EditHarmonics,
+    HarmonicsDialogUpdated(HarmonicsDialogMessage),
     PlayPressed,
     StopPressed,
     ThemeUpdated(iced::Theme),
@@ -416,7 +1337,64 @@ enum Message {
     SaveLoaded(Result<(PathBuf, Arc<StateSave>), Error>),
     StateSaved(Result<PathBuf, Error>),
     SaveDialogUpdated(SaveDialogMessage),
+    RenderPressed,
+    RenderDialogUpdated(RenderDialogMessage),
+    Rendered(Result<PathBuf, Error>),
+    ImportScalaPressed,
+    ScalaImported(Result<ScalaImport, Error>),
+    ExportScalaPressed,
+    ScalaExported(Result<PathBuf, Error>),
+    /// Open the dialog for exporting the voices as MIDI Tuning Standard SysEx
+    ExportMtsPressed,
+    MtsDialogUpdated(MtsDialogMessage),
+    MtsExported(Result<PathBuf, Error>),
+    SettingsPressed,
+    SettingsDialogUpdated(SettingsDialogMessage),
+    /// Open the dialog for MIDI input and its mapping
+    MidiPressed,
+    MidiDialogUpdated(MidiDialogMessage),
+    MidiReceived(MidiEvent),
+    TimelineUpdated(TimelineMessage),
+    GraphUpdated(GraphMessage),
+    /// Poll which step of the timeline the engine is playing
+    TransportTicked,
+    MidiInputFailed(MidiError),
+    ScopeToggled(bool),
+    ScopeUpdated(ScopeMessage),
+    /// Poll the latest frames of the engine for the scope
+    ScopeTicked,
+}
+/// The status of the audio output shown in the bottom bar, warning when no device is playing the sound
+fn output_status<'a>(output: Option<&AudioOutput>) -> Element<'a, Message> {
+    let status = match output {
+        Some(output) if output.is_null() => match output.null_output_file() {
+            Some(path) => format!(
+                "No audio device, writing to {} - {} Hz",
+                path.display(),
+                output.sample_rate()
+            ),
+            None => format!("No audio device - {} Hz", output.sample_rate()),
+        },
+        Some(output) => format!(
+            "{} - {} Hz",
+            output.device_name().unwrap_or("Unknown device"),
+            output.sample_rate()
+        ),
+        None => String::from("No audio output"),
+    };
+    let is_playing_on_device = output.is_some_and(|output| !output.is_null());
+    text(status)
+        .style(move |theme: &iced::Theme| text::Style {
+            color: Some(if is_playing_on_device {
+                theme.palette().text.scale_alpha(0.6)
+            } else {
+                theme.palette().danger
+            }),
+        })
+        .size(12)
+        .into()
 }
+
 impl State {
     fn title(&self) -> String {
         let (ref path, has_saved) = self.file;
@@ -441,40 +1419,119 @@ impl State {
     fn update(&mut self, message: Message) -> Task<Message> {
         match message {
             Message::GlobalFrequencyUpdated { id, message } => {
-                self.update_global_frequency(id, message);
-                self.unsave();
+                self.edit(Target::GlobalFrequency(id), |state| {
+                    state.update_global_frequency(id, message)
+                });
                 Task::none()
             }
             Message::RelativeFrequencyUpdated { id, message } => {
-                self.update_relative_frequency(id, message);
-                self.unsave();
+                self.edit(Target::RelativeFrequency(id), |state| {
+                    state.update_relative_frequency(id, message)
+                });
                 Task::none()
             }
             Message::RelativeFrequencyDeleted(id) => {
-                self.delete_relative_frequency(id);
-                self.unsave();
+                self.edit_many(
+                    &[Target::RelativeFrequency(id), Target::Timeline],
+                    |state| {
+                        state.delete_relative_frequency(id);
+                        state.timeline.remove_voice(id);
+                    },
+                );
                 Task::none()
             }
             Message::AddGlobalFrequency => {
-                self.add_global_frequency(220.0);
-                self.unsave();
+                let id = self.next_global_frequency_id();
+                self.edit(Target::GlobalFrequency(id), |state| {
+                    state.add_global_frequency(220.0)
+                });
                 Task::none()
             }
             Message::AddRelativeFrequency => {
-                self.add_relative_frequency(RelativeFrequency::new(0, Ratio::new(1, 1), -2.0));
-                self.unsave();
+                let id = self.next_relative_frequency_id();
+                self.edit(Target::RelativeFrequency(id), |state| {
+                    state.add_relative_frequency(RelativeFrequency::new(0, Ratio::new(1, 1), -2.0))
+                });
                 Task::none()
             }
             Message::WaveFormUpdated(waveform) => {
-                self.set_waveform(waveform);
-                self.unsave();
+                self.edit(Target::WaveForm, |state| state.set_waveform(waveform));
                 Task::none()
             }
             Message::VolumeUpdated(volume) => {
-                self.set_volume(volume);
-                self.unsave();
+                self.edit(Target::Volume, |state| state.set_volume(volume));
+                Task::none()
+            }
+            Message::EnvelopeUpdated(message) => {
+                let mut envelope = self.envelope;
+                envelope_editor::update(&mut envelope, message);
+                self.edit(Target::Envelope, |state| state.set_envelope(envelope));
+                Task::none()
+            }
+            Message::SmoothingUpdated(message) => {
+                let mut smoothing = self.smoothing;
+                smoothing_editor::update(&mut smoothing, message);
+                self.edit(Target::Smoothing, |state| state.set_smoothing(smoothing));
+                Task::none()
+            }
+            Message::PhaseResetToggled(phase_reset) => {
+                self.edit(Target::PhaseReset, |state| {
+                    state.set_phase_reset(phase_reset)
+                });
+                Task::none()
+            }
+            Message::Undo => {
+                self.undo();
+                Task::none()
+            }
+            Message::Redo => {
+                self.redo();
+                Task::none()
+            }
+            Message::EditEnvelope(id) => {
+                self.editing_envelope = Some(id);
                 Task::none()
             }
+            Message::EnvelopeDialogUpdated(message) => {
+                let Some(id) = self.editing_envelope else {
+                    return Task::none();
+                };
+                if let EnvelopeDialogMessage::ClosePressed = message {
+                    self.editing_envelope = None;
+                    return Task::none();
+                }
+                let Some((relative_frequency, _, _, _)) = self.relative_frequencies.get(&id) else {
+                    self.editing_envelope = None;
+                    return Task::none();
+                };
+                let mut envelope = relative_frequency.envelope();
+                EnvelopeDialog::update(&mut envelope, self.envelope, message);
+                self.edit(Target::RelativeFrequency(id), |state| {
+                    state.update_relative_frequency(
+                        id,
+                        RelativeFrequencyMessage::EnvelopeUpdated(envelope),
+                    )
+                });
+                Task::none()
+            }
+            Message::EditHarmonics => {
+                self.show_harmonics_dialog = true;
+                Task::none()
+            }
+            Message::HarmonicsDialogUpdated(message) => match message {
+                HarmonicsDialogMessage::ClosePressed => {
+                    self.show_harmonics_dialog = false;
+                    Task::none()
+                }
+                HarmonicsDialogMessage::HarmonicsUpdated(message) => {
+                    let mut harmonics = self.custom_harmonics.clone();
+                    harmonics_editor::update(&mut harmonics, message);
+                    self.edit(Target::CustomHarmonics, |state| {
+                        state.set_custom_harmonics(harmonics)
+                    });
+                    Task::none()
+                }
+            },
             Message::ThemeUpdated(theme) => {
                 self.theme = theme;
                 Task::none()
@@ -486,8 +1543,9 @@ impl State {
                     return Task::none();
                 }
                 if !self.is_loading {
-                    self.engine.lock().unwrap().reset();
-                    *self = Self::new(self.engine.clone());
+                    self.engine.reset();
+                    let state = Self::new(self.engine.clone(), self.output.take());
+                    self.replace_project(state);
                 }
                 Task::none()
             }
@@ -521,12 +1579,14 @@ impl State {
 
                 match result {
                     Ok((path, save)) => {
-                        *self = Self::from_save(
+                        let state = Self::from_save(
                             self.engine.clone(),
+                            self.output.take(),
                             save,
                             Some(path),
                             self.theme.clone(),
                         );
+                        self.replace_project(state);
                     }
                     Err(error) => {
                         self.set_error(error);
@@ -552,11 +1612,19 @@ impl State {
                 Task::none()
             }
             Message::PlayPressed => {
-                self.engine.lock().unwrap().play();
+                self.finish_timeline();
+                self.engine.play();
+                self.is_playing = true;
+                self.send_midi_play();
                 Task::none()
             }
             Message::StopPressed => {
-                self.engine.lock().unwrap().stop();
+                self.engine.stop();
+                self.finish_timeline();
+                self.is_playing = false;
+                if let Some(midi_output) = &mut self.midi_output {
+                    midi_output.all_notes_off();
+                }
                 Task::none()
             }
             Message::SaveDialogUpdated(save_dialog_message) => {
@@ -577,49 +1645,322 @@ impl State {
                 }
                 Task::none()
             }
+            Message::RenderPressed => {
+                self.show_render_dialog = true;
+                Task::none()
+            }
+            Message::RenderDialogUpdated(render_dialog_message) => {
+                self.render_dialog.update(render_dialog_message);
+                match render_dialog_message {
+                    RenderDialogMessage::CancelPressed => {
+                        self.show_render_dialog = false;
+                        Task::none()
+                    }
+                    RenderDialogMessage::RenderPressed => {
+                        self.show_render_dialog = false;
+                        // render with a separate engine so the playback isn't affected
+                        let mut engine = AudioEngine::new(self.engine.sample_rate());
+                        if let Err(error) = self.to_save().initialize_engine(&mut engine) {
+                            self.set_error(Error::Graph(error));
+                            return Task::none();
+                        }
+                        Task::perform(
+                            render_file(
+                                engine,
+                                Duration::from_secs_f32(self.render_dialog.duration()),
+                                self.render_dialog.format(),
+                            ),
+                            Message::Rendered,
+                        )
+                    }
+                    _ => Task::none(),
+                }
+            }
+            Message::Rendered(result) => {
+                if let Err(error) = result {
+                    self.set_error(error);
+                }
+                Task::none()
+            }
+            Message::ImportScalaPressed => Task::perform(import_scala(), Message::ScalaImported),
+            Message::ScalaImported(result) => {
+                match result {
+                    Ok(import) => self.import_scala(import),
+                    Err(error) => self.set_error(error),
+                }
+                Task::none()
+            }
+            Message::ExportScalaPressed => {
+                Task::perform(export_scala(self.scala_scales()), Message::ScalaExported)
+            }
+            Message::ScalaExported(result) => {
+                if let Err(error) = result {
+                    self.set_error(error);
+                }
+                Task::none()
+            }
+            Message::ExportMtsPressed => {
+                self.show_mts_dialog = true;
+                Task::none()
+            }
+            Message::MtsDialogUpdated(mts_dialog_message) => {
+                self.mts_dialog.update(mts_dialog_message);
+                match mts_dialog_message {
+                    MtsDialogMessage::CancelPressed => {
+                        self.show_mts_dialog = false;
+                        Task::none()
+                    }
+                    MtsDialogMessage::ExportPressed => {
+                        self.show_mts_dialog = false;
+                        let sysex = self
+                            .mts_tuning()
+                            .to_sysex(self.mts_dialog.message(), self.mts_dialog.program());
+                        Task::perform(export_mts(sysex), Message::MtsExported)
+                    }
+                    _ => Task::none(),
+                }
+            }
+            Message::MtsExported(result) => {
+                if let Err(error) = result {
+                    self.set_error(error);
+                }
+                Task::none()
+            }
+            Message::MidiPressed => {
+                self.show_midi_dialog = true;
+                Task::none()
+            }
+            Message::MidiDialogUpdated(midi_dialog_message) => {
+                match midi_dialog_message {
+                    MidiDialogMessage::InputToggled(midi_input_enabled) => {
+                        self.release_midi();
+                        self.midi_input_enabled = midi_input_enabled;
+                    }
+                    MidiDialogMessage::TransposedFrequencyUpdated(FrequencyChoice(id)) => {
+                        let midi = MidiMapping {
+                            transposed_frequency: id,
+                            ..self.midi
+                        };
+                        self.edit(Target::Midi, |state| state.set_midi_mapping(midi));
+                    }
+                    MidiDialogMessage::ReferenceNoteUpdated(reference_note) => {
+                        let midi = MidiMapping {
+                            reference_note,
+                            ..self.midi
+                        };
+                        self.edit(Target::Midi, |state| state.set_midi_mapping(midi));
+                    }
+                    MidiDialogMessage::VoiceNoteUpdated(id, NoteChoice(note)) => {
+                        self.edit(Target::RelativeFrequency(id), |state| {
+                            state.update_relative_frequency(
+                                id,
+                                RelativeFrequencyMessage::MidiNoteUpdated(note),
+                            )
+                        });
+                    }
+                    MidiDialogMessage::OutputToggled(enabled) => {
+                        self.set_midi_output_enabled(enabled);
+                    }
+                    MidiDialogMessage::ZoneUpdated(zone) => {
+                        self.set_midi_output_settings(MidiOutputSettings {
+                            zone,
+                            ..self.midi_output_settings
+                        });
+                    }
+                    MidiDialogMessage::MemberChannelsUpdated(member_channels) => {
+                        self.set_midi_output_settings(MidiOutputSettings {
+                            member_channels,
+                            ..self.midi_output_settings
+                        });
+                    }
+                    MidiDialogMessage::BendRangeUpdated(bend_range) => {
+                        self.set_midi_output_settings(MidiOutputSettings {
+                            bend_range,
+                            ..self.midi_output_settings
+                        });
+                    }
+                    MidiDialogMessage::ClosePressed => {
+                        self.show_midi_dialog = false;
+                    }
+                }
+                Task::none()
+            }
+            Message::MidiReceived(event) => {
+                self.play_midi_event(event);
+                Task::none()
+            }
+            Message::TimelineUpdated(timeline_message) => {
+                self.update_timeline(timeline_message);
+                Task::none()
+            }
+            Message::GraphUpdated(graph_message) => {
+                self.update_graph(graph_message);
+                Task::none()
+            }
+            Message::TransportTicked => {
+                if self.engine.is_timeline_stopped() {
+                    self.finish_timeline();
+                } else {
+                    self.playing_step = self.engine.transport_step();
+                }
+                Task::none()
+            }
+            Message::MidiInputFailed(error) => {
+                self.midi_input_enabled = false;
+                self.set_error(Error::Midi(error));
+                Task::none()
+            }
+            Message::ScopeToggled(show_scope) => {
+                self.show_scope = show_scope;
+                if !show_scope {
+                    self.engine.set_scope_voice(None);
+                    self.scope = ScopeSnapshot::default();
+                }
+                Task::none()
+            }
+            Message::ScopeUpdated(scope_message) => {
+                match scope_message {
+                    ScopeMessage::VoiceSelected(choice) => self.scope_voice = choice.id,
+                    ScopeMessage::TriggerToggled(is_triggered) => {
+                        self.is_scope_triggered = is_triggered
+                    }
+                }
+                Task::none()
+            }
+            Message::ScopeTicked => {
+                // the voice is looked up every time, since its oscillator changes when it is initialized again
+                let oscillator_id = self.scope_voice.and_then(|id| {
+                    let (_, oscillator_id, _, _) = self.relative_frequencies.get(&id)?;
+                    *oscillator_id
+                });
+                if oscillator_id.is_none() {
+                    self.scope_voice = None;
+                }
+                self.engine.set_scope_voice(oscillator_id);
+                self.scope = self.engine.scope(scope_view::SNAPSHOT_FRAMES);
+                Task::none()
+            }
+            Message::SettingsPressed => {
+                let settings = Settings::load().unwrap_or_else(|error| {
+                    self.set_error(Error::Settings(error));
+                    Settings::default()
+                });
+                self.settings_dialog = Some(SettingsDialog::new(settings));
+                Task::none()
+            }
+            Message::SettingsDialogUpdated(settings_dialog_message) => {
+                let Some(settings_dialog) = &mut self.settings_dialog else {
+                    return Task::none();
+                };
+                settings_dialog.update(settings_dialog_message.clone());
+                match settings_dialog_message {
+                    SettingsDialogMessage::NullOutputFilePressed => {
+                        return Task::perform(pick_null_output_file(), |path| {
+                            Message::SettingsDialogUpdated(
+                                SettingsDialogMessage::NullOutputFileChosen(path),
+                            )
+                        });
+                    }
+                    SettingsDialogMessage::CancelPressed => {
+                        self.settings_dialog = None;
+                    }
+                    SettingsDialogMessage::ApplyPressed => {
+                        let settings = settings_dialog.settings().clone();
+                        self.settings_dialog = None;
+                        self.reopen_output(&settings);
+                        if let Err(error) = settings.save() {
+                            self.set_error(Error::IO(error.kind()));
+                        }
+                    }
+                    _ => {}
+                }
+                Task::none()
+            }
         }
     }
 
+    /// Undo with Ctrl+Z and redo with Ctrl+Shift+Z, or Cmd instead of Ctrl on macOS,
+    /// receive MIDI input while it is enabled, follow the transport while the timeline plays
+    /// and redraw the scope while it is shown
+    fn subscription(&self) -> Subscription<Message> {
+        let history = keyboard::on_key_press(|key, modifiers| match key.as_ref() {
+            keyboard::Key::Character(character)
+                if modifiers.command() && character.eq_ignore_ascii_case("z") =>
+            {
+                Some(if modifiers.shift() {
+                    Message::Redo
+                } else {
+                    Message::Undo
+                })
+            }
+            _ => None,
+        });
+        let midi_input = if self.midi_input_enabled {
+            Subscription::run(midi_input)
+        } else {
+            Subscription::none()
+        };
+        let transport = if self.is_timeline_playing {
+            iced::time::every(Duration::from_millis(50)).map(|_| Message::TransportTicked)
+        } else {
+            Subscription::none()
+        };
+        let scope = if self.show_scope {
+            iced::time::every(Duration::from_millis(33)).map(|_| Message::ScopeTicked)
+        } else {
+            Subscription::none()
+        };
+        Subscription::batch([history, midi_input, transport, scope])
+    }
+
     fn view(&self) -> Element<Message> {
         let global_frequencies = iced::widget::scrollable(
             column(
                 self.global_frequencies
                     .iter()
                     .map(|(index, freq)| {
-                        freq.view()
-                            .map(move |message| Message::GlobalFrequencyUpdated {
+                        freq.view(&self.global_frequencies).map(move |message| {
+                            Message::GlobalFrequencyUpdated {
                                 id: index.to_owned(),
                                 message,
-                            })
+                            }
+                        })
                     })
                     .chain(once(
                         icon_button(icon::plus(), 14)
                             .on_press(Message::AddGlobalFrequency)
-                            .width(200)
+                            .width(240)
                             .into(),
                     )),
             )
             .spacing(1),
         );
 
+        let integers = VariableChoice::terms(&self.global_frequencies);
         let relative_frequencies = iced::widget::scrollable(
             row(self
                 .relative_frequencies
                 .iter()
                 .map(|(id, (relative_frequency, _, shared_frequency, _))| {
                     relative_frequency
-                        .view(self.global_frequencies.len(), shared_frequency.get())
+                        .view(
+                            self.global_frequencies.len(),
+                            shared_frequency.get(),
+                            &self.custom_harmonics,
+                            &integers,
+                        )
                         .map(move |message| match message {
                             RelativeFrequencyMessage::Deleted => {
                                 Message::RelativeFrequencyDeleted(*id)
                             }
+                            RelativeFrequencyMessage::EnvelopePressed => Message::EditEnvelope(*id),
                             message => Message::RelativeFrequencyUpdated { id: *id, message },
                         })
                 })
                 .chain(once(
                     icon_button(icon::plus(), 14)
                         .on_press(Message::AddRelativeFrequency)
-                        .height(200)
+                        .height(265)
                         .into(),
                 )))
             .spacing(1),
@@ -633,33 +1974,27 @@ impl State {
         .align_x(Horizontal::Center)
         .height(Length::Fill);
 
-        let waveform_selection = column![
-            radio(
-                "Sine",
-                WaveForm::Sine,
-                self.waveform,
-                Message::WaveFormUpdated
-            ),
-            radio(
-                "Triangle",
-                WaveForm::Triangle,
-                self.waveform,
-                Message::WaveFormUpdated
-            ),
-            radio(
-                "Square",
-                WaveForm::Square,
-                self.waveform,
-                Message::WaveFormUpdated
-            ),
-            radio(
-                "Saw",
-                WaveForm::Saw,
-                self.waveform,
-                Message::WaveFormUpdated
-            ),
-        ]
-        .spacing(12.5);
+        let selected_waveform = WaveForm::ALL
+            .iter()
+            .position(|waveform| *waveform == self.waveform)
+            .unwrap_or(WaveForm::ALL.len());
+        let waveform_selection = column(
+            WaveForm::ALL
+                .into_iter()
+                .chain(once(WaveForm::Custom(self.custom_harmonics.clone())))
+                .enumerate()
+                .map(|(index, waveform)| {
+                    radio(
+                        waveform.to_string(),
+                        index,
+                        Some(selected_waveform),
+                        move |_| Message::WaveFormUpdated(waveform),
+                    )
+                    .into()
+                }),
+        )
+        .push(button(text("Harmonics").size(12)).on_press(Message::EditHarmonics))
+        .spacing(10);
 
         let theme_selection = combo_box(
             &self.theme_selector_state,
@@ -691,9 +2026,23 @@ impl State {
             button("New").on_press(Message::NewFile),
             button("Open").on_press(Message::OpenFile),
             button("Save").on_press(Message::SaveFile),
+            button("Render").on_press(Message::RenderPressed),
+            button("Import scale").on_press(Message::ImportScalaPressed),
+            button("Export scale").on_press_maybe(
+                (!self.relative_frequencies.is_empty()).then_some(Message::ExportScalaPressed)
+            ),
+            button("Export MTS").on_press_maybe(
+                (!self.relative_frequencies.is_empty()).then_some(Message::ExportMtsPressed)
+            ),
+            button("Settings").on_press(Message::SettingsPressed),
+            button("MIDI").on_press(Message::MidiPressed),
+            button("Undo").on_press_maybe(self.history.can_undo().then_some(Message::Undo)),
+            button("Redo").on_press_maybe(self.history.can_redo().then_some(Message::Redo)),
             horizontal_space().width(Length::Fill),
             audio_button(icon::play(), Message::PlayPressed),
             audio_button(icon::stop(), Message::StopPressed),
+            checkbox("Reset phase", self.phase_reset).on_toggle(Message::PhaseResetToggled),
+            checkbox("Scope", self.show_scope).on_toggle(Message::ScopeToggled),
             horizontal_space().width(Length::Fill),
             container(theme_selection).width(150)
         ]
@@ -711,6 +2060,7 @@ impl State {
             })
             .size(15),
             horizontal_space().width(Length::Fill),
+            output_status(self.output.as_ref()),
         ];
 
         let window_content = container(
@@ -739,7 +2089,7 @@ impl State {
                         .align_x(Horizontal::Center)
                     )
                     .padding(5)
-                    .height(200)
+                    .height(285)
                     .style(|theme: &iced::Theme| {
                         iced::widget::container::Style::default().border(
                             iced::Border::default()
@@ -756,7 +2106,43 @@ impl State {
                         ]
                         .align_x(Horizontal::Center)
                     )
-                    .max_height(200)
+                    .max_height(285)
+                    .padding(10)
+                    .style(|theme: &iced::Theme| {
+                        iced::widget::container::Style::default().border(
+                            iced::Border::default()
+                                .width(1)
+                                .rounded(2)
+                                .color(theme.palette().background.inverse().scale_alpha(0.4)),
+                        )
+                    }),
+                    container(
+                        column![
+                            text("Envelope"),
+                            envelope_editor::view(&self.envelope).map(Message::EnvelopeUpdated)
+                        ]
+                        .align_x(Horizontal::Center)
+                        .spacing(5)
+                    )
+                    .max_height(285)
+                    .padding(10)
+                    .style(|theme: &iced::Theme| {
+                        iced::widget::container::Style::default().border(
+                            iced::Border::default()
+                                .width(1)
+                                .rounded(2)
+                                .color(theme.palette().background.inverse().scale_alpha(0.4)),
+                        )
+                    }),
+                    container(
+                        column![
+                            text("Smoothing"),
+                            smoothing_editor::view(&self.smoothing).map(Message::SmoothingUpdated)
+                        ]
+                        .align_x(Horizontal::Center)
+                        .spacing(5)
+                    )
+                    .max_height(285)
                     .padding(10)
                     .style(|theme: &iced::Theme| {
                         iced::widget::container::Style::default().border(
@@ -767,7 +2153,7 @@ impl State {
                         )
                     }),
                     container(master_volume_slider)
-                        .max_height(200)
+                        .max_height(285)
                         .padding(10)
                         .style(|theme: &iced::Theme| {
                             iced::widget::container::Style::default().border(
@@ -780,6 +2166,76 @@ impl State {
                 ]
                 //.height(150)
                 .spacing(10),
+                row![
+                    container(
+                        graph_editor::view(&self.graph, self.selected_node)
+                            .map(Message::GraphUpdated)
+                    )
+                    .padding(5)
+                    .height(260)
+                    .style(|theme: &iced::Theme| {
+                        iced::widget::container::Style::default().border(
+                            iced::Border::default()
+                                .width(1)
+                                .rounded(2)
+                                .color(theme.palette().background.inverse().scale_alpha(0.4)),
+                        )
+                    })
+                ]
+                .push_maybe(self.show_scope.then(|| {
+                    container(
+                        scope_view::view(
+                            &self.scope,
+                            self.engine.sample_rate(),
+                            &self
+                                .relative_frequencies
+                                .iter()
+                                .filter(|(_, (_, oscillator_id, _, _))| oscillator_id.is_some())
+                                .map(|(id, (relative_frequency, _, _, _))| {
+                                    (*id, relative_frequency)
+                                })
+                                .collect::<Vec<_>>(),
+                            &self.global_frequencies,
+                            self.scope_voice,
+                            self.is_scope_triggered,
+                        )
+                        .map(Message::ScopeUpdated),
+                    )
+                    .padding(5)
+                    .width(420)
+                    .height(260)
+                    .style(|theme: &iced::Theme| {
+                        iced::widget::container::Style::default().border(
+                            iced::Border::default()
+                                .width(1)
+                                .rounded(2)
+                                .color(theme.palette().background.inverse().scale_alpha(0.4)),
+                        )
+                    })
+                }))
+                .spacing(10),
+                container(
+                    timeline_editor::view(
+                        &self.timeline,
+                        &self
+                            .relative_frequencies
+                            .iter()
+                            .map(|(id, (relative_frequency, _, _, _))| (*id, relative_frequency))
+                            .collect::<Vec<_>>(),
+                        self.playing_step,
+                    )
+                    .map(Message::TimelineUpdated)
+                )
+                .padding(5)
+                .height(220)
+                .style(|theme: &iced::Theme| {
+                    iced::widget::container::Style::default().border(
+                        iced::Border::default()
+                            .width(1)
+                            .rounded(2)
+                            .color(theme.palette().background.inverse().scale_alpha(0.4)),
+                    )
+                }),
                 bottom_bar,
             ]
             .spacing(10),
@@ -792,6 +2248,51 @@ impl State {
                 window_content,
                 SaveDialog::view().map(Message::SaveDialogUpdated),
             )
+        } else if let Some(settings_dialog) = &self.settings_dialog {
+            modal(
+                window_content,
+                settings_dialog.view().map(Message::SettingsDialogUpdated),
+            )
+        } else if self.show_mts_dialog {
+            modal(
+                window_content,
+                self.mts_dialog.view().map(Message::MtsDialogUpdated),
+            )
+        } else if self.show_render_dialog {
+            modal(
+                window_content,
+                self.render_dialog.view().map(Message::RenderDialogUpdated),
+            )
+        } else if self.show_midi_dialog {
+            modal(
+                window_content,
+                MidiDialog::view(
+                    self.midi_input_enabled,
+                    &self.midi,
+                    self.midi_output.is_some(),
+                    &self.midi_output_settings,
+                    VariableChoice::all(&self.global_frequencies, VariableType::Frequency)
+                        .filter_map(|choice| choice.0),
+                    self.relative_frequencies
+                        .iter()
+                        .map(|(id, (relative_frequency, _, _, _))| (*id, relative_frequency)),
+                )
+                .map(Message::MidiDialogUpdated),
+            )
+        } else if self.show_harmonics_dialog {
+            modal(
+                window_content,
+                HarmonicsDialog::view(&self.custom_harmonics).map(Message::HarmonicsDialogUpdated),
+            )
+        } else if let Some((relative_frequency, _, _, _)) = self
+            .editing_envelope
+            .and_then(|id| self.relative_frequencies.get(&id))
+        {
+            modal(
+                window_content,
+                EnvelopeDialog::view(relative_frequency.envelope())
+                    .map(Message::EnvelopeDialogUpdated),
+            )
         } else {
             window_content
         }
@@ -800,7 +2301,7 @@ impl State {
 async fn open_file() -> Result<(PathBuf, Arc<StateSave>), Error> {
     let picked_file = rfd::AsyncFileDialog::new()
         .set_title("Open a file...")
-        .add_filter("Harmony playground file", &["harm"])
+        .add_filter("Harmony playground file", &["harm", TEXT_EXTENSION])
         .pick_file()
         .await
         .ok_or(Error::FileDialogClosed)?;
@@ -813,10 +2314,10 @@ async fn load_file(path: impl Into<PathBuf>) -> Result<(PathBuf, Arc<StateSave>)
 
     let contents = tokio::fs::read(&path)
         .await
-        .map(|bytevec| postcard::from_bytes(&bytevec))
+        .map(|bytevec| StateSave::from_file(&path, &bytevec))
         .map_err(|tokio_fs_error| Error::IO(tokio_fs_error.kind()))?
         .map(Arc::new)
-        .map_err(Error::Postcard)?;
+        .map_err(Error::Load)?;
 
     Ok((path, contents))
 }
@@ -827,6 +2328,7 @@ async fn save_file(path: Option<PathBuf>, state_save: Arc<StateSave>) -> Result<
     } else {
         rfd::AsyncFileDialog::new()
             .add_filter("Harmony playground file", &["harm"])
+            .add_filter("Harmony playground text file", &[TEXT_EXTENSION])
             .save_file()
             .await
             .as_ref()
@@ -835,7 +2337,10 @@ async fn save_file(path: Option<PathBuf>, state_save: Arc<StateSave>) -> Result<
             .ok_or(Error::FileDialogClosed)?
     };
 
-    let contents = to_allocvec(&*state_save).map_err(Error::Postcard)?;
+    let contents = match FileFormat::from_path(&path) {
+        FileFormat::Binary => state_save.to_bytes().map_err(Error::Postcard)?,
+        FileFormat::Text => state_save.to_text().map_err(Error::Ron)?.into_bytes(),
+    };
 
     tokio::fs::write(&path, contents)
         .await
@@ -846,51 +2351,182 @@ async fn save_file(path: Option<PathBuf>, state_save: Arc<StateSave>) -> Result<
     Ok(path)
 }
 
-struct AudioSource(Arc<Mutex<AudioEngine>>);
+/// Pick a Scala scale file and optionally a keyboard mapping file, and read the frequencies to import from them
+async fn import_scala() -> Result<ScalaImport, Error> {
+    let picked_files = rfd::AsyncFileDialog::new()
+        .set_title("Pick a scale and optionally a keyboard mapping...")
+        .add_filter("Scala file", &["scl", "kbm"])
+        .pick_files()
+        .await
+        .ok_or(Error::FileDialogClosed)?;
 
-impl Iterator for AudioSource {
-    type Item = f32;
+    let has_extension = |file: &&rfd::FileHandle, extension: &str| {
+        file.path()
+            .extension()
+            .is_some_and(|file_extension| file_extension.eq_ignore_ascii_case(extension))
+    };
+    let scale_file = picked_files
+        .iter()
+        .find(|file| has_extension(file, "scl"))
+        .ok_or(Error::Scala(ScalaError::MissingScale))?;
+    let mapping_file = picked_files.iter().find(|file| has_extension(file, "kbm"));
+
+    let read = |file: &rfd::FileHandle| {
+        let path = file.path().to_owned();
+        async move {
+            tokio::fs::read_to_string(path)
+                .await
+                .map_err(|error| Error::IO(error.kind()))
+        }
+    };
+    let scale = Scale::parse(&read(scale_file).await?).map_err(Error::Scala)?;
+    let mapping = match mapping_file {
+        Some(mapping_file) => {
+            Some(KeyboardMapping::parse(&read(mapping_file).await?).map_err(Error::Scala)?)
+        }
+        None => None,
+    };
 
-    fn next(&mut self) -> Option<Self::Item> {
-        self.0.lock().unwrap().next()
-    }
+    ScalaImport::new(&scale, mapping.as_ref()).map_err(Error::Scala)
 }
 
-impl Source for AudioSource {
-    fn current_frame_len(&self) -> Option<usize> {
-        None //TODO: maybe research this more
-    }
+/// Write every scale to a .scl file. When there are several, the id of their global frequency is added to the file names
+async fn export_scala(scales: Vec<(usize, Scale)>) -> Result<PathBuf, Error> {
+    let path = rfd::AsyncFileDialog::new()
+        .add_filter("Scala file", &["scl"])
+        .save_file()
+        .await
+        .as_ref()
+        .map(rfd::FileHandle::path)
+        .map(std::path::Path::to_owned)
+        .ok_or(Error::FileDialogClosed)?;
 
-    fn channels(&self) -> u16 {
-        1
+    let is_single = scales.len() == 1;
+    for (id, scale) in scales {
+        let scale_path = if is_single {
+            path.clone()
+        } else {
+            let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+            path.with_file_name(format!("{stem}_{id}.scl"))
+        };
+        tokio::fs::write(&scale_path, scale.to_string())
+            .await
+            .map_err(|error| Error::IO(error.kind()))?;
+        println!("exported scale to file {scale_path:?}");
     }
 
-    fn sample_rate(&self) -> u32 {
-        48000
-    }
+    Ok(path)
+}
 
-    fn total_duration(&self) -> Option<std::time::Duration> {
-        None
-    }
+async fn export_mts(sysex: Vec<u8>) -> Result<PathBuf, Error> {
+    let path = rfd::AsyncFileDialog::new()
+        .add_filter("SysEx file", &["syx"])
+        .save_file()
+        .await
+        .as_ref()
+        .map(rfd::FileHandle::path)
+        .map(std::path::Path::to_owned)
+        .ok_or(Error::FileDialogClosed)?;
+
+    tokio::fs::write(&path, sysex)
+        .await
+        .map_err(|error| Error::IO(error.kind()))?;
+    println!("exported tuning to file {path:?}");
+
+    Ok(path)
 }
 
-fn main() -> color_eyre::eyre::Result<()> {
-    color_eyre::install()?;
+/// Receive the events of the MIDI input port for as long as the subscription runs
+fn midi_input() -> impl iced::futures::Stream<Item = Message> {
+    iced::stream::channel(100, |mut output| async move {
+        // the port is owned by its own thread, which closes it once the subscription is dropped
+        std::thread::spawn(move || {
+            let mut events = output.clone();
+            match MidiInputPort::open(move |event| {
+                let _ = events.try_send(Message::MidiReceived(event));
+            }) {
+                Ok(_port) => {
+                    while !output.is_closed() {
+                        std::thread::sleep(Duration::from_millis(100));
+                    }
+                }
+                Err(error) => {
+                    let _ = output.try_send(Message::MidiInputFailed(error));
+                }
+            }
+        });
+        std::future::pending::<()>().await
+    })
+}
 
-    let engine = Arc::new(Mutex::new(AudioEngine::new(48000)));
+async fn pick_null_output_file() -> Option<PathBuf> {
+    rfd::AsyncFileDialog::new()
+        .add_filter("Wave audio file", &["wav"])
+        .save_file()
+        .await
+        .as_ref()
+        .map(rfd::FileHandle::path)
+        .map(std::path::Path::to_owned)
+}
+
+async fn render_file(
+    mut engine: AudioEngine,
+    duration: Duration,
+    format: SampleFormat,
+) -> Result<PathBuf, Error> {
+    let path = rfd::AsyncFileDialog::new()
+        .add_filter("Wave audio file", &["wav"])
+        .save_file()
+        .await
+        .as_ref()
+        .map(rfd::FileHandle::path)
+        .map(std::path::Path::to_owned)
+        .ok_or(Error::FileDialogClosed)?;
+
+    engine.play();
+    render_to_wav(&mut engine, duration, format, &path)
+        .map_err(|error| Error::Wav(Arc::new(error)))?;
+
+    println!("rendered to file {path:?}");
+
+    Ok(path)
+}
 
-    let audio_source = AudioSource(engine.clone()).low_pass(2000);
+fn main() -> color_eyre::eyre::Result<()> {
+    color_eyre::install()?;
 
-    let (_stream, stream_handle) = OutputStream::try_default().unwrap();
-    let _ = stream_handle.play_raw(audio_source.convert_samples());
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    // a single argument which isn't a command or an option is a project to open, like when opening a file with the program
+    let project = match args.as_slice() {
+        [] => None,
+        [path] if !cli::is_command(path) && !path.starts_with('-') => Some(PathBuf::from(path)),
+        _ => return cli::run(args),
+    };
 
-    let state = State::new(engine);
+    let (settings, settings_error) = match Settings::load() {
+        Ok(settings) => (settings, None),
+        Err(error) => (Settings::default(), Some(error)),
+    };
+    let (output, controller) = AudioOutput::from_settings(&settings);
+    let mut state = State::new(controller, Some(output));
+    state.midi_output_settings = settings.midi_output;
+    // the defaults are used, but the user is told their settings couldn't be read
+    if let Some(error) = settings_error {
+        state.set_error(Error::Settings(error));
+    }
 
     iced::application(State::title, State::update, State::view)
+        .subscription(State::subscription)
         .theme(|state| state.theme.clone())
         .font(icon::FONT)
         .font(iced_fonts::REQUIRED_FONT_BYTES)
-        .run_with(move || (state, Task::none()))
+        .run_with(move || match project {
+            Some(path) => {
+                state.is_loading = true;
+                (state, Task::perform(load_file(path), Message::SaveLoaded))
+            }
+            None => (state, Task::none()),
+        })
         .unwrap();
     Ok(())
 }
@@ -0,0 +1,430 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use midir::{
+    ConnectErrorKind, InitError, MidiInput, MidiInputConnection, MidiOutput, MidiOutputConnection,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::audio::theory::{Note, NoteName};
+
+/// The name of the client and port other programs connect to
+pub const PORT_NAME: &str = "Harmony playground";
+
+/// The highest MIDI note number
+pub const MAX_NOTE: u8 = 127;
+
+/// The most member channels an MPE zone can have, which is every channel but the manager channel
+pub const MAX_MEMBER_CHANNELS: u8 = 15;
+
+/// The largest pitch bend range in semitones, which MPE allows for member channels
+pub const MAX_BEND_RANGE: u8 = 96;
+
+/// The pitch bend which leaves a note unbent
+const CENTER_BEND: f32 = 8192.0;
+
+/// A message from a MIDI input which the playground reacts to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MidiEvent {
+    NoteOn { note: u8, velocity: u8 },
+    NoteOff { note: u8 },
+}
+
+impl MidiEvent {
+    /// Parse a raw MIDI message on any channel, ignoring every kind of message but notes
+    pub fn parse(message: &[u8]) -> Option<Self> {
+        match *message {
+            // a note on with zero velocity is a note off
+            [status, note, 0] if status & 0xF0 == 0x90 => Some(Self::NoteOff { note }),
+            [status, note, velocity] if status & 0xF0 == 0x90 => {
+                Some(Self::NoteOn { note, velocity })
+            }
+            [status, note, _] if status & 0xF0 == 0x80 => Some(Self::NoteOff { note }),
+            _ => None,
+        }
+    }
+}
+
+/// How incoming notes play the project. Voices are gated by the note of their own relative frequency,
+/// and a global frequency can be transposed by every note
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MidiMapping {
+    /// The global frequency incoming notes transpose and gate the voices of, if any
+    pub transposed_frequency: Option<usize>,
+    /// The note which plays the transposed global frequency at its own frequency
+    pub reference_note: u8,
+}
+
+impl Default for MidiMapping {
+    fn default() -> Self {
+        Self {
+            transposed_frequency: None,
+            // middle C
+            reference_note: 60,
+        }
+    }
+}
+
+impl MidiMapping {
+    /// Get the multiplicand a note transposes the global frequency by, in 12-TET steps from the reference note
+    pub fn transposition(&self, note: u8) -> f32 {
+        ((note as f32 - self.reference_note as f32) / 12.0).exp2()
+    }
+}
+
+/// Get the name of a MIDI note, like C4 for note 60
+pub fn note_name(note: u8) -> String {
+    let name = NoteName::from_index((note % 12) as isize);
+    format!("{}{}", name.get_str(), note as i32 / 12 - 1)
+}
+
+/// An open MIDI input, which stops receiving events when dropped
+pub struct MidiInputPort {
+    _connection: MidiInputConnection<()>,
+}
+
+impl MidiInputPort {
+    /// Open a virtual input port other programs can connect to, calling the callback with every event
+    /// on the MIDI thread
+    #[cfg(unix)]
+    pub fn open(mut callback: impl FnMut(MidiEvent) + Send + 'static) -> Result<Self, MidiError> {
+        use midir::os::unix::VirtualInput;
+
+        let input = MidiInput::new(PORT_NAME)?;
+        let connection = input
+            .create_virtual(
+                PORT_NAME,
+                move |_, message, _| {
+                    if let Some(event) = MidiEvent::parse(message) {
+                        callback(event)
+                    }
+                },
+                (),
+            )
+            .map_err(|error| MidiError::Connect(error.kind()))?;
+        Ok(Self {
+            _connection: connection,
+        })
+    }
+
+    /// Connect to the first input port, since virtual ports are only supported on unix,
+    /// calling the callback with every event on the MIDI thread
+    #[cfg(not(unix))]
+    pub fn open(mut callback: impl FnMut(MidiEvent) + Send + 'static) -> Result<Self, MidiError> {
+        let input = MidiInput::new(PORT_NAME)?;
+        let port = input.ports().into_iter().next().ok_or(MidiError::NoPort)?;
+        let connection = input
+            .connect(
+                &port,
+                PORT_NAME,
+                move |_, message, _| {
+                    if let Some(event) = MidiEvent::parse(message) {
+                        callback(event)
+                    }
+                },
+                (),
+            )
+            .map_err(|error| MidiError::Connect(error.kind()))?;
+        Ok(Self {
+            _connection: connection,
+        })
+    }
+}
+
+/// Which end of the MIDI channels an MPE zone takes up. The lower zone is managed on channel 1 with
+/// member channels counting up from 2, and the upper zone on channel 16 with member channels counting down from 15
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MpeZone {
+    #[default]
+    Lower,
+    Upper,
+}
+
+impl MpeZone {
+    pub const ALL: [Self; 2] = [Self::Lower, Self::Upper];
+}
+
+impl Display for MpeZone {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MpeZone::Lower => write!(f, "Lower"),
+            MpeZone::Upper => write!(f, "Upper"),
+        }
+    }
+}
+
+/// How voices are sent to a MIDI output, where every voice gets its own member channel of an MPE zone
+/// so it can be bent to its exact frequency
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MidiOutputSettings {
+    pub zone: MpeZone,
+    /// The number of member channels in the zone, which is how many voices can be sent at once
+    pub member_channels: u8,
+    /// How many semitones the largest pitch bend moves a note
+    pub bend_range: u8,
+}
+
+impl Default for MidiOutputSettings {
+    fn default() -> Self {
+        Self {
+            zone: MpeZone::Lower,
+            member_channels: MAX_MEMBER_CHANNELS,
+            // the default bend range of member channels in MPE
+            bend_range: 48,
+        }
+    }
+}
+
+impl MidiOutputSettings {
+    /// Get the channel of the zone which configures it, numbered from 0
+    fn manager_channel(&self) -> u8 {
+        match self.zone {
+            MpeZone::Lower => 0,
+            MpeZone::Upper => 15,
+        }
+    }
+
+    /// Get the channels of the zone which play notes, numbered from 0
+    fn member_channels(&self) -> impl Iterator<Item = u8> {
+        let zone = self.zone;
+        (1..=self.member_channels.clamp(1, MAX_MEMBER_CHANNELS)).map(move |offset| match zone {
+            MpeZone::Lower => offset,
+            MpeZone::Upper => 15 - offset,
+        })
+    }
+
+    /// Get the 14 bit pitch bend which moves a note by the cents, clamped to the bend range
+    pub fn pitch_bend(&self, cents: f32) -> u16 {
+        let range = self.bend_range.max(1) as f32 * 100.0;
+        (CENTER_BEND + cents / range * CENTER_BEND)
+            .round()
+            .clamp(0.0, 16383.0) as u16
+    }
+}
+
+/// Get how many cents a frequency is above MIDI note 0, or None if the closest note can't be played over MIDI
+fn midi_cents(frequency: f32) -> Option<f32> {
+    let note = Note::from_frequency(frequency);
+    (0..=MAX_NOTE as i32)
+        .contains(&note.midi_note())
+        .then(|| note.midi_note() as f32 * 100.0 + note.cent_offset())
+}
+
+/// Where the messages of a MIDI output are sent, which is a connection to another program
+/// unless they are collected to be checked
+pub trait MidiSender {
+    fn send(&mut self, message: &[u8]);
+}
+
+impl MidiSender for MidiOutputConnection {
+    fn send(&mut self, message: &[u8]) {
+        // if the receiving program is gone there is nothing left to play
+        let _ = MidiOutputConnection::send(self, message);
+    }
+}
+
+impl MidiSender for Vec<Vec<u8>> {
+    fn send(&mut self, message: &[u8]) {
+        self.push(message.to_vec());
+    }
+}
+
+/// An open MIDI output, which sends every voice as a note on its own member channel,
+/// bent from the nearest note to the exact frequency of the voice
+pub struct MidiOutputPort<S: MidiSender = MidiOutputConnection> {
+    connection: S,
+    settings: MidiOutputSettings,
+    /// Every voice being sent, by the id of its relative frequency
+    voices: BTreeMap<usize, SentVoice>,
+}
+
+/// A voice being sent as a note on a member channel
+#[derive(Debug, Clone, Copy)]
+struct SentVoice {
+    channel: u8,
+    note: u8,
+    velocity: u8,
+}
+
+impl MidiOutputPort {
+    /// Open a virtual output port other programs can connect to, and configure the MPE zone on it
+    #[cfg(unix)]
+    pub fn open(settings: MidiOutputSettings) -> Result<Self, MidiError> {
+        use midir::os::unix::VirtualOutput;
+
+        let output = MidiOutput::new(PORT_NAME)?;
+        let connection = output
+            .create_virtual(PORT_NAME)
+            .map_err(|error| MidiError::Connect(error.kind()))?;
+        Ok(Self::new(connection, settings))
+    }
+
+    /// Connect to the first output port, since virtual ports are only supported on unix,
+    /// and configure the MPE zone on it
+    #[cfg(not(unix))]
+    pub fn open(settings: MidiOutputSettings) -> Result<Self, MidiError> {
+        let output = MidiOutput::new(PORT_NAME)?;
+        let port = output.ports().into_iter().next().ok_or(MidiError::NoPort)?;
+        let connection = output
+            .connect(&port, PORT_NAME)
+            .map_err(|error| MidiError::Connect(error.kind()))?;
+        Ok(Self::new(connection, settings))
+    }
+}
+
+impl<S: MidiSender> MidiOutputPort<S> {
+    /// Send to a connection, configuring the MPE zone on it
+    pub fn new(connection: S, settings: MidiOutputSettings) -> Self {
+        let mut port = Self {
+            connection,
+            settings,
+            voices: BTreeMap::new(),
+        };
+        port.configure(settings);
+        port
+    }
+
+    pub fn settings(&self) -> MidiOutputSettings {
+        self.settings
+    }
+
+    pub fn connection_mut(&mut self) -> &mut S {
+        &mut self.connection
+    }
+
+    /// Configure the MPE zone and the bend range of the receiving synthesizer, stopping every voice first
+    pub fn configure(&mut self, settings: MidiOutputSettings) {
+        self.all_notes_off();
+        self.settings = settings;
+        // the MPE configuration message sets the number of member channels of the zone
+        self.send_rpn(settings.manager_channel(), 6, settings.member_channels);
+        for channel in settings.member_channels() {
+            // pitch bend sensitivity
+            self.send_rpn(channel, 0, settings.bend_range);
+        }
+    }
+
+    /// Start sending a voice at a frequency on a free member channel. The voice isn't sent if every member
+    /// channel is taken or the frequency is outside the MIDI notes
+    pub fn note_on(&mut self, id: usize, frequency: f32, velocity: u8) {
+        if self.voices.contains_key(&id) {
+            self.retune(id, frequency);
+            return;
+        }
+        let Some(cents) = midi_cents(frequency) else {
+            return;
+        };
+        let Some(channel) = self
+            .settings
+            .member_channels()
+            .find(|channel| self.voices.values().all(|voice| voice.channel != *channel))
+        else {
+            return;
+        };
+        let voice = SentVoice {
+            channel,
+            note: (cents / 100.0).round() as u8,
+            velocity: velocity.clamp(1, 127),
+        };
+        self.play(voice, cents);
+        self.voices.insert(id, voice);
+    }
+
+    /// Move a voice being sent to a new frequency, bending its note if the bend range reaches it
+    /// and playing the nearest note otherwise
+    pub fn retune(&mut self, id: usize, frequency: f32) {
+        let Some(&voice) = self.voices.get(&id) else {
+            return;
+        };
+        let Some(cents) = midi_cents(frequency) else {
+            self.note_off(id);
+            return;
+        };
+        let offset = cents - voice.note as f32 * 100.0;
+        if offset.abs() <= self.settings.bend_range as f32 * 100.0 {
+            self.send_pitch_bend(voice.channel, offset);
+            return;
+        }
+        self.send(&[0x80 | voice.channel, voice.note, 0]);
+        let voice = SentVoice {
+            note: (cents / 100.0).round() as u8,
+            ..voice
+        };
+        self.play(voice, cents);
+        self.voices.insert(id, voice);
+    }
+
+    /// Stop sending a voice, freeing its member channel
+    pub fn note_off(&mut self, id: usize) {
+        if let Some(voice) = self.voices.remove(&id) {
+            self.send(&[0x80 | voice.channel, voice.note, 0]);
+        }
+    }
+
+    /// Stop sending every voice
+    pub fn all_notes_off(&mut self) {
+        for id in self.voices.keys().copied().collect::<Vec<_>>() {
+            self.note_off(id);
+        }
+    }
+
+    /// Bend the channel of a voice to the cents above MIDI note 0 before starting its note,
+    /// so the note starts at the right frequency
+    fn play(&mut self, voice: SentVoice, cents: f32) {
+        self.send_pitch_bend(voice.channel, cents - voice.note as f32 * 100.0);
+        self.send(&[0x90 | voice.channel, voice.note, voice.velocity]);
+    }
+
+    fn send_pitch_bend(&mut self, channel: u8, cents: f32) {
+        let bend = self.settings.pitch_bend(cents);
+        self.send(&[0xE0 | channel, (bend & 0x7F) as u8, (bend >> 7) as u8]);
+    }
+
+    /// Set a registered parameter of a channel, deselecting it afterwards so later data entry doesn't change it
+    fn send_rpn(&mut self, channel: u8, parameter: u8, value: u8) {
+        let status = 0xB0 | channel;
+        self.send(&[status, 101, 0]);
+        self.send(&[status, 100, parameter]);
+        self.send(&[status, 6, value]);
+        self.send(&[status, 38, 0]);
+        self.send(&[status, 101, 127]);
+        self.send(&[status, 100, 127]);
+    }
+
+    fn send(&mut self, message: &[u8]) {
+        self.connection.send(message);
+    }
+}
+
+impl<S: MidiSender> Drop for MidiOutputPort<S> {
+    fn drop(&mut self) {
+        self.all_notes_off();
+    }
+}
+
+/// An error which occurred while opening a MIDI port
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MidiError {
+    /// The MIDI system of the platform couldn't be used
+    Init(InitError),
+    Connect(ConnectErrorKind),
+    /// There is no port to connect to
+    NoPort,
+}
+
+impl Display for MidiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MidiError::Init(error) => write!(f, "{error}"),
+            MidiError::Connect(error) => write!(f, "{error}"),
+            MidiError::NoPort => write!(f, "no MIDI port found"),
+        }
+    }
+}
+
+impl std::error::Error for MidiError {}
+
+impl From<InitError> for MidiError {
+    fn from(error: InitError) -> Self {
+        Self::Init(error)
+    }
+}
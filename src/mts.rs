@@ -0,0 +1,173 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use crate::midi::MAX_NOTE;
+
+/// The frequency of A4, which frequencies of the MIDI Tuning Standard are relative to
+const A4_FREQUENCY: f64 = 440.0;
+
+const A4_NOTE: f64 = 69.0;
+
+/// The number of steps a semitone is divided into
+const FRACTION_STEPS: f64 = 16384.0;
+
+/// The device id which every device responds to
+const ALL_DEVICES: u8 = 0x7F;
+
+/// The number of characters in the name of a bulk tuning dump
+const NAME_LENGTH: usize = 16;
+
+/// Which MIDI Tuning Standard message a tuning is exported as
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MtsMessage {
+    /// Tunes every note of a tuning program, which is stored by the synthesizer
+    #[default]
+    BulkDump,
+    /// Tunes only the notes with a voice, in real time
+    SingleNote,
+}
+
+impl MtsMessage {
+    pub const ALL: [MtsMessage; 2] = [Self::BulkDump, Self::SingleNote];
+}
+
+impl Display for MtsMessage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                MtsMessage::BulkDump => "Bulk tuning dump",
+                MtsMessage::SingleNote => "Single note tuning change",
+            }
+        )
+    }
+}
+
+/// A frequency in the format of the MIDI Tuning Standard, as the 12-TET note at or below it
+/// and the 14 bit fraction of a semitone it is above that note
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MtsFrequency {
+    pub note: u8,
+    pub fraction: u16,
+}
+
+impl MtsFrequency {
+    /// Convert a frequency, or None if it is outside the range MIDI notes can be tuned to
+    pub fn from_frequency(frequency: f32) -> Option<Self> {
+        let semitones = A4_NOTE + 12.0 * (frequency as f64 / A4_FREQUENCY).log2();
+        let mut note = semitones.floor();
+        let mut fraction = ((semitones - note) * FRACTION_STEPS).round();
+        if fraction >= FRACTION_STEPS {
+            note += 1.0;
+            fraction = 0.0;
+        }
+        // the largest fraction of the highest note is reserved for leaving a note unchanged
+        let is_reserved = note == MAX_NOTE as f64 && fraction == FRACTION_STEPS - 1.0;
+        ((0.0..=MAX_NOTE as f64).contains(&note) && !is_reserved).then_some(Self {
+            note: note as u8,
+            fraction: fraction as u16,
+        })
+    }
+
+    /// The frequency of a 12-TET note
+    pub fn equal_tempered(note: u8) -> Self {
+        Self { note, fraction: 0 }
+    }
+
+    /// Get the 12-TET note closest to the frequency
+    pub fn nearest_note(&self) -> u8 {
+        if self.fraction as f64 >= FRACTION_STEPS / 2.0 {
+            (self.note + 1).min(MAX_NOTE)
+        } else {
+            self.note
+        }
+    }
+
+    fn bytes(&self) -> [u8; 3] {
+        [
+            self.note,
+            (self.fraction >> 7) as u8,
+            (self.fraction & 0x7F) as u8,
+        ]
+    }
+}
+
+/// The frequencies MIDI notes are tuned to
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Tuning {
+    pub name: String,
+    /// The frequency of every tuned note, by its note number
+    pub notes: BTreeMap<u8, MtsFrequency>,
+}
+
+impl Tuning {
+    /// Tune a note for each voice, given as its frequency and the note it is mapped to, if any.
+    /// Voices without a note are tuned on the note closest to their frequency, and voices on a note
+    /// which is already tuned or with a frequency MIDI can't reach are left out
+    pub fn from_voices(name: String, voices: impl IntoIterator<Item = (Option<u8>, f32)>) -> Self {
+        let mut notes = BTreeMap::new();
+        for (note, frequency) in voices {
+            let Some(frequency) = MtsFrequency::from_frequency(frequency) else {
+                continue;
+            };
+            let note = note.unwrap_or(frequency.nearest_note());
+            notes.entry(note).or_insert(frequency);
+        }
+        Self { name, notes }
+    }
+
+    /// Build the SysEx message of the tuning for a tuning program
+    pub fn to_sysex(&self, message: MtsMessage, program: u8) -> Vec<u8> {
+        match message {
+            MtsMessage::BulkDump => self.bulk_dump(program),
+            MtsMessage::SingleNote => self.single_note_changes(program),
+        }
+    }
+
+    /// Build a bulk tuning dump, which keeps the notes without a voice in 12-TET
+    fn bulk_dump(&self, program: u8) -> Vec<u8> {
+        let mut message = vec![0xF0, 0x7E, ALL_DEVICES, 0x08, 0x01, program & 0x7F];
+        message.extend(
+            self.name
+                .chars()
+                .map(|character| match character {
+                    ' '..='~' => character as u8,
+                    _ => b'?',
+                })
+                .chain(std::iter::repeat(b' '))
+                .take(NAME_LENGTH),
+        );
+        for note in 0..=MAX_NOTE {
+            let frequency = self
+                .notes
+                .get(&note)
+                .copied()
+                .unwrap_or(MtsFrequency::equal_tempered(note));
+            message.extend(frequency.bytes());
+        }
+        // the checksum covers everything after the start of the message
+        let checksum = message[1..]
+            .iter()
+            .fold(0, |checksum, byte| checksum ^ byte)
+            & 0x7F;
+        message.extend([checksum, 0xF7]);
+        message
+    }
+
+    /// Build single note tuning changes for the notes with a voice, split into several messages
+    /// since a message can tune at most 127 notes
+    fn single_note_changes(&self, program: u8) -> Vec<u8> {
+        let notes: Vec<(&u8, &MtsFrequency)> = self.notes.iter().collect();
+        let mut messages = Vec::new();
+        for chunk in notes.chunks(MAX_NOTE as usize) {
+            messages.extend([0xF0, 0x7F, ALL_DEVICES, 0x08, 0x02, program & 0x7F]);
+            messages.push(chunk.len() as u8);
+            for (note, frequency) in chunk {
+                messages.push(**note);
+                messages.extend(frequency.bytes());
+            }
+            messages.push(0xF7);
+        }
+        messages
+    }
+}
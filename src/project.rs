@@ -0,0 +1,1066 @@
+use std::{collections::BTreeMap, fmt::Display, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    audio::{
+        engine::{EngineControl, SharedFrequency, SharedVolumeMultiplier, Volume},
+        envelope::Envelope,
+        graph::{Graph, GraphError},
+        smoothing::Smoothing,
+        synthesizer::{Harmonic, WaveForm},
+    },
+    expression::{Expression, Number, Rational},
+    gui::{
+        global_frequency::GlobalFrequency,
+        harmonics,
+        relative_frequency::{Ratio, RelativeFrequency},
+    },
+    midi::MidiMapping,
+    timeline::{Step, Timeline},
+    variables::{self, Definition, VariableError},
+};
+
+/// The bytes every versioned .harm file starts with. Files without them are from before the format was versioned
+pub const MAGIC: [u8; 4] = *b"HARM";
+
+/// The version of the format files are saved in. Bump it whenever the layout of [StateSave] changes,
+/// keeping the old layout in its own module with a migration to the next version
+pub const FORMAT_VERSION: u32 = 6;
+
+/// The extension of project files in the text format, which can be diffed and edited by hand
+pub const TEXT_EXTENSION: &str = "ron";
+
+/// The format a project file is stored in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileFormat {
+    /// The compact postcard binary format of .harm files
+    Binary,
+    /// The RON text format of .ron files
+    Text,
+}
+
+impl FileFormat {
+    /// Get the format of a file from its extension, which is binary for anything but the text extension
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension() {
+            Some(extension) if extension.eq_ignore_ascii_case(TEXT_EXTENSION) => FileFormat::Text,
+            _ => FileFormat::Binary,
+        }
+    }
+}
+
+/// The header of the text format, read before the project so newer versions are rejected with a clear error
+#[derive(Deserialize)]
+struct TextHeader {
+    format_version: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TextSave<S> {
+    format_version: u32,
+    project: S,
+}
+
+/// The contents of a .harm project file
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct StateSave {
+    pub volume: Volume,
+    pub waveform: WaveForm,
+    /// The harmonics of the custom waveform, kept even when no voice plays it
+    pub custom_harmonics: Vec<Harmonic>,
+    /// The envelope of every voice which doesn't override it
+    pub envelope: Envelope,
+    /// How oscillators move to new frequencies and volumes
+    pub smoothing: Smoothing,
+    /// Whether every voice restarts at phase zero when playback starts
+    pub phase_reset: bool,
+    /// The global variables, where the frequencies are those relative frequencies are played relative to
+    pub global_frequencies: BTreeMap<usize, GlobalFrequency>,
+    pub relative_frequencies: Vec<RelativeFrequency>,
+    /// How incoming MIDI notes play the project
+    #[serde(default)]
+    pub midi: MidiMapping,
+    /// The harmonic progression of the project, where the relative frequencies of the steps are indices
+    /// into [StateSave::relative_frequencies]
+    #[serde(default)]
+    pub timeline: Timeline,
+    /// The graph the voices are processed by
+    #[serde(default)]
+    pub graph: Graph,
+}
+
+impl StateSave {
+    /// Deserialize a save of any format version, migrating older versions to the current one
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, LoadError> {
+        let (version, body) = match bytes.strip_prefix(&MAGIC) {
+            Some(rest) => {
+                let (version, body) = rest.split_first_chunk::<4>().ok_or(LoadError::Postcard(
+                    postcard::Error::DeserializeUnexpectedEnd,
+                ))?;
+                (u32::from_le_bytes(*version), body)
+            }
+            None => (0, bytes),
+        };
+
+        let save = match version {
+            0 => VersionedSave::V0(postcard::from_bytes(body)?),
+            1 => VersionedSave::V1(postcard::from_bytes(body)?),
+            2 => VersionedSave::V2(postcard::from_bytes(body)?),
+            3 => VersionedSave::V3(postcard::from_bytes(body)?),
+            4 => VersionedSave::V4(postcard::from_bytes(body)?),
+            5 => VersionedSave::V5(postcard::from_bytes(body)?),
+            6 => VersionedSave::V6(postcard::from_bytes(body)?),
+            version => return Err(LoadError::UnsupportedVersion(version)),
+        };
+        let save = save.migrate_to_current();
+        save.graph.validate()?;
+        Ok(save)
+    }
+
+    /// Serialize the save to the postcard binary format, behind the magic header and the current format version
+    pub fn to_bytes(&self) -> Result<Vec<u8>, postcard::Error> {
+        let mut bytes = Vec::from(MAGIC);
+        bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        postcard::to_extend(self, bytes)
+    }
+
+    /// Deserialize a save from the RON text format. Since the file may be edited by hand, its variables
+    /// are evaluated again and its graph is validated
+    pub fn from_text(text: &str) -> Result<Self, LoadError> {
+        let TextHeader { format_version } = ron::from_str(text)?;
+        let mut save = match format_version {
+            // fields added before global variables have defaults, so those versions load as the last of them
+            0..=3 => {
+                let TextSave { project, .. } = ron::from_str(text)?;
+                VersionedSave::V3(project).migrate_to_current()
+            }
+            // the expressions of relative frequencies and the graph have defaults, so versions 4 and 5 load as the current one
+            4..=FORMAT_VERSION => {
+                let TextSave { project, .. } = ron::from_str(text)?;
+                project
+            }
+            version => return Err(LoadError::UnsupportedVersion(version)),
+        };
+        save.evaluate_variables()?;
+        save.graph.validate()?;
+        Ok(save)
+    }
+
+    /// Serialize the save to the RON text format, with the current format version
+    pub fn to_text(&self) -> Result<String, ron::Error> {
+        ron::ser::to_string_pretty(
+            &TextSave {
+                format_version: FORMAT_VERSION,
+                project: self,
+            },
+            ron::ser::PrettyConfig::default(),
+        )
+    }
+
+    /// Deserialize the contents of a project file in the format of its extension
+    pub fn from_file(path: &Path, contents: &[u8]) -> Result<Self, LoadError> {
+        match FileFormat::from_path(path) {
+            FileFormat::Binary => Self::from_bytes(contents),
+            FileFormat::Text => {
+                Self::from_text(std::str::from_utf8(contents).map_err(|_| LoadError::InvalidText)?)
+            }
+        }
+    }
+
+    /// Evaluate the global variables and the ratios of the relative frequencies which reference them
+    pub fn evaluate_variables(&mut self) -> Result<(), VariableError> {
+        variables::evaluate(&mut self.global_frequencies)?;
+        for relative_frequency in &mut self.relative_frequencies {
+            relative_frequency.evaluate_ratio(&self.global_frequencies);
+        }
+        Ok(())
+    }
+
+    /// Get the frequency a relative frequency plays at, if it references an existing global frequency
+    pub fn played_frequency(&self, relative_frequency: &RelativeFrequency) -> Option<f32> {
+        self.global_frequencies
+            .get(&relative_frequency.absolute_frequency_id())
+            .and_then(GlobalFrequency::frequency)
+            .map(|frequency| frequency * relative_frequency.multiplicand())
+    }
+
+    /// Set up the engine to play this save. Returns the oscillator id, shared frequency and shared volume
+    /// multiplier of every relative frequency in the save, in order, or the error of compiling its graph,
+    /// in which case the engine is left as it was
+    pub fn initialize_engine(
+        &self,
+        engine: &mut impl EngineControl,
+    ) -> Result<Vec<(Option<usize>, SharedFrequency, SharedVolumeMultiplier)>, GraphError> {
+        engine.set_graph(&self.graph)?;
+        engine.clear_oscillators();
+        engine.set_volume(self.volume);
+        engine.set_waveform(self.waveform.clone());
+        engine.set_smoothing(self.smoothing);
+        engine.set_phase_reset(self.phase_reset);
+        engine.stop();
+
+        Ok(self
+            .relative_frequencies
+            .iter()
+            .map(|relative_frequency| {
+                match initialize_oscillator(
+                    engine,
+                    &self.global_frequencies,
+                    self.envelope,
+                    relative_frequency,
+                ) {
+                    (Some((oscillator_id, shared_frequency)), shared_volume_multiplier) => (
+                        Some(oscillator_id),
+                        shared_frequency,
+                        shared_volume_multiplier,
+                    ),
+                    (None, shared_volume_multiplier) => {
+                        println!("failed to initialize oscillator");
+                        (None, SharedFrequency::new(220.0), shared_volume_multiplier)
+                    }
+                }
+            })
+            .collect())
+    }
+}
+
+/// A save in the layout of one of the format versions
+enum VersionedSave {
+    V0(v0::StateSave),
+    V1(v1::StateSave),
+    V2(v2::StateSave),
+    V3(v3::StateSave),
+    V4(v4::StateSave),
+    V5(v5::StateSave),
+    V6(StateSave),
+}
+
+impl VersionedSave {
+    /// Migrate the save to the next format version
+    fn migrate(self) -> Self {
+        match self {
+            VersionedSave::V0(save) => VersionedSave::V1(save.into()),
+            VersionedSave::V1(save) => VersionedSave::V2(save.into()),
+            VersionedSave::V2(save) => VersionedSave::V3(save.into()),
+            VersionedSave::V3(save) => VersionedSave::V4(save.into()),
+            VersionedSave::V4(save) => VersionedSave::V5(save.into()),
+            VersionedSave::V5(save) => VersionedSave::V6(save.into()),
+            VersionedSave::V6(save) => VersionedSave::V6(save),
+        }
+    }
+
+    /// Migrate the save through every newer format version
+    fn migrate_to_current(mut self) -> StateSave {
+        loop {
+            match self {
+                VersionedSave::V6(save) => return save,
+                older => self = older.migrate(),
+            }
+        }
+    }
+}
+
+/// The layout of saves before the format was versioned, which only had frequencies, a volume and a waveform
+mod v0 {
+    use std::collections::BTreeMap;
+
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct StateSave {
+        pub volume: f32,
+        pub waveform: WaveForm,
+        pub global_frequencies: BTreeMap<usize, GlobalFrequency>,
+        pub relative_frequencies: Vec<RelativeFrequency>,
+    }
+
+    #[derive(Deserialize)]
+    pub enum WaveForm {
+        Sine,
+        Triangle,
+        Square,
+        Saw,
+    }
+
+    #[derive(Deserialize)]
+    pub struct GlobalFrequency {
+        pub id: usize,
+        pub frequency: f32,
+    }
+
+    #[derive(Deserialize)]
+    pub struct RelativeFrequency {
+        pub absolute_frequency_id: usize,
+        pub ratio: Ratio,
+        pub volume: f32,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Ratio {
+        pub numerator: u32,
+        pub denominator: u32,
+    }
+}
+
+/// The layout of saves before MIDI input, where voices had no MIDI note and there was no MIDI mapping.
+/// The types every later layout kept as they were are frozen here
+mod v1 {
+    use std::collections::BTreeMap;
+
+    use serde::Deserialize;
+
+    use super::v3::{GlobalFrequency, Ratio};
+
+    #[derive(Deserialize)]
+    pub struct StateSave {
+        pub volume: Volume,
+        pub waveform: WaveForm,
+        pub custom_harmonics: Vec<Harmonic>,
+        pub envelope: Envelope,
+        pub smoothing: Smoothing,
+        pub phase_reset: bool,
+        pub global_frequencies: BTreeMap<usize, GlobalFrequency>,
+        pub relative_frequencies: Vec<RelativeFrequency>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Volume(pub f32);
+
+    #[derive(Deserialize)]
+    pub enum WaveForm {
+        Sine,
+        Triangle,
+        Square,
+        Saw,
+        Custom(Vec<Harmonic>),
+    }
+
+    #[derive(Deserialize)]
+    pub struct Harmonic {
+        pub amplitude: f32,
+        pub phase: f32,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Envelope {
+        pub attack: f32,
+        pub decay: f32,
+        pub sustain: f32,
+        pub release: f32,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Smoothing {
+        pub time: f32,
+        pub portamento: bool,
+        pub portamento_time: f32,
+    }
+
+    #[derive(Deserialize)]
+    pub struct RelativeFrequency {
+        pub absolute_frequency_id: usize,
+        pub ratio: Ratio,
+        pub volume: f32,
+        pub envelope: Option<Envelope>,
+        pub waveform: Option<WaveForm>,
+        pub hard_sync: bool,
+        pub pan: f32,
+    }
+}
+
+/// The layout of saves before the timeline
+mod v2 {
+    use std::collections::BTreeMap;
+
+    use serde::Deserialize;
+
+    use super::{
+        v1::{Envelope, Harmonic, Smoothing, Volume, WaveForm},
+        v3::{GlobalFrequency, RelativeFrequency},
+    };
+
+    #[derive(Deserialize)]
+    pub struct StateSave {
+        pub volume: Volume,
+        pub waveform: WaveForm,
+        pub custom_harmonics: Vec<Harmonic>,
+        pub envelope: Envelope,
+        pub smoothing: Smoothing,
+        pub phase_reset: bool,
+        pub global_frequencies: BTreeMap<usize, GlobalFrequency>,
+        pub relative_frequencies: Vec<RelativeFrequency>,
+        pub midi: MidiMapping,
+    }
+
+    #[derive(Deserialize)]
+    pub struct MidiMapping {
+        pub transposed_frequency: Option<usize>,
+        pub reference_note: u8,
+    }
+
+    impl Default for MidiMapping {
+        fn default() -> Self {
+            Self {
+                transposed_frequency: None,
+                // middle C
+                reference_note: 60,
+            }
+        }
+    }
+}
+
+/// The layout of saves before global variables, where every global frequency was a frequency
+/// and ratios couldn't reference integer variables. The fields added since version 1 have defaults,
+/// so text files of those versions load in this layout
+mod v3 {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use serde::Deserialize;
+
+    use super::{
+        v1::{Envelope, Harmonic, Smoothing, Volume, WaveForm},
+        v2::MidiMapping,
+    };
+
+    #[derive(Deserialize)]
+    pub struct StateSave {
+        pub volume: Volume,
+        pub waveform: WaveForm,
+        pub custom_harmonics: Vec<Harmonic>,
+        pub envelope: Envelope,
+        pub smoothing: Smoothing,
+        pub phase_reset: bool,
+        pub global_frequencies: BTreeMap<usize, GlobalFrequency>,
+        pub relative_frequencies: Vec<RelativeFrequency>,
+        #[serde(default)]
+        pub midi: MidiMapping,
+        #[serde(default)]
+        pub timeline: Timeline,
+    }
+
+    #[derive(Deserialize)]
+    pub struct GlobalFrequency {
+        pub id: usize,
+        pub frequency: f32,
+    }
+
+    #[derive(Deserialize)]
+    pub struct RelativeFrequency {
+        pub absolute_frequency_id: usize,
+        pub ratio: Ratio,
+        pub volume: f32,
+        pub envelope: Option<Envelope>,
+        pub waveform: Option<WaveForm>,
+        pub hard_sync: bool,
+        pub pan: f32,
+        #[serde(default)]
+        pub midi_note: Option<u8>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Ratio {
+        pub numerator: u32,
+        pub denominator: u32,
+    }
+
+    #[derive(Default, Deserialize)]
+    pub struct Timeline {
+        pub steps: Vec<Step>,
+        pub looping: bool,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Step {
+        pub duration: f32,
+        pub global_frequencies: BTreeMap<usize, f32>,
+        pub active_voices: BTreeSet<usize>,
+    }
+}
+
+/// The layout of saves before expressions, where relative frequencies had no expression
+mod v4 {
+    use std::collections::BTreeMap;
+
+    use serde::Deserialize;
+
+    use super::{
+        v1::{Envelope, Harmonic, Smoothing, Volume, WaveForm},
+        v2::MidiMapping,
+        v3::Timeline,
+    };
+
+    #[derive(Deserialize)]
+    pub struct StateSave {
+        pub volume: Volume,
+        pub waveform: WaveForm,
+        pub custom_harmonics: Vec<Harmonic>,
+        pub envelope: Envelope,
+        pub smoothing: Smoothing,
+        pub phase_reset: bool,
+        pub global_frequencies: BTreeMap<usize, GlobalFrequency>,
+        pub relative_frequencies: Vec<RelativeFrequency>,
+        pub midi: MidiMapping,
+        pub timeline: Timeline,
+    }
+
+    #[derive(Deserialize)]
+    pub struct GlobalFrequency {
+        pub id: usize,
+        pub name: String,
+        pub definition: Definition,
+    }
+
+    #[derive(Deserialize)]
+    pub enum Definition {
+        Frequency(f32),
+        Interval {
+            base: usize,
+            ratio: usize,
+            frequency: f32,
+        },
+        Integer(u32),
+        Ratio(Ratio),
+    }
+
+    #[derive(Deserialize)]
+    pub struct RelativeFrequency {
+        pub absolute_frequency_id: usize,
+        pub ratio: Ratio,
+        pub volume: f32,
+        pub envelope: Option<Envelope>,
+        pub waveform: Option<WaveForm>,
+        pub hard_sync: bool,
+        pub pan: f32,
+        pub midi_note: Option<u8>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Ratio {
+        pub numerator: u32,
+        pub denominator: u32,
+        pub numerator_variable: Option<usize>,
+        pub denominator_variable: Option<usize>,
+    }
+}
+
+/// The layout of saves before graphs, where the voices were played as they are
+mod v5 {
+    use std::collections::BTreeMap;
+
+    use serde::Deserialize;
+
+    use super::{
+        v1::{Envelope, Harmonic, Smoothing, Volume, WaveForm},
+        v2::MidiMapping,
+        v3::Timeline,
+        v4::Ratio,
+    };
+
+    #[derive(Deserialize)]
+    pub struct StateSave {
+        pub volume: Volume,
+        pub waveform: WaveForm,
+        pub custom_harmonics: Vec<Harmonic>,
+        pub envelope: Envelope,
+        pub smoothing: Smoothing,
+        pub phase_reset: bool,
+        pub global_frequencies: BTreeMap<usize, GlobalFrequency>,
+        pub relative_frequencies: Vec<RelativeFrequency>,
+        pub midi: MidiMapping,
+        pub timeline: Timeline,
+    }
+
+    #[derive(Deserialize)]
+    pub struct GlobalFrequency {
+        pub id: usize,
+        pub name: String,
+        pub definition: Definition,
+    }
+
+    #[derive(Deserialize)]
+    pub enum Definition {
+        Frequency(f32),
+        Interval {
+            base: usize,
+            ratio: usize,
+            frequency: f32,
+        },
+        Integer(u32),
+        Ratio(Ratio),
+        Expression(Expression),
+    }
+
+    #[derive(Deserialize)]
+    pub struct RelativeFrequency {
+        pub absolute_frequency_id: usize,
+        pub ratio: Ratio,
+        pub volume: f32,
+        pub envelope: Option<Envelope>,
+        pub waveform: Option<WaveForm>,
+        pub hard_sync: bool,
+        pub pan: f32,
+        pub midi_note: Option<u8>,
+        pub expression: Option<Expression>,
+    }
+
+    #[derive(Deserialize)]
+    pub struct Expression {
+        pub source: String,
+        pub value: Option<Number>,
+    }
+
+    #[derive(Deserialize)]
+    pub enum Number {
+        Rational { numerator: i64, denominator: i64 },
+        Real(f64),
+    }
+}
+
+impl From<v5::StateSave> for StateSave {
+    fn from(save: v5::StateSave) -> Self {
+        Self {
+            volume: Volume::new(save.volume.0),
+            waveform: save.waveform.into(),
+            custom_harmonics: save
+                .custom_harmonics
+                .into_iter()
+                .map(Harmonic::from)
+                .collect(),
+            envelope: save.envelope.into(),
+            smoothing: Smoothing {
+                time: save.smoothing.time,
+                portamento: save.smoothing.portamento,
+                portamento_time: save.smoothing.portamento_time,
+            },
+            phase_reset: save.phase_reset,
+            global_frequencies: save
+                .global_frequencies
+                .into_iter()
+                .map(|(id, old)| {
+                    let mut global_frequency =
+                        GlobalFrequency::new(old.id, 0.0).with_name(old.name);
+                    global_frequency.set_definition(match old.definition {
+                        v5::Definition::Frequency(frequency) => Definition::Frequency(frequency),
+                        v5::Definition::Interval {
+                            base,
+                            ratio,
+                            frequency,
+                        } => Definition::Interval {
+                            base,
+                            ratio,
+                            frequency,
+                        },
+                        v5::Definition::Integer(integer) => Definition::Integer(integer),
+                        v5::Definition::Ratio(ratio) => Definition::Ratio(ratio.into()),
+                        v5::Definition::Expression(expression) => {
+                            Definition::Expression(expression.into())
+                        }
+                    });
+                    (id, global_frequency)
+                })
+                .collect(),
+            relative_frequencies: save
+                .relative_frequencies
+                .into_iter()
+                .map(|old| {
+                    RelativeFrequency::new(old.absolute_frequency_id, old.ratio.into(), old.volume)
+                        .with_envelope(old.envelope.map(Envelope::from))
+                        .with_waveform(old.waveform.map(WaveForm::from))
+                        .with_hard_sync(old.hard_sync)
+                        .with_pan(old.pan)
+                        .with_midi_note(old.midi_note)
+                        .with_expression(old.expression.map(Expression::from))
+                })
+                .collect(),
+            midi: MidiMapping {
+                transposed_frequency: save.midi.transposed_frequency,
+                reference_note: save.midi.reference_note,
+            },
+            timeline: Timeline {
+                steps: save
+                    .timeline
+                    .steps
+                    .into_iter()
+                    .map(|step| Step {
+                        duration: step.duration,
+                        global_frequencies: step.global_frequencies,
+                        active_voices: step.active_voices,
+                    })
+                    .collect(),
+                looping: save.timeline.looping,
+            },
+            graph: Graph::default(),
+        }
+    }
+}
+
+impl From<v1::WaveForm> for WaveForm {
+    fn from(waveform: v1::WaveForm) -> Self {
+        match waveform {
+            v1::WaveForm::Sine => WaveForm::Sine,
+            v1::WaveForm::Triangle => WaveForm::Triangle,
+            v1::WaveForm::Square => WaveForm::Square,
+            v1::WaveForm::Saw => WaveForm::Saw,
+            v1::WaveForm::Custom(harmonics) => {
+                WaveForm::Custom(harmonics.into_iter().map(Harmonic::from).collect())
+            }
+        }
+    }
+}
+
+impl From<v1::Harmonic> for Harmonic {
+    fn from(harmonic: v1::Harmonic) -> Self {
+        Harmonic::new(harmonic.amplitude, harmonic.phase)
+    }
+}
+
+impl From<v1::Envelope> for Envelope {
+    fn from(envelope: v1::Envelope) -> Self {
+        Envelope {
+            attack: envelope.attack,
+            decay: envelope.decay,
+            sustain: envelope.sustain,
+            release: envelope.release,
+        }
+    }
+}
+
+impl From<v4::Ratio> for Ratio {
+    fn from(ratio: v4::Ratio) -> Self {
+        Ratio {
+            numerator: ratio.numerator,
+            denominator: ratio.denominator,
+            numerator_variable: ratio.numerator_variable,
+            denominator_variable: ratio.denominator_variable,
+        }
+    }
+}
+
+impl From<v5::Expression> for Expression {
+    fn from(expression: v5::Expression) -> Self {
+        Expression::new(expression.source).with_value(expression.value.and_then(
+            |value| match value {
+                // a fraction which isn't in lowest terms is reduced, and one with a zero denominator is dropped
+                v5::Number::Rational {
+                    numerator,
+                    denominator,
+                } => Rational::new(numerator, denominator).map(Number::Rational),
+                v5::Number::Real(real) => Some(Number::Real(real)),
+            },
+        ))
+    }
+}
+
+impl From<v4::StateSave> for v5::StateSave {
+    fn from(save: v4::StateSave) -> Self {
+        Self {
+            volume: save.volume,
+            waveform: save.waveform,
+            custom_harmonics: save.custom_harmonics,
+            envelope: save.envelope,
+            smoothing: save.smoothing,
+            phase_reset: save.phase_reset,
+            global_frequencies: save
+                .global_frequencies
+                .into_iter()
+                .map(|(id, old)| {
+                    (
+                        id,
+                        v5::GlobalFrequency {
+                            id: old.id,
+                            name: old.name,
+                            definition: match old.definition {
+                                v4::Definition::Frequency(frequency) => {
+                                    v5::Definition::Frequency(frequency)
+                                }
+                                v4::Definition::Interval {
+                                    base,
+                                    ratio,
+                                    frequency,
+                                } => v5::Definition::Interval {
+                                    base,
+                                    ratio,
+                                    frequency,
+                                },
+                                v4::Definition::Integer(integer) => {
+                                    v5::Definition::Integer(integer)
+                                }
+                                v4::Definition::Ratio(ratio) => v5::Definition::Ratio(ratio),
+                            },
+                        },
+                    )
+                })
+                .collect(),
+            relative_frequencies: save
+                .relative_frequencies
+                .into_iter()
+                .map(|old| v5::RelativeFrequency {
+                    absolute_frequency_id: old.absolute_frequency_id,
+                    ratio: old.ratio,
+                    volume: old.volume,
+                    envelope: old.envelope,
+                    waveform: old.waveform,
+                    hard_sync: old.hard_sync,
+                    pan: old.pan,
+                    midi_note: old.midi_note,
+                    expression: None,
+                })
+                .collect(),
+            midi: save.midi,
+            timeline: save.timeline,
+        }
+    }
+}
+
+impl From<v3::StateSave> for v4::StateSave {
+    fn from(save: v3::StateSave) -> Self {
+        let ratio = |ratio: v3::Ratio| v4::Ratio {
+            numerator: ratio.numerator,
+            denominator: ratio.denominator,
+            numerator_variable: None,
+            denominator_variable: None,
+        };
+        Self {
+            volume: save.volume,
+            waveform: save.waveform,
+            custom_harmonics: save.custom_harmonics,
+            envelope: save.envelope,
+            smoothing: save.smoothing,
+            phase_reset: save.phase_reset,
+            global_frequencies: save
+                .global_frequencies
+                .into_iter()
+                .map(|(id, global_frequency)| {
+                    (
+                        id,
+                        v4::GlobalFrequency {
+                            id: global_frequency.id,
+                            name: String::new(),
+                            definition: v4::Definition::Frequency(global_frequency.frequency),
+                        },
+                    )
+                })
+                .collect(),
+            relative_frequencies: save
+                .relative_frequencies
+                .into_iter()
+                .map(|old| v4::RelativeFrequency {
+                    absolute_frequency_id: old.absolute_frequency_id,
+                    ratio: ratio(old.ratio),
+                    volume: old.volume,
+                    envelope: old.envelope,
+                    waveform: old.waveform,
+                    hard_sync: old.hard_sync,
+                    pan: old.pan,
+                    midi_note: old.midi_note,
+                })
+                .collect(),
+            midi: save.midi,
+            timeline: save.timeline,
+        }
+    }
+}
+
+impl From<v2::StateSave> for v3::StateSave {
+    fn from(save: v2::StateSave) -> Self {
+        Self {
+            volume: save.volume,
+            waveform: save.waveform,
+            custom_harmonics: save.custom_harmonics,
+            envelope: save.envelope,
+            smoothing: save.smoothing,
+            phase_reset: save.phase_reset,
+            global_frequencies: save.global_frequencies,
+            relative_frequencies: save.relative_frequencies,
+            midi: save.midi,
+            timeline: v3::Timeline::default(),
+        }
+    }
+}
+
+impl From<v1::StateSave> for v2::StateSave {
+    fn from(save: v1::StateSave) -> Self {
+        Self {
+            volume: save.volume,
+            waveform: save.waveform,
+            custom_harmonics: save.custom_harmonics,
+            envelope: save.envelope,
+            smoothing: save.smoothing,
+            phase_reset: save.phase_reset,
+            global_frequencies: save.global_frequencies,
+            relative_frequencies: save
+                .relative_frequencies
+                .into_iter()
+                .map(|old| v3::RelativeFrequency {
+                    absolute_frequency_id: old.absolute_frequency_id,
+                    ratio: old.ratio,
+                    volume: old.volume,
+                    envelope: old.envelope,
+                    waveform: old.waveform,
+                    hard_sync: old.hard_sync,
+                    pan: old.pan,
+                    midi_note: None,
+                })
+                .collect(),
+            midi: v2::MidiMapping::default(),
+        }
+    }
+}
+
+impl From<v0::StateSave> for v1::StateSave {
+    fn from(save: v0::StateSave) -> Self {
+        let envelope = Envelope::default();
+        let smoothing = Smoothing::default();
+        Self {
+            volume: v1::Volume(save.volume),
+            waveform: match save.waveform {
+                v0::WaveForm::Sine => v1::WaveForm::Sine,
+                v0::WaveForm::Triangle => v1::WaveForm::Triangle,
+                v0::WaveForm::Square => v1::WaveForm::Square,
+                v0::WaveForm::Saw => v1::WaveForm::Saw,
+            },
+            // the settings added in version 1 start out as the defaults of the current version
+            custom_harmonics: harmonics::default_harmonics()
+                .into_iter()
+                .map(|harmonic| v1::Harmonic {
+                    amplitude: harmonic.amplitude,
+                    phase: harmonic.phase,
+                })
+                .collect(),
+            envelope: v1::Envelope {
+                attack: envelope.attack,
+                decay: envelope.decay,
+                sustain: envelope.sustain,
+                release: envelope.release,
+            },
+            smoothing: v1::Smoothing {
+                time: smoothing.time,
+                portamento: smoothing.portamento,
+                portamento_time: smoothing.portamento_time,
+            },
+            phase_reset: false,
+            global_frequencies: save
+                .global_frequencies
+                .into_iter()
+                .map(|(id, global_frequency)| {
+                    (
+                        id,
+                        v3::GlobalFrequency {
+                            id: global_frequency.id,
+                            frequency: global_frequency.frequency,
+                        },
+                    )
+                })
+                .collect(),
+            relative_frequencies: save
+                .relative_frequencies
+                .into_iter()
+                .map(|relative_frequency| v1::RelativeFrequency {
+                    absolute_frequency_id: relative_frequency.absolute_frequency_id,
+                    ratio: v3::Ratio {
+                        numerator: relative_frequency.ratio.numerator,
+                        denominator: relative_frequency.ratio.denominator,
+                    },
+                    volume: relative_frequency.volume,
+                    envelope: None,
+                    waveform: None,
+                    hard_sync: false,
+                    pan: 0.0,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// An error which occurred while loading a save
+#[derive(Debug, Clone)]
+pub enum LoadError {
+    Postcard(postcard::Error),
+    Ron(ron::error::SpannedError),
+    /// A file of the text format isn't valid UTF-8
+    InvalidText,
+    /// The save is of a newer format version than this program knows
+    UnsupportedVersion(u32),
+    /// The global variables of a file edited by hand can't be evaluated
+    Variable(VariableError),
+    /// The graph of a file edited by hand can't be compiled
+    Graph(GraphError),
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LoadError::Postcard(error) => write!(f, "{error}"),
+            LoadError::Ron(error) => write!(f, "{error}"),
+            LoadError::InvalidText => write!(f, "the text file isn't valid UTF-8"),
+            LoadError::UnsupportedVersion(version) => write!(
+                f,
+                "the file is of format version {version}, but only versions up to {FORMAT_VERSION} are supported"
+            ),
+            LoadError::Variable(error) => write!(f, "{error}"),
+            LoadError::Graph(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl From<postcard::Error> for LoadError {
+    fn from(error: postcard::Error) -> Self {
+        Self::Postcard(error)
+    }
+}
+
+impl From<ron::error::SpannedError> for LoadError {
+    fn from(error: ron::error::SpannedError) -> Self {
+        Self::Ron(error)
+    }
+}
+
+impl From<VariableError> for LoadError {
+    fn from(error: VariableError) -> Self {
+        Self::Variable(error)
+    }
+}
+
+impl From<GraphError> for LoadError {
+    fn from(error: GraphError) -> Self {
+        Self::Graph(error)
+    }
+}
+
+/// Returns the oscillator id and shared frequency if it succeeds to initialize, else None
+pub fn initialize_oscillator(
+    engine: &mut impl EngineControl,
+    global_frequencies: &BTreeMap<usize, GlobalFrequency>,
+    project_envelope: Envelope,
+    relative_frequency: &RelativeFrequency,
+) -> (Option<(usize, SharedFrequency)>, SharedVolumeMultiplier) {
+    let shared_volume_multiplier =
+        SharedVolumeMultiplier::new(Volume::new(relative_frequency.volume()).multiple());
+    // if global frequency doesn't exist or isn't a frequency, don't create an oscillator
+    let Some(frequency) = global_frequencies
+        .get(&relative_frequency.absolute_frequency_id())
+        .and_then(GlobalFrequency::frequency)
+    else {
+        return (None, shared_volume_multiplier);
+    };
+    let shared_frequency = SharedFrequency::new(frequency * relative_frequency.multiplicand());
+    // this initializes a shared channel for updating the frequency of the oscillator remotely
+    //  so you don't have to lock the entire audio engine for that
+    let oscillator_id = engine.add_oscillator(
+        shared_frequency.clone(),
+        shared_volume_multiplier.clone(),
+        relative_frequency.envelope().unwrap_or(project_envelope),
+        relative_frequency.waveform(),
+    );
+    engine.set_oscillator_pan(&oscillator_id, relative_frequency.pan());
+    if let Some(ratio) = relative_frequency.sync_ratio() {
+        engine.set_oscillator_sync(&oscillator_id, Some(ratio));
+    }
+    (
+        Some((oscillator_id, shared_frequency)),
+        shared_volume_multiplier,
+    )
+}
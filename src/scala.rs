@@ -0,0 +1,379 @@
+use std::fmt::Display;
+
+use crate::gui::relative_frequency::Ratio;
+
+/// The frequency of degree zero when no keyboard mapping is given, which is middle C like in Scala
+pub const DEFAULT_REFERENCE_FREQUENCY: f64 = 261.625_565_3;
+
+/// The largest denominator used when approximating a pitch in cents as a ratio
+const MAX_DENOMINATOR: u32 = 10_000;
+
+/// A scale of a Scala .scl file
+#[derive(Debug, Clone, PartialEq)]
+pub struct Scale {
+    pub description: String,
+    /// The pitches of every degree but the implicit 1/1, where the last one is the period of the scale
+    pub pitches: Vec<Pitch>,
+}
+
+/// A pitch of a scale relative to 1/1
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pitch {
+    Ratio(Ratio),
+    Cents(f64),
+}
+
+impl Pitch {
+    /// Calculate the multiplicand of the pitch
+    pub fn multiplicand(&self) -> f64 {
+        match self {
+            Pitch::Ratio(ratio) => ratio.numerator as f64 / ratio.denominator as f64,
+            Pitch::Cents(cents) => (cents / 1200.0).exp2(),
+        }
+    }
+
+    /// Get the pitch as a ratio, approximating pitches in cents
+    pub fn to_ratio(&self) -> Ratio {
+        match self {
+            Pitch::Ratio(ratio) => *ratio,
+            Pitch::Cents(_) => approximate_ratio(self.multiplicand()),
+        }
+    }
+
+    fn parse(text: &str) -> Option<Self> {
+        // anything after the pitch is a comment
+        let pitch = text.split_whitespace().next()?;
+        if pitch.contains('.') {
+            return pitch
+                .parse()
+                .ok()
+                .filter(|cents: &f64| cents.is_finite())
+                .map(Pitch::Cents);
+        }
+        let ratio = match pitch.split_once('/') {
+            Some((numerator, denominator)) => {
+                Ratio::new(numerator.parse().ok()?, denominator.parse().ok()?)
+            }
+            None => Ratio::new(pitch.parse().ok()?, 1),
+        };
+        (ratio.numerator > 0 && ratio.denominator > 0).then_some(Pitch::Ratio(ratio))
+    }
+}
+
+impl Display for Pitch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pitch::Ratio(ratio) => write!(f, "{}/{}", ratio.numerator, ratio.denominator),
+            // the period marks the value as cents
+            Pitch::Cents(cents) => write!(f, "{cents:.5}"),
+        }
+    }
+}
+
+impl Scale {
+    /// Build an octave repeating scale from ratios, which are reduced into a single octave, sorted and deduplicated
+    pub fn from_ratios(description: String, ratios: impl IntoIterator<Item = Ratio>) -> Self {
+        let mut pitches: Vec<(f64, Pitch)> = ratios
+            .into_iter()
+            .filter(|ratio| ratio.numerator > 0 && ratio.denominator > 0)
+            .map(octave_reduce)
+            .map(|pitch| (pitch.multiplicand(), pitch))
+            // the unison is implicit
+            .filter(|(multiplicand, _)| *multiplicand > 1.0)
+            .collect();
+        pitches.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+        pitches.dedup_by(|(a, _), (b, _)| a == b);
+
+        Self {
+            description,
+            pitches: pitches
+                .into_iter()
+                .map(|(_, pitch)| pitch)
+                .chain(std::iter::once(Pitch::Ratio(Ratio::new(2, 1))))
+                .collect(),
+        }
+    }
+
+    /// Parse the contents of a .scl file
+    pub fn parse(text: &str) -> Result<Self, ScalaError> {
+        let mut lines = content_lines(text);
+
+        let (_, description) = lines.next().ok_or(ScalaError::MissingLine("description"))?;
+        let (line, count) = lines.next().ok_or(ScalaError::MissingLine("note count"))?;
+        let count: usize = count
+            .split_whitespace()
+            .next()
+            .and_then(|count| count.parse().ok())
+            .ok_or(ScalaError::InvalidLine {
+                line,
+                expected: "note count",
+            })?;
+
+        let pitches = lines
+            .take(count)
+            .map(|(line, text)| {
+                Pitch::parse(text).ok_or(ScalaError::InvalidLine {
+                    line,
+                    expected: "pitch",
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if pitches.len() < count {
+            return Err(ScalaError::MissingLine("pitch"));
+        }
+        if pitches.is_empty() {
+            return Err(ScalaError::EmptyScale);
+        }
+
+        Ok(Self {
+            description: description.trim().to_owned(),
+            pitches,
+        })
+    }
+
+    /// Calculate the multiplicand of a degree relative to degree zero, repeating the scale every period
+    pub fn degree_multiplicand(&self, degree: i64) -> f64 {
+        let count = self.pitches.len() as i64;
+        let period = self.pitches[self.pitches.len() - 1].multiplicand();
+        let index = degree.rem_euclid(count) as usize;
+        let pitch = match index {
+            0 => 1.0,
+            index => self.pitches[index - 1].multiplicand(),
+        };
+        period.powi(degree.div_euclid(count) as i32) * pitch
+    }
+}
+
+impl Display for Scale {
+    /// Write the scale in the .scl format
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", self.description)?;
+        writeln!(f, " {}", self.pitches.len())?;
+        writeln!(f, "!")?;
+        for pitch in &self.pitches {
+            writeln!(f, " {pitch}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A keyboard mapping of a Scala .kbm file, which decides the reference pitch and which degrees are played
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyboardMapping {
+    pub first_note: u8,
+    pub last_note: u8,
+    /// The note degree zero is mapped to
+    pub middle_note: u8,
+    /// The note which is tuned to the reference frequency
+    pub reference_note: u8,
+    pub reference_frequency: f64,
+    /// The degree the mapping repeats at
+    pub octave_degree: i64,
+    /// The degree of every key in the repeating pattern, or None for unmapped keys.
+    /// Empty for a linear mapping where every key plays the next degree
+    pub mapping: Vec<Option<i64>>,
+}
+
+impl KeyboardMapping {
+    /// Parse the contents of a .kbm file
+    pub fn parse(text: &str) -> Result<Self, ScalaError> {
+        let mut lines = content_lines(text);
+        let mut field = |expected: &'static str| {
+            let (line, text) = lines.next().ok_or(ScalaError::MissingLine(expected))?;
+            Ok::<_, ScalaError>((line, text.split_whitespace().next().unwrap_or_default()))
+        };
+        fn parse<T: std::str::FromStr>(
+            (line, text): (usize, &str),
+            expected: &'static str,
+        ) -> Result<T, ScalaError> {
+            text.parse()
+                .map_err(|_| ScalaError::InvalidLine { line, expected })
+        }
+
+        let size: usize = parse(field("map size")?, "map size")?;
+        let first_note = parse(field("first note")?, "first note")?;
+        let last_note = parse(field("last note")?, "last note")?;
+        let middle_note = parse(field("middle note")?, "middle note")?;
+        let reference_note = parse(field("reference note")?, "reference note")?;
+        let (line, text) = field("reference frequency")?;
+        let reference_frequency: f64 = parse((line, text), "reference frequency")?;
+        if !reference_frequency.is_finite() || reference_frequency <= 0.0 {
+            return Err(ScalaError::InvalidLine {
+                line,
+                expected: "reference frequency",
+            });
+        }
+        let octave_degree = parse(field("octave degree")?, "octave degree")?;
+        let mut mapping = Vec::with_capacity(size);
+        for _ in 0..size {
+            // keys missing at the end of the file are unmapped
+            let Ok((line, text)) = field("mapping") else {
+                mapping.push(None);
+                continue;
+            };
+            mapping.push(match text {
+                "x" | "X" => None,
+                text => Some(parse((line, text), "mapped degree")?),
+            });
+        }
+
+        Ok(Self {
+            first_note,
+            last_note,
+            middle_note,
+            reference_note,
+            reference_frequency,
+            octave_degree,
+            mapping,
+        })
+    }
+
+    /// Get the degree a note plays, or None if it is unmapped or outside the range of the mapping
+    pub fn degree(&self, note: u8) -> Option<i64> {
+        if !(self.first_note..=self.last_note).contains(&note) {
+            return None;
+        }
+        let offset = note as i64 - self.middle_note as i64;
+        if self.mapping.is_empty() {
+            return Some(offset);
+        }
+        let size = self.mapping.len() as i64;
+        self.mapping[offset.rem_euclid(size) as usize]
+            .map(|degree| degree + offset.div_euclid(size) * self.octave_degree)
+    }
+}
+
+/// The frequencies of an imported scale, as a reference frequency and the ratios of the degrees to play on it
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScalaImport {
+    /// The frequency of degree zero
+    pub reference_frequency: f32,
+    pub ratios: Vec<Ratio>,
+}
+
+impl ScalaImport {
+    /// Get every degree of a scale up to and including its period, tuned by the keyboard mapping if any.
+    /// Degrees which no key of the mapping plays are left out
+    pub fn new(scale: &Scale, mapping: Option<&KeyboardMapping>) -> Result<Self, ScalaError> {
+        let reference_frequency = match mapping {
+            Some(mapping) => {
+                let degree = mapping
+                    .degree(mapping.reference_note)
+                    .ok_or(ScalaError::UnmappedReference)?;
+                mapping.reference_frequency / scale.degree_multiplicand(degree)
+            }
+            None => DEFAULT_REFERENCE_FREQUENCY,
+        };
+
+        let count = scale.pitches.len();
+        let ratios = (0..=count)
+            .filter(|degree| {
+                mapping.is_none_or(|mapping| {
+                    mapping.mapping.is_empty()
+                        || mapping.mapping.iter().flatten().any(|mapped| {
+                            mapped.rem_euclid(count as i64) as usize == degree % count
+                        })
+                })
+            })
+            .map(|degree| match degree {
+                0 => Ratio::new(1, 1),
+                degree => scale.pitches[degree - 1].to_ratio(),
+            })
+            .collect();
+
+        Ok(Self {
+            reference_frequency: reference_frequency as f32,
+            ratios,
+        })
+    }
+}
+
+/// Iterate over the lines of a Scala file which aren't comments, with their line numbers starting at 1
+fn content_lines(text: &str) -> impl Iterator<Item = (usize, &str)> {
+    text.lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line))
+        .filter(|(_, line)| !line.starts_with('!'))
+}
+
+/// Move a ratio into the octave from 1/1 up to but not including 2/1, in lowest terms.
+/// Ratios whose terms grow too large for a ratio are given in cents instead
+fn octave_reduce(ratio: Ratio) -> Pitch {
+    let (mut numerator, mut denominator) = (ratio.numerator as u64, ratio.denominator as u64);
+    while numerator >= 2 * denominator {
+        denominator *= 2;
+    }
+    while numerator < denominator {
+        numerator *= 2;
+    }
+    let divisor = gcd(numerator, denominator);
+    let (numerator, denominator) = (numerator / divisor, denominator / divisor);
+    match (u32::try_from(numerator), u32::try_from(denominator)) {
+        (Ok(numerator), Ok(denominator)) => Pitch::Ratio(Ratio::new(numerator, denominator)),
+        _ => Pitch::Cents((numerator as f64 / denominator as f64).log2() * 1200.0),
+    }
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// Find the closest ratio to a multiplicand with a denominator of at most [MAX_DENOMINATOR],
+/// using the convergents of its continued fraction
+pub fn approximate_ratio(multiplicand: f64) -> Ratio {
+    let (mut previous_numerator, mut numerator) = (0u64, 1u64);
+    let (mut previous_denominator, mut denominator) = (1u64, 0u64);
+    let mut rest = multiplicand;
+    loop {
+        let whole = rest.floor();
+        let next_numerator = whole as u64 * numerator + previous_numerator;
+        let next_denominator = whole as u64 * denominator + previous_denominator;
+        if next_denominator > MAX_DENOMINATOR as u64 || next_numerator > u32::MAX as u64 {
+            break;
+        }
+        (previous_numerator, numerator) = (numerator, next_numerator);
+        (previous_denominator, denominator) = (denominator, next_denominator);
+        let fraction = rest - whole;
+        if fraction < 1e-9 {
+            break;
+        }
+        rest = fraction.recip();
+    }
+    Ratio::new(numerator.max(1) as u32, denominator.max(1) as u32)
+}
+
+/// An error in a Scala file
+#[derive(Debug, Clone, PartialEq)]
+pub enum ScalaError {
+    /// The file ended before the expected line
+    MissingLine(&'static str),
+    /// A line couldn't be parsed as what was expected there, with the line number starting at 1
+    InvalidLine { line: usize, expected: &'static str },
+    /// The scale has no pitches, so it has no period to repeat at
+    EmptyScale,
+    /// The reference note of the keyboard mapping doesn't play a degree, so the scale can't be tuned to it
+    UnmappedReference,
+    /// Only a keyboard mapping was given, without a scale to map
+    MissingScale,
+}
+
+impl Display for ScalaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ScalaError::MissingLine(expected) => write!(f, "the file ended before the {expected}"),
+            ScalaError::InvalidLine { line, expected } => {
+                write!(f, "line {line} isn't a valid {expected}")
+            }
+            ScalaError::EmptyScale => write!(f, "the scale has no pitches"),
+            ScalaError::UnmappedReference => {
+                write!(f, "the reference note of the keyboard mapping isn't mapped")
+            }
+            ScalaError::MissingScale => write!(f, "no .scl scale file was given"),
+        }
+    }
+}
+
+impl std::error::Error for ScalaError {}
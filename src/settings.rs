@@ -0,0 +1,161 @@
+use std::{fmt::Display, io, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::midi::MidiOutputSettings;
+
+/// The bytes every versioned settings file starts with. Files without them are from before the settings were versioned
+const MAGIC: [u8; 4] = *b"HPST";
+
+/// The version of the layout settings are saved in. Bump it whenever the layout of [Settings] changes,
+/// keeping the old layout in its own module with a migration to the next version
+const SETTINGS_VERSION: u32 = 2;
+
+/// Settings of the application which aren't part of a project, persisted in the config directory of the user
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Settings {
+    /// The name of the output device, or None for the default device
+    pub output_device: Option<String>,
+    /// The sample rate of the output, or None for the default sample rate of the device
+    pub sample_rate: Option<u32>,
+    /// The wav file the null backend writes to when no device is available, if any
+    pub null_output_file: Option<PathBuf>,
+    /// How voices are sent to the MIDI output
+    pub midi_output: MidiOutputSettings,
+}
+
+impl Settings {
+    fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("harmony_playground").join("settings"))
+    }
+
+    /// Load the persisted settings, or the default settings if there are none yet
+    pub fn load() -> Result<Self, SettingsError> {
+        let Some(path) = Self::path() else {
+            return Ok(Self::default());
+        };
+        match std::fs::read(path) {
+            Ok(bytes) => Self::from_bytes(&bytes),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(error) => Err(SettingsError::IO(error.kind())),
+        }
+    }
+
+    /// Persist the settings, so they are used the next time the application starts
+    pub fn save(&self) -> io::Result<()> {
+        let path = Self::path().ok_or_else(|| io::Error::other("no config directory found"))?;
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(path, self.to_bytes().map_err(io::Error::other)?)
+    }
+
+    /// Deserialize settings from the postcard binary format, migrating older layouts
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SettingsError> {
+        let Some(rest) = bytes.strip_prefix(&MAGIC) else {
+            return Self::from_unversioned_bytes(bytes);
+        };
+        let (version, body) = rest
+            .split_first_chunk::<4>()
+            .ok_or(SettingsError::Postcard(
+                postcard::Error::DeserializeUnexpectedEnd,
+            ))?;
+        // the layouts from before the header are only stored without it
+        match u32::from_le_bytes(*version) {
+            SETTINGS_VERSION => Ok(postcard::from_bytes(body)?),
+            version => Err(SettingsError::UnsupportedVersion(version)),
+        }
+    }
+
+    /// Deserialize settings saved before they were versioned. Every layout adds fields to the end of the one
+    /// before it, so they are tried from the newest, where an older file runs out of bytes
+    fn from_unversioned_bytes(bytes: &[u8]) -> Result<Self, SettingsError> {
+        postcard::from_bytes(bytes)
+            .or_else(|_| postcard::from_bytes::<v1::Settings>(bytes).map(Self::from))
+            .or_else(|_| postcard::from_bytes::<v0::Settings>(bytes).map(Self::from))
+            .map_err(SettingsError::from)
+    }
+
+    /// Serialize the settings to the postcard binary format, behind the magic header and the current version
+    pub fn to_bytes(&self) -> Result<Vec<u8>, postcard::Error> {
+        let mut bytes = Vec::from(MAGIC);
+        bytes.extend_from_slice(&SETTINGS_VERSION.to_le_bytes());
+        postcard::to_extend(self, bytes)
+    }
+}
+
+/// The layout of settings before the null output file
+mod v0 {
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Settings {
+        pub output_device: Option<String>,
+        pub sample_rate: Option<u32>,
+    }
+}
+
+impl From<v0::Settings> for Settings {
+    fn from(settings: v0::Settings) -> Self {
+        Self {
+            output_device: settings.output_device,
+            sample_rate: settings.sample_rate,
+            ..Self::default()
+        }
+    }
+}
+
+/// The layout of settings before the MIDI output
+mod v1 {
+    use std::path::PathBuf;
+
+    use serde::Deserialize;
+
+    #[derive(Deserialize)]
+    pub struct Settings {
+        pub output_device: Option<String>,
+        pub sample_rate: Option<u32>,
+        pub null_output_file: Option<PathBuf>,
+    }
+}
+
+impl From<v1::Settings> for Settings {
+    fn from(settings: v1::Settings) -> Self {
+        Self {
+            output_device: settings.output_device,
+            sample_rate: settings.sample_rate,
+            null_output_file: settings.null_output_file,
+            midi_output: MidiOutputSettings::default(),
+        }
+    }
+}
+
+/// An error when the persisted settings can't be read
+#[derive(Debug, Clone)]
+pub enum SettingsError {
+    IO(io::ErrorKind),
+    Postcard(postcard::Error),
+    /// The settings were saved by a newer version of the application
+    UnsupportedVersion(u32),
+}
+
+impl Display for SettingsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SettingsError::IO(error_kind) => write!(f, "{error_kind}"),
+            SettingsError::Postcard(error) => write!(f, "{error}"),
+            SettingsError::UnsupportedVersion(version) => write!(
+                f,
+                "the settings are of version {version}, but only versions up to {SETTINGS_VERSION} are supported"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SettingsError {}
+
+impl From<postcard::Error> for SettingsError {
+    fn from(error: postcard::Error) -> Self {
+        Self::Postcard(error)
+    }
+}
@@ -0,0 +1,75 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+/// The duration of a new step in seconds
+pub const DEFAULT_STEP_DURATION: f32 = 1.0;
+
+/// A harmonic progression, played as a sequence of chords
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Timeline {
+    pub steps: Vec<Step>,
+    /// Whether the timeline starts over after its last step
+    pub looping: bool,
+}
+
+/// A step of a timeline, holding a snapshot of the chord played during it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Step {
+    /// The duration of the step in seconds
+    pub duration: f32,
+    /// The frequency of every global frequency during the step, by its id.
+    /// Global frequencies which aren't in the snapshot keep their own frequency
+    pub global_frequencies: BTreeMap<usize, f32>,
+    /// The ids of the relative frequencies which play during the step
+    pub active_voices: BTreeSet<usize>,
+}
+
+impl Step {
+    /// Take a snapshot of a chord, where every voice is active
+    pub fn new(global_frequencies: BTreeMap<usize, f32>, active_voices: BTreeSet<usize>) -> Self {
+        Self {
+            duration: DEFAULT_STEP_DURATION,
+            global_frequencies,
+            active_voices,
+        }
+    }
+
+    /// Get the frequency of a global frequency during the step, or None if it isn't in the snapshot
+    pub fn global_frequency(&self, id: usize) -> Option<f32> {
+        self.global_frequencies.get(&id).copied()
+    }
+}
+
+impl Timeline {
+    /// Get the duration of the whole timeline in seconds
+    pub fn duration(&self) -> f32 {
+        self.steps.iter().map(|step| step.duration).sum()
+    }
+
+    /// Replace the ids of the relative frequencies the steps refer to, leaving out the voices without a new id
+    pub fn map_voices(&self, new_id: impl Fn(usize) -> Option<usize>) -> Self {
+        Self {
+            steps: self
+                .steps
+                .iter()
+                .map(|step| Step {
+                    active_voices: step
+                        .active_voices
+                        .iter()
+                        .filter_map(|id| new_id(*id))
+                        .collect(),
+                    ..step.clone()
+                })
+                .collect(),
+            looping: self.looping,
+        }
+    }
+
+    /// Stop every step from playing a relative frequency, like when it is deleted
+    pub fn remove_voice(&mut self, id: usize) {
+        for step in &mut self.steps {
+            step.active_voices.remove(&id);
+        }
+    }
+}
@@ -0,0 +1,286 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    expression::Expression,
+    gui::{global_frequency::GlobalFrequency, relative_frequency::Ratio},
+};
+
+/// The type of a global variable, which decides where it can be referenced
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VariableType {
+    /// A frequency in Hz, which relative frequencies are played relative to
+    Frequency,
+    /// A whole number, which can be the numerator or denominator of a ratio
+    Integer,
+    /// A ratio, which can multiply a frequency
+    Ratio,
+}
+
+/// How the value of a global variable is defined
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Definition {
+    Frequency(f32),
+    /// A frequency variable multiplied by a ratio variable, with the frequency it was last evaluated to
+    Interval {
+        base: usize,
+        ratio: usize,
+        frequency: f32,
+    },
+    Integer(u32),
+    /// A ratio, whose numerator and denominator can be integer variables
+    Ratio(Ratio),
+    /// A frequency calculated from other variables, like `g1 * 3/2`
+    Expression(Expression),
+}
+
+/// The kinds of definitions, which can be picked for a global variable
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DefinitionKind {
+    Frequency,
+    Interval,
+    Integer,
+    Ratio,
+    Expression,
+}
+
+impl DefinitionKind {
+    pub const ALL: [DefinitionKind; 5] = [
+        Self::Frequency,
+        Self::Interval,
+        Self::Integer,
+        Self::Ratio,
+        Self::Expression,
+    ];
+}
+
+impl Display for DefinitionKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                DefinitionKind::Frequency => "Frequency",
+                DefinitionKind::Interval => "Interval",
+                DefinitionKind::Integer => "Integer",
+                DefinitionKind::Ratio => "Ratio",
+                DefinitionKind::Expression => "Expression",
+            }
+        )
+    }
+}
+
+impl Definition {
+    pub fn kind(&self) -> DefinitionKind {
+        match self {
+            Definition::Frequency(_) => DefinitionKind::Frequency,
+            Definition::Interval { .. } => DefinitionKind::Interval,
+            Definition::Integer(_) => DefinitionKind::Integer,
+            Definition::Ratio(_) => DefinitionKind::Ratio,
+            Definition::Expression(_) => DefinitionKind::Expression,
+        }
+    }
+
+    pub fn variable_type(&self) -> VariableType {
+        match self {
+            Definition::Frequency(_) | Definition::Interval { .. } | Definition::Expression(_) => {
+                VariableType::Frequency
+            }
+            Definition::Integer(_) => VariableType::Integer,
+            Definition::Ratio(_) => VariableType::Ratio,
+        }
+    }
+
+    /// Get the ids of the variables the definition references
+    pub fn references(&self) -> Vec<usize> {
+        match self {
+            Definition::Interval { base, ratio, .. } => vec![*base, *ratio],
+            Definition::Ratio(ratio) => ratio.references().collect(),
+            Definition::Expression(expression) => expression.references(),
+            Definition::Frequency(_) | Definition::Integer(_) => Vec::new(),
+        }
+    }
+
+    /// Evaluate the references of the definition. References to variables which don't exist or are of
+    /// the wrong type keep the value they were last evaluated to, and so do expressions with errors
+    pub fn evaluated(&self, variables: &BTreeMap<usize, GlobalFrequency>) -> Self {
+        match self {
+            Definition::Interval {
+                base,
+                ratio,
+                frequency,
+            } => {
+                let base_frequency = variables.get(base).and_then(GlobalFrequency::frequency);
+                let multiplier = variables.get(ratio).and_then(GlobalFrequency::ratio);
+                Definition::Interval {
+                    base: *base,
+                    ratio: *ratio,
+                    frequency: match (base_frequency, multiplier) {
+                        (Some(base_frequency), Some(multiplier)) => {
+                            base_frequency * multiplier.multiplicand()
+                        }
+                        _ => *frequency,
+                    },
+                }
+            }
+            Definition::Ratio(ratio) => Definition::Ratio(ratio.evaluated(variables)),
+            Definition::Expression(expression) => {
+                let mut expression = expression.clone();
+                expression.evaluate(|id| variables.get(&id)?.number());
+                Definition::Expression(expression)
+            }
+            Definition::Frequency(_) | Definition::Integer(_) => self.clone(),
+        }
+    }
+}
+
+impl Display for Definition {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Definition::Frequency(frequency) => write!(f, "{frequency} Hz"),
+            Definition::Interval {
+                base,
+                ratio,
+                frequency,
+            } => write!(f, "g{base} * g{ratio} = {frequency} Hz"),
+            Definition::Integer(integer) => write!(f, "{integer}"),
+            Definition::Ratio(ratio) => write!(f, "{}/{}", ratio.numerator, ratio.denominator),
+            Definition::Expression(expression) => match expression.value() {
+                Some(value) => write!(f, "{} = {} Hz", expression.source(), value.to_f64()),
+                None => write!(f, "{}", expression.source()),
+            },
+        }
+    }
+}
+
+/// An error in how global variables reference each other
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VariableError {
+    /// The variables reference each other in a cycle, given from a variable back to itself
+    Cycle(Vec<usize>),
+}
+
+impl Display for VariableError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VariableError::Cycle(cycle) => write!(
+                f,
+                "the variables reference each other in a cycle: {}",
+                cycle
+                    .iter()
+                    .map(|id| format!("g{id}"))
+                    .collect::<Vec<String>>()
+                    .join(" -> ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for VariableError {}
+
+/// Get the order variables are evaluated in, where every variable comes after the variables it references
+pub fn evaluation_order(
+    variables: &BTreeMap<usize, GlobalFrequency>,
+) -> Result<Vec<usize>, VariableError> {
+    let mut order = Vec::new();
+    let mut path = Vec::new();
+    for id in variables.keys() {
+        visit(*id, variables, &mut path, &mut order)?;
+    }
+    Ok(order)
+}
+
+/// Add a variable to the evaluation order after the variables it references, where the path is the
+/// variables being visited which reference it
+fn visit(
+    id: usize,
+    variables: &BTreeMap<usize, GlobalFrequency>,
+    path: &mut Vec<usize>,
+    order: &mut Vec<usize>,
+) -> Result<(), VariableError> {
+    if order.contains(&id) {
+        return Ok(());
+    }
+    if let Some(start) = path.iter().position(|visited| *visited == id) {
+        let mut cycle = path[start..].to_vec();
+        cycle.push(id);
+        return Err(VariableError::Cycle(cycle));
+    }
+    let Some(variable) = variables.get(&id) else {
+        return Ok(());
+    };
+    path.push(id);
+    for reference in variable.definition().references() {
+        visit(reference, variables, path, order)?;
+    }
+    path.pop();
+    order.push(id);
+    Ok(())
+}
+
+/// Evaluate every variable after the variables it references, leaving them unchanged if they reference each other in a cycle
+pub fn evaluate(variables: &mut BTreeMap<usize, GlobalFrequency>) -> Result<(), VariableError> {
+    for id in evaluation_order(variables)? {
+        let definition = variables[&id].definition().evaluated(variables);
+        if let Some(variable) = variables.get_mut(&id) {
+            variable.set_definition(definition);
+        }
+    }
+    Ok(())
+}
+
+/// Get the ids of the variable and every variable which references it, directly or through other variables
+pub fn dependents(variables: &BTreeMap<usize, GlobalFrequency>, id: usize) -> BTreeSet<usize> {
+    let mut dependents = BTreeSet::from([id]);
+    loop {
+        let added: Vec<usize> = variables
+            .iter()
+            .filter(|(id, variable)| {
+                !dependents.contains(id)
+                    && variable
+                        .definition()
+                        .references()
+                        .iter()
+                        .any(|reference| dependents.contains(reference))
+            })
+            .map(|(id, _)| *id)
+            .collect();
+        if added.is_empty() {
+            return dependents;
+        }
+        dependents.extend(added);
+    }
+}
+
+/// Get the variables with a frequency variable moved by the transposition, where every variable defined from it
+/// is evaluated again so it moves along. The project is left unchanged, like when a MIDI note transposes it
+pub fn transposed(
+    variables: &BTreeMap<usize, GlobalFrequency>,
+    id: usize,
+    transposition: f32,
+) -> BTreeMap<usize, GlobalFrequency> {
+    let mut transposed = variables.clone();
+    let Some(variable) = transposed.get_mut(&id) else {
+        return transposed;
+    };
+    let Some(frequency) = variable.frequency() else {
+        return transposed;
+    };
+    // the transposed variable no longer references anything, so it keeps its moved frequency
+    variable.set_definition(Definition::Frequency(frequency * transposition));
+    // only the variables defined from it change, and variables in a cycle keep the values they were last evaluated to
+    let dependents = dependents(variables, id);
+    if let Ok(order) = evaluation_order(&transposed) {
+        for id in order.into_iter().filter(|id| dependents.contains(id)) {
+            let definition = transposed[&id].definition().evaluated(&transposed);
+            if let Some(variable) = transposed.get_mut(&id) {
+                variable.set_definition(definition);
+            }
+        }
+    }
+    transposed
+}
@@ -0,0 +1,93 @@
+use harmony_playground::expression::{Expression, ExpressionError, Number, Rational};
+
+/// Evaluate the source where g1 is 3, to its value or the error it fails with
+fn evaluate(source: &str) -> Result<Number, ExpressionError> {
+    let mut expression = Expression::new(String::from(source));
+    expression.evaluate(|id| (id == 1).then_some(Number::Rational(Rational::integer(3))));
+    match expression.error() {
+        Some(error) => Err(error.clone()),
+        None => Ok(expression.value().unwrap()),
+    }
+}
+
+fn rational(numerator: i64, denominator: i64) -> Result<Number, ExpressionError> {
+    Ok(Number::Rational(
+        Rational::new(numerator, denominator).unwrap(),
+    ))
+}
+
+#[test]
+fn respects_operator_precedence() {
+    assert_eq!(evaluate("1 + 2 * 3"), rational(7, 1));
+    assert_eq!(evaluate("(1 + 2) * 3"), rational(9, 1));
+    assert_eq!(evaluate("2 * g1 ^ 2"), rational(18, 1));
+    // operators of the same precedence are left associative, except for ^
+    assert_eq!(evaluate("10 - 2 - 3"), rational(5, 1));
+    assert_eq!(evaluate("6 / 4 * 2"), rational(3, 1));
+    assert_eq!(evaluate("2 ^ 3 ^ 2"), rational(512, 1));
+    // a negation applies to the power, and may be its exponent
+    assert_eq!(evaluate("3 - -2 ^ 2"), rational(7, 1));
+    assert_eq!(evaluate("2 ^ -1"), rational(1, 2));
+}
+
+#[test]
+fn keeps_rational_arithmetic_exact() {
+    assert_eq!(evaluate("1/3 + 1/6"), rational(1, 2));
+    assert_eq!(evaluate("0.25 * g1"), rational(3, 4));
+    assert_eq!(evaluate("(9/4) ^ (1/2)"), rational(3, 2));
+    assert!(matches!(evaluate("2 ^ (7/12)"), Ok(Number::Real(_))));
+}
+
+#[test]
+fn rejects_division_by_zero() {
+    assert_eq!(evaluate("1 / 0"), Err(ExpressionError::DivisionByZero));
+    assert_eq!(
+        evaluate("1 / (g1 - 3)"),
+        Err(ExpressionError::DivisionByZero)
+    );
+    assert_eq!(evaluate("0 ^ -1"), Err(ExpressionError::DivisionByZero));
+
+    // the value of the last evaluation which succeeded is kept
+    let mut expression = Expression::new(String::from("3/2"));
+    expression.evaluate(|_| None);
+    expression.set_source(String::from("3/0"));
+    expression.evaluate(|_| None);
+    assert_eq!(expression.value(), rational(3, 2).ok());
+    assert_eq!(expression.error(), Some(&ExpressionError::DivisionByZero));
+}
+
+#[test]
+fn falls_back_to_real_numbers_on_overflow() {
+    let two_to_the_64 = Ok(Number::Real(2f64.powi(64)));
+    assert_eq!(evaluate("9223372036854775807 * 2"), two_to_the_64);
+    assert_eq!(evaluate("2 ^ 62 * 4"), two_to_the_64);
+    assert_eq!(evaluate("2 ^ 63"), Ok(Number::Real(2f64.powi(63))));
+    assert!(matches!(
+        evaluate("1/9223372036854775807 + 1/9223372036854775806"),
+        Ok(Number::Real(_))
+    ));
+    // a literal which doesn't fit is rejected rather than rounded
+    assert_eq!(
+        evaluate("1 + 99999999999999999999"),
+        Err(ExpressionError::UnexpectedCharacter('9', 4))
+    );
+
+    assert_eq!(Rational::new(i64::MIN, -1), None);
+    assert_eq!(Rational::new(1, 0), None);
+    assert_eq!(Rational::new(2, -4), Rational::new(-1, 2));
+}
+
+#[test]
+fn reports_malformed_expressions() {
+    assert_eq!(
+        evaluate("(1 + 2"),
+        Err(ExpressionError::UnclosedParenthesis(0))
+    );
+    assert_eq!(evaluate("1 +"), Err(ExpressionError::UnexpectedEnd));
+    assert_eq!(
+        evaluate("1 $ 2"),
+        Err(ExpressionError::UnexpectedCharacter('$', 2))
+    );
+    assert_eq!(evaluate("g2"), Err(ExpressionError::UnknownVariable(2)));
+    assert_eq!(evaluate("1 - g1"), Err(ExpressionError::NotPositive));
+}
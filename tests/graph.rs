@@ -0,0 +1,67 @@
+use harmony_playground::audio::{
+    engine::{AudioEngine, EngineControl, SharedFrequency, SharedVolumeMultiplier},
+    envelope::Envelope,
+    graph::{Connection, Graph, NodeKind},
+};
+
+/// Play a voice through the graph, returning the first samples of the engine
+fn play(graph: &Graph, samples: usize) -> Vec<f32> {
+    let mut engine = AudioEngine::new(48000);
+    engine.add_oscillator(
+        SharedFrequency::new(440.0),
+        SharedVolumeMultiplier::new(1.0),
+        Envelope::default(),
+        None,
+    );
+    engine.set_graph(graph).unwrap();
+    engine.play();
+    engine.take(samples).collect()
+}
+
+#[test]
+fn plays_voices_only_through_oscillator_nodes() {
+    let playing = play(&Graph::default(), 2000);
+    assert!(playing.iter().any(|sample| sample.abs() > 0.01));
+
+    let mut disconnected = Graph::default();
+    disconnected.disconnect(2, 0);
+    assert!(
+        play(&disconnected, 2000)
+            .iter()
+            .all(|sample| *sample == 0.0)
+    );
+}
+
+#[test]
+fn plays_voices_once_for_every_oscillator_node() {
+    let mut graph = Graph::default();
+    let oscillator = graph.add_node(NodeKind::Oscillator, 0.0, 0.0);
+    let mixer = graph.add_node(NodeKind::Mixer(2), 0.0, 0.0);
+    for (from, to, input) in [(1, mixer, 0), (oscillator, mixer, 1), (mixer, 2, 0)] {
+        graph.connect(Connection { from, to, input }).unwrap();
+    }
+
+    // both oscillator nodes output the same frame of the voices, rather than each playing the next one
+    let doubled: Vec<f32> = play(&Graph::default(), 2000)
+        .into_iter()
+        .map(|sample| sample * 2.0)
+        .collect();
+    assert_eq!(play(&graph, 2000), doubled);
+}
+
+#[test]
+fn keeps_voices_playing_when_the_graph_is_replaced() {
+    let mut engine = AudioEngine::new(48000);
+    engine.add_oscillator(
+        SharedFrequency::new(440.0),
+        SharedVolumeMultiplier::new(1.0),
+        Envelope::default(),
+        None,
+    );
+    engine.play();
+    engine.by_ref().take(1000).for_each(drop);
+
+    engine.set_graph(&Graph::default()).unwrap();
+    assert_eq!(engine.get_oscillators().count(), 1);
+    assert!(engine.take(2000).any(|sample| sample.abs() > 0.01));
+}
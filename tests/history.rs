@@ -0,0 +1,104 @@
+use std::time::{Duration, Instant};
+
+use harmony_playground::{
+    audio::engine::Volume,
+    history::{Edit, History, Value},
+};
+
+fn volume_edit(before: f32, after: f32) -> Edit {
+    Edit {
+        before: vec![Value::Volume(Volume::new(before))],
+        after: vec![Value::Volume(Volume::new(after))],
+    }
+}
+
+fn phase_reset_edit() -> Edit {
+    Edit {
+        before: vec![Value::PhaseReset(false)],
+        after: vec![Value::PhaseReset(true)],
+    }
+}
+
+/// Undo every edit, giving the values they revert to from the last edit to the first
+fn undo_all(history: &mut History) -> Vec<Vec<Value>> {
+    std::iter::from_fn(|| history.undo().map(|edit| edit.before)).collect()
+}
+
+#[test]
+fn merges_edits_of_the_same_target() {
+    let start = Instant::now();
+    let mut history = History::default();
+    history.push_at(volume_edit(-4.0, -3.0), start);
+    history.push_at(volume_edit(-3.0, -2.0), start + Duration::from_millis(300));
+    history.push_at(volume_edit(-2.0, -1.0), start + Duration::from_millis(600));
+
+    let edit = history.undo().unwrap();
+    assert_eq!(edit.before, [Value::Volume(Volume::new(-4.0))]);
+    assert_eq!(edit.after, [Value::Volume(Volume::new(-1.0))]);
+    assert!(!history.can_undo());
+}
+
+#[test]
+fn keeps_edits_of_other_targets_apart() {
+    let start = Instant::now();
+    let mut history = History::default();
+    history.push_at(volume_edit(-4.0, -3.0), start);
+    history.push_at(phase_reset_edit(), start + Duration::from_millis(100));
+    history.push_at(volume_edit(-3.0, -2.0), start + Duration::from_millis(200));
+
+    assert_eq!(
+        undo_all(&mut history),
+        [
+            vec![Value::Volume(Volume::new(-3.0))],
+            vec![Value::PhaseReset(false)],
+            vec![Value::Volume(Volume::new(-4.0))],
+        ]
+    );
+}
+
+#[test]
+fn keeps_edits_past_the_interval_apart() {
+    let start = Instant::now();
+    let mut history = History::default();
+    history.push_at(volume_edit(-4.0, -3.0), start);
+    history.push_at(volume_edit(-3.0, -2.0), start + Duration::from_millis(500));
+
+    assert_eq!(undo_all(&mut history).len(), 2);
+}
+
+#[test]
+fn skips_edits_without_changes() {
+    let start = Instant::now();
+    let mut history = History::default();
+    history.push_at(volume_edit(-4.0, -4.0), start);
+    assert!(!history.can_undo());
+
+    // an edit merged back to where it started changes nothing either
+    history.push_at(volume_edit(-4.0, -3.0), start);
+    history.push_at(volume_edit(-3.0, -4.0), start + Duration::from_millis(100));
+    assert!(!history.can_undo());
+}
+
+#[test]
+fn clears_redo_on_new_edits() {
+    let start = Instant::now();
+    let mut history = History::default();
+    history.push_at(volume_edit(-4.0, -3.0), start);
+    history.push_at(phase_reset_edit(), start + Duration::from_millis(100));
+
+    let undone = history.undo().unwrap();
+    assert!(history.can_redo());
+    assert_eq!(history.redo().unwrap().after, undone.after);
+    history.undo();
+
+    // an edit right after undoing isn't merged into the undone one
+    history.push_at(volume_edit(-3.0, -2.0), start + Duration::from_millis(200));
+    assert!(!history.can_redo());
+    assert_eq!(
+        undo_all(&mut history),
+        [
+            vec![Value::Volume(Volume::new(-3.0))],
+            vec![Value::Volume(Volume::new(-4.0))],
+        ]
+    );
+}
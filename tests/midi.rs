@@ -0,0 +1,77 @@
+use harmony_playground::midi::{MidiOutputPort, MidiOutputSettings, MpeZone};
+
+/// Open an output which collects its messages, leaving out the ones configuring the zone
+fn output(settings: MidiOutputSettings) -> MidiOutputPort<Vec<Vec<u8>>> {
+    let mut output = MidiOutputPort::new(Vec::new(), settings);
+    output.connection_mut().clear();
+    output
+}
+
+/// Check the status of every message sent since the last check, with the note of note messages
+/// and the 14 bit value of pitch bends, which may be off by a fraction of a cent
+fn assert_sent(output: &mut MidiOutputPort<Vec<Vec<u8>>>, expected: &[[u16; 2]]) {
+    let sent = std::mem::take(output.connection_mut());
+    assert_eq!(sent.len(), expected.len(), "{sent:?}");
+    for (message, [status, value]) in sent.iter().zip(expected) {
+        assert_eq!(message[0] as u16, *status, "{sent:?}");
+        if message[0] & 0xF0 == 0xE0 {
+            let bend = message[1] as u16 | (message[2] as u16) << 7;
+            assert!(bend.abs_diff(*value) <= 4, "{sent:?}");
+        } else {
+            assert_eq!(message[1] as u16, *value, "{sent:?}");
+        }
+    }
+}
+
+#[test]
+fn bends_to_the_ends_of_the_bend_range() {
+    let settings = MidiOutputSettings::default();
+
+    assert_eq!(settings.pitch_bend(0.0), 8192);
+    assert_eq!(settings.pitch_bend(2400.0), 12288);
+    assert_eq!(settings.pitch_bend(4800.0), 16383);
+    assert_eq!(settings.pitch_bend(-4800.0), 0);
+    // bends past the range are clamped
+    assert_eq!(settings.pitch_bend(9600.0), 16383);
+    assert_eq!(settings.pitch_bend(-9600.0), 0);
+}
+
+#[test]
+fn takes_upper_zone_member_channels_counting_down() {
+    let mut output = output(MidiOutputSettings {
+        zone: MpeZone::Upper,
+        member_channels: 3,
+        bend_range: 2,
+    });
+    for id in 0..4 {
+        output.note_on(id, 440.0, 100);
+    }
+
+    let channels: Vec<u8> = output
+        .connection_mut()
+        .iter()
+        .filter(|message| message[0] & 0xF0 == 0x90)
+        .map(|message| message[0] & 0x0F)
+        .collect();
+    // the voice past the member channels isn't sent
+    assert_eq!(channels, [14, 13, 12]);
+}
+
+#[test]
+fn retunes_by_bending_within_the_bend_range() {
+    let mut output = output(MidiOutputSettings {
+        zone: MpeZone::Lower,
+        member_channels: 15,
+        bend_range: 2,
+    });
+    output.note_on(0, 440.0, 100);
+    assert_sent(&mut output, &[[0xE1, 8192], [0x91, 69]]);
+
+    // a semitone up is half of the bend range
+    output.retune(0, 440.0 * 2f32.powf(1.0 / 12.0));
+    assert_sent(&mut output, &[[0xE1, 12288]]);
+
+    // an octave up is past the bend range, so the nearest note is played instead
+    output.retune(0, 880.0);
+    assert_sent(&mut output, &[[0x81, 69], [0xE1, 8192], [0x91, 81]]);
+}
@@ -0,0 +1,126 @@
+use std::collections::BTreeMap;
+
+use harmony_playground::mts::{MtsFrequency, MtsMessage, Tuning};
+
+/// A quarter tone above A4
+const QUARTER_TONE_A4: f32 = 452.893;
+
+#[test]
+fn converts_frequencies() {
+    assert_eq!(
+        MtsFrequency::from_frequency(440.0),
+        Some(MtsFrequency {
+            note: 69,
+            fraction: 0
+        })
+    );
+    assert_eq!(
+        MtsFrequency::from_frequency(QUARTER_TONE_A4),
+        Some(MtsFrequency {
+            note: 69,
+            fraction: 8192
+        })
+    );
+    // a fraction rounded up to a whole semitone is the next note
+    assert_eq!(
+        MtsFrequency::from_frequency(879.9999),
+        Some(MtsFrequency::equal_tempered(81))
+    );
+    assert_eq!(
+        MtsFrequency::from_frequency(QUARTER_TONE_A4)
+            .unwrap()
+            .nearest_note(),
+        70
+    );
+}
+
+#[test]
+fn rejects_frequencies_out_of_range() {
+    assert_eq!(MtsFrequency::from_frequency(4.0), None);
+    assert_eq!(MtsFrequency::from_frequency(20000.0), None);
+}
+
+#[test]
+fn tunes_first_voice_of_a_note() {
+    let tuning = Tuning::from_voices(
+        String::from("test"),
+        [(None, 440.0), (Some(69), 441.0), (Some(60), 440.0)],
+    );
+
+    assert_eq!(
+        tuning.notes,
+        BTreeMap::from([
+            (60, MtsFrequency::equal_tempered(69)),
+            (69, MtsFrequency::equal_tempered(69)),
+        ])
+    );
+}
+
+#[test]
+fn encodes_bulk_dump() {
+    let tuning = Tuning {
+        name: String::from("Just ä"),
+        notes: BTreeMap::from([(
+            60,
+            MtsFrequency {
+                note: 60,
+                fraction: 8193,
+            },
+        )]),
+    };
+    let message = tuning.to_sysex(MtsMessage::BulkDump, 130);
+
+    // header, name, three bytes for each of the 128 notes, checksum and end
+    assert_eq!(message.len(), 6 + 16 + 128 * 3 + 2);
+    assert_eq!(message[..6], [0xF0, 0x7E, 0x7F, 0x08, 0x01, 2]);
+    assert_eq!(&message[6..22], b"Just ?          ");
+    let note = |note: usize| &message[22 + note * 3..22 + note * 3 + 3];
+    assert_eq!(note(60), [60, 64, 1]);
+    // notes without a voice are kept in 12-TET
+    assert_eq!(note(61), [61, 0, 0]);
+    assert_eq!(note(127), [127, 0, 0]);
+
+    let checksum = message[1..message.len() - 2]
+        .iter()
+        .fold(0, |checksum, byte| checksum ^ byte)
+        & 0x7F;
+    assert_eq!(message[message.len() - 2..], [checksum, 0xF7]);
+    assert!(
+        message[1..message.len() - 1]
+            .iter()
+            .all(|byte| *byte < 0x80)
+    );
+}
+
+#[test]
+fn encodes_single_note_changes() {
+    let tuning = Tuning::from_voices(
+        String::new(),
+        [(Some(60), 440.0), (Some(61), QUARTER_TONE_A4)],
+    );
+    let message = tuning.to_sysex(MtsMessage::SingleNote, 1);
+
+    assert_eq!(
+        message,
+        [
+            0xF0, 0x7F, 0x7F, 0x08, 0x02, 1, 2, 60, 69, 0, 0, 61, 69, 64, 0, 0xF7
+        ]
+    );
+}
+
+#[test]
+fn splits_single_note_changes() {
+    let tuning = Tuning::from_voices(String::new(), (0..=127).map(|note| (Some(note), 440.0)));
+    let message = tuning.to_sysex(MtsMessage::SingleNote, 0);
+
+    // a message tunes at most 127 notes, so the last note gets its own message
+    let ends: Vec<usize> = message
+        .iter()
+        .enumerate()
+        .filter(|(_, byte)| **byte == 0xF7)
+        .map(|(index, _)| index)
+        .collect();
+    assert_eq!(ends, [7 + 127 * 4, 7 + 127 * 4 + 1 + 7 + 4]);
+    assert_eq!(message[6], 127);
+    assert_eq!(message[ends[0] + 7], 1);
+}
@@ -0,0 +1,370 @@
+use std::path::Path;
+
+use harmony_playground::{
+    audio::{
+        engine::Volume,
+        envelope::Envelope,
+        graph::{Connection, Filter, FilterMode, Graph, GraphError, NodeKind},
+        synthesizer::WaveForm,
+    },
+    expression::{ExpressionError, Number, Rational},
+    gui::relative_frequency::RelativeFrequencyMessage,
+    midi::MidiMapping,
+    project::{FORMAT_VERSION, LoadError, MAGIC, StateSave},
+    timeline::Timeline,
+    variables::{Definition, VariableError},
+};
+
+const V0: &[u8] = include_bytes!("fixtures/v0.harm");
+const V1: &[u8] = include_bytes!("fixtures/v1.harm");
+const V2: &[u8] = include_bytes!("fixtures/v2.harm");
+const V3: &[u8] = include_bytes!("fixtures/v3.harm");
+const V4: &[u8] = include_bytes!("fixtures/v4.harm");
+const V5: &[u8] = include_bytes!("fixtures/v5.harm");
+const V6: &[u8] = include_bytes!("fixtures/v6.harm");
+const V1_TEXT: &str = include_str!("fixtures/v1.ron");
+
+#[test]
+fn loads_v0() {
+    let save = StateSave::from_bytes(V0).unwrap();
+
+    assert_eq!(save.volume.get(), -1.5);
+    assert_eq!(save.waveform, WaveForm::Square);
+    assert!(!save.phase_reset);
+    assert_eq!(save.global_frequencies.len(), 2);
+    assert_eq!(save.global_frequencies[&1].frequency(), Some(220.0));
+    assert_eq!(save.global_frequencies[&2].id(), 2);
+    assert_eq!(save.global_frequencies[&2].frequency(), Some(330.0));
+
+    let [first, second] = save.relative_frequencies.as_slice() else {
+        panic!("expected two relative frequencies");
+    };
+    assert_eq!(first.absolute_frequency_id(), 1);
+    assert_eq!((first.ratio().numerator, first.ratio().denominator), (3, 2));
+    assert_eq!(first.volume(), -2.0);
+    assert_eq!(second.absolute_frequency_id(), 2);
+    assert_eq!(
+        (second.ratio().numerator, second.ratio().denominator),
+        (5, 4)
+    );
+    // the fields added since are migrated to their defaults
+    assert_eq!(first.envelope(), None);
+    assert_eq!(first.waveform(), None);
+    assert_eq!(first.sync_ratio(), None);
+    assert_eq!(first.pan(), 0.0);
+}
+
+#[test]
+fn loads_v1() {
+    let save = StateSave::from_bytes(V1).unwrap();
+
+    assert_eq!(save.volume.get(), -1.5);
+    assert_eq!(save.waveform, WaveForm::Saw);
+    assert!(save.phase_reset);
+    assert_eq!(save.global_frequencies[&1].frequency(), Some(110.0));
+
+    let [relative_frequency] = save.relative_frequencies.as_slice() else {
+        panic!("expected a single relative frequency");
+    };
+    assert_eq!(relative_frequency.absolute_frequency_id(), 1);
+    assert_eq!(
+        (
+            relative_frequency.ratio().numerator,
+            relative_frequency.ratio().denominator
+        ),
+        (7, 4)
+    );
+    assert_eq!(relative_frequency.volume(), -2.5);
+    assert_eq!(relative_frequency.envelope(), Some(Envelope::default()));
+    assert_eq!(relative_frequency.waveform(), Some(WaveForm::Triangle));
+    assert_eq!(relative_frequency.sync_ratio(), Some(1.75));
+    assert_eq!(relative_frequency.pan(), 0.5);
+    assert_eq!(relative_frequency.midi_note(), None);
+    assert_eq!(save.midi, MidiMapping::default());
+}
+
+#[test]
+fn loads_v2() {
+    let save = StateSave::from_bytes(V2).unwrap();
+
+    assert_eq!(save.global_frequencies[&1].frequency(), Some(110.0));
+    assert_eq!(
+        save.midi,
+        MidiMapping {
+            transposed_frequency: Some(1),
+            reference_note: 57,
+        }
+    );
+
+    let [relative_frequency] = save.relative_frequencies.as_slice() else {
+        panic!("expected a single relative frequency");
+    };
+    assert_eq!(relative_frequency.pan(), 0.5);
+    assert_eq!(relative_frequency.midi_note(), Some(64));
+    assert_eq!(save.timeline, Timeline::default());
+}
+
+#[test]
+fn loads_v3() {
+    let save = StateSave::from_bytes(V3).unwrap();
+
+    assert_eq!(save.midi.reference_note, 57);
+    assert!(save.timeline.looping);
+    let [first, second] = save.timeline.steps.as_slice() else {
+        panic!("expected two timeline steps");
+    };
+    assert_eq!(first.duration, 1.0);
+    assert_eq!(first.global_frequency(1), Some(110.0));
+    assert!(first.active_voices.contains(&0));
+    assert_eq!(second.duration, 0.5);
+    assert_eq!(second.global_frequency(1), Some(165.0));
+    assert!(second.active_voices.is_empty());
+}
+
+#[test]
+fn loads_v4() {
+    let save = StateSave::from_bytes(V4).unwrap();
+
+    assert_eq!(save.global_frequencies[&1].name(), "root");
+    assert_eq!(save.global_frequencies[&1].frequency(), Some(110.0));
+    assert_eq!(
+        save.global_frequencies[&2].definition(),
+        &Definition::Integer(5)
+    );
+    let ratio = save.global_frequencies[&3].ratio().unwrap();
+    assert_eq!(ratio.numerator_variable, Some(2));
+    assert_eq!(ratio.multiplicand(), 1.25);
+    assert_eq!(save.global_frequencies[&4].frequency(), Some(137.5));
+
+    let [first, second] = save.relative_frequencies.as_slice() else {
+        panic!("expected two relative frequencies");
+    };
+    assert_eq!(first.ratio().numerator_variable, Some(2));
+    assert_eq!(save.played_frequency(first), Some(137.5));
+    assert_eq!(second.absolute_frequency_id(), 4);
+    assert_eq!(save.played_frequency(second), Some(275.0));
+}
+
+#[test]
+fn loads_v5() {
+    let save = StateSave::from_bytes(V5).unwrap();
+
+    assert_eq!(save.global_frequencies[&5].frequency(), Some(165.0));
+    assert_eq!(
+        save.global_frequencies[&5].number(),
+        Some(Number::Rational(Rational::integer(165)))
+    );
+
+    let [first, second] = save.relative_frequencies.as_slice() else {
+        panic!("expected two relative frequencies");
+    };
+    // irrational expressions are played exactly, with an approximated ratio
+    assert_eq!(first.expression().unwrap().source(), "2^(7/12)");
+    assert_eq!(first.multiplicand(), 2f32.powf(7.0 / 12.0));
+    assert_eq!(
+        (first.ratio().numerator, first.ratio().denominator),
+        (10178, 6793)
+    );
+    // rational expressions are kept exact
+    assert_eq!(
+        (second.ratio().numerator, second.ratio().denominator),
+        (9, 4)
+    );
+    assert_eq!(save.played_frequency(second), Some(309.375));
+    // saves from before graphs play the voices as they are
+    assert_eq!(save.graph, Graph::default());
+}
+
+#[test]
+fn loads_v6() {
+    let save = StateSave::from_bytes(V6).unwrap();
+
+    assert_eq!(save.relative_frequencies.len(), 2);
+    assert_eq!(save.graph.nodes.len(), 4);
+    assert_eq!(
+        save.graph.nodes[&3].kind,
+        NodeKind::Filter(Filter {
+            mode: FilterMode::HighPass,
+            cutoff: 800.0,
+            resonance: 2.0,
+        })
+    );
+    assert_eq!(
+        save.graph.nodes[&4].kind,
+        NodeKind::Envelope(Envelope {
+            attack: 0.5,
+            decay: 0.25,
+            sustain: 0.75,
+            release: 1.0,
+        })
+    );
+    assert_eq!(
+        (save.graph.nodes[&4].x, save.graph.nodes[&4].y),
+        (150.0, 120.0)
+    );
+    // the oscillators are played through the filter and the envelope
+    let connected = |from, to| {
+        save.graph
+            .connection(to, 0)
+            .map(|connection| connection.from)
+            == Some(from)
+    };
+    assert!(connected(1, 3) && connected(3, 4) && connected(4, 2));
+}
+
+#[test]
+fn saves_current_version() {
+    let save = StateSave::from_bytes(V0).unwrap();
+    let bytes = save.to_bytes().unwrap();
+
+    assert_eq!(bytes[..4], MAGIC);
+    assert_eq!(bytes[4..8], FORMAT_VERSION.to_le_bytes());
+
+    let reloaded = StateSave::from_bytes(&bytes).unwrap();
+    assert_eq!(reloaded.volume.get(), save.volume.get());
+    assert_eq!(reloaded.global_frequencies, save.global_frequencies);
+    assert_eq!(reloaded.relative_frequencies, save.relative_frequencies);
+}
+
+#[test]
+fn rejects_newer_version() {
+    let mut bytes = Vec::from(V1);
+    bytes[4..8].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+
+    assert!(matches!(
+        StateSave::from_bytes(&bytes),
+        Err(LoadError::UnsupportedVersion(version)) if version == FORMAT_VERSION + 1
+    ));
+}
+
+#[test]
+fn loads_v1_text() {
+    let text_save = StateSave::from_file(Path::new("project.ron"), V1_TEXT.as_bytes()).unwrap();
+    let save = StateSave::from_bytes(V1).unwrap();
+
+    assert_eq!(text_save.volume.get(), save.volume.get());
+    assert_eq!(text_save.waveform, save.waveform);
+    assert_eq!(text_save.custom_harmonics, save.custom_harmonics);
+    assert_eq!(text_save.envelope, save.envelope);
+    assert_eq!(text_save.smoothing, save.smoothing);
+    assert_eq!(text_save.phase_reset, save.phase_reset);
+    assert_eq!(text_save.global_frequencies, save.global_frequencies);
+    assert_eq!(text_save.relative_frequencies, save.relative_frequencies);
+}
+
+#[test]
+fn saves_text_with_silent_volume() {
+    let mut save = StateSave::from_bytes(V1).unwrap();
+    save.volume = Volume::new(f32::NEG_INFINITY);
+
+    let reloaded = StateSave::from_text(&save.to_text().unwrap()).unwrap();
+    assert_eq!(reloaded.volume.get(), f32::NEG_INFINITY);
+    assert_eq!(reloaded.relative_frequencies, save.relative_frequencies);
+}
+
+#[test]
+fn rejects_newer_text_version() {
+    let text = V1_TEXT.replacen(
+        "format_version: 1",
+        &format!("format_version: {}", FORMAT_VERSION + 1),
+        1,
+    );
+
+    assert!(matches!(
+        StateSave::from_text(&text),
+        Err(LoadError::UnsupportedVersion(version)) if version == FORMAT_VERSION + 1
+    ));
+}
+
+#[test]
+fn rejects_text_with_variable_cycle() {
+    let mut save = StateSave::from_bytes(V4).unwrap();
+    let mut root = save.global_frequencies[&1].clone();
+    root.set_definition(Definition::Interval {
+        base: 4,
+        ratio: 3,
+        frequency: 110.0,
+    });
+    save.global_frequencies.insert(1, root);
+
+    assert!(matches!(
+        StateSave::from_text(&save.to_text().unwrap()),
+        Err(LoadError::Variable(VariableError::Cycle(cycle))) if cycle == [1, 4, 1]
+    ));
+}
+
+#[test]
+fn evaluates_expressions_of_text() {
+    let mut save = StateSave::from_bytes(V5).unwrap();
+    save.relative_frequencies[0].update(RelativeFrequencyMessage::ExpressionUpdated(String::from(
+        "g2 / 4",
+    )));
+    save.relative_frequencies[1].update(RelativeFrequencyMessage::ExpressionUpdated(String::from(
+        "3/(2",
+    )));
+
+    let reloaded = StateSave::from_text(&save.to_text().unwrap()).unwrap();
+    let [first, second] = reloaded.relative_frequencies.as_slice() else {
+        panic!("expected two relative frequencies");
+    };
+    assert_eq!((first.ratio().numerator, first.ratio().denominator), (5, 4));
+    // an expression which can't be parsed keeps its last value
+    assert_eq!(
+        second.expression().unwrap().error(),
+        Some(&ExpressionError::UnclosedParenthesis(2))
+    );
+    assert_eq!(second.multiplicand(), 2.25);
+}
+
+#[test]
+fn saves_graph() {
+    let mut save = StateSave::from_bytes(V5).unwrap();
+    let gain = save.graph.add_node(NodeKind::Gain(-6.0), 150.0, 40.0);
+    save.graph
+        .connect(Connection {
+            from: 1,
+            to: gain,
+            input: 0,
+        })
+        .unwrap();
+    save.graph
+        .connect(Connection {
+            from: gain,
+            to: 2,
+            input: 0,
+        })
+        .unwrap();
+
+    let reloaded = StateSave::from_bytes(&save.to_bytes().unwrap()).unwrap();
+    assert_eq!(reloaded.graph, save.graph);
+    let reloaded = StateSave::from_text(&save.to_text().unwrap()).unwrap();
+    assert_eq!(reloaded.graph, save.graph);
+}
+
+#[test]
+fn rejects_saves_with_graph_cycle() {
+    let mut save = StateSave::from_bytes(V5).unwrap();
+    let first = save.graph.add_node(NodeKind::Mixer(2), 150.0, 40.0);
+    let second = save.graph.add_node(NodeKind::Gain(0.0), 150.0, 120.0);
+    save.graph.connections.extend([
+        Connection {
+            from: first,
+            to: second,
+            input: 0,
+        },
+        Connection {
+            from: second,
+            to: first,
+            input: 1,
+        },
+    ]);
+
+    assert!(matches!(
+        StateSave::from_text(&save.to_text().unwrap()),
+        Err(LoadError::Graph(GraphError::Cycle(cycle))) if cycle == [first, second, first]
+    ));
+    assert!(matches!(
+        StateSave::from_bytes(&save.to_bytes().unwrap()),
+        Err(LoadError::Graph(GraphError::Cycle(cycle))) if cycle == [first, second, first]
+    ));
+}
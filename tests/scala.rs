@@ -0,0 +1,203 @@
+use harmony_playground::{
+    gui::relative_frequency::Ratio,
+    scala::{KeyboardMapping, Pitch, ScalaError, ScalaImport, Scale},
+};
+
+const SCALE: &str = "! just.scl
+!
+ Just major tetrachord with a tempered fifth
+ 4
+!
+ 9/8
+ 701.955 cents
+ 5/4 major third
+ 2
+";
+
+#[test]
+fn parses_scale() {
+    let scale = Scale::parse(SCALE).unwrap();
+
+    assert_eq!(
+        scale.description,
+        "Just major tetrachord with a tempered fifth"
+    );
+    assert_eq!(
+        scale.pitches,
+        [
+            Pitch::Ratio(Ratio::new(9, 8)),
+            Pitch::Cents(701.955),
+            Pitch::Ratio(Ratio::new(5, 4)),
+            Pitch::Ratio(Ratio::new(2, 1)),
+        ]
+    );
+}
+
+#[test]
+fn repeats_degrees_every_period() {
+    let scale = Scale::parse(SCALE).unwrap();
+
+    assert_eq!(scale.degree_multiplicand(0), 1.0);
+    assert_eq!(scale.degree_multiplicand(3), 1.25);
+    assert_eq!(scale.degree_multiplicand(5), 2.25);
+    assert_eq!(scale.degree_multiplicand(-1), 0.625);
+}
+
+#[test]
+fn rejects_malformed_pitch() {
+    let text = SCALE.replace(" 5/4 major third", " 5/x");
+
+    assert_eq!(
+        Scale::parse(&text),
+        Err(ScalaError::InvalidLine {
+            line: 8,
+            expected: "pitch"
+        })
+    );
+    // pitches of zero have no multiplicand
+    assert!(Scale::parse(&SCALE.replace(" 9/8", " 0/8")).is_err());
+    assert!(Scale::parse(&SCALE.replace(" 9/8", " inf.0")).is_err());
+}
+
+#[test]
+fn rejects_malformed_count() {
+    assert_eq!(
+        Scale::parse(&SCALE.replace(" 4\n", " four\n")),
+        Err(ScalaError::InvalidLine {
+            line: 4,
+            expected: "note count"
+        })
+    );
+    assert_eq!(
+        Scale::parse(&SCALE.replace(" 4\n", " 5\n")),
+        Err(ScalaError::MissingLine("pitch"))
+    );
+    assert_eq!(Scale::parse("empty\n 0\n"), Err(ScalaError::EmptyScale));
+    assert_eq!(
+        Scale::parse("! only a comment\n"),
+        Err(ScalaError::MissingLine("description"))
+    );
+}
+
+const MAPPING: &str = "! mapping.kbm
+! map size
+5
+! first and last note
+48
+72
+! middle note
+60
+! reference note and frequency
+62
+440.0
+! octave degree
+3
+! mapping
+0
+x
+2
+X
+";
+
+#[test]
+fn maps_keys_to_degrees() {
+    let mapping = KeyboardMapping::parse(MAPPING).unwrap();
+
+    assert_eq!(mapping.mapping, [Some(0), None, Some(2), None, None]);
+    assert_eq!(mapping.degree(60), Some(0));
+    assert_eq!(mapping.degree(62), Some(2));
+    // keys marked with x or missing at the end of the file are unmapped
+    assert_eq!(mapping.degree(61), None);
+    assert_eq!(mapping.degree(64), None);
+    // the mapping repeats every map size keys, an octave degree further
+    assert_eq!(mapping.degree(65), Some(3));
+    assert_eq!(mapping.degree(57), Some(-1));
+    assert_eq!(mapping.degree(59), None);
+    // keys outside the first and last note are unmapped
+    assert_eq!(mapping.degree(45), None);
+    assert_eq!(mapping.degree(75), None);
+}
+
+#[test]
+fn maps_keys_linearly_without_mapping() {
+    let mapping = KeyboardMapping::parse("0\n0\n127\n60\n69\n440\n12\n").unwrap();
+
+    assert_eq!(mapping.degree(60), Some(0));
+    assert_eq!(mapping.degree(48), Some(-12));
+
+    let mapping = KeyboardMapping::parse("0\n21\n108\n60\n69\n440\n12\n").unwrap();
+    assert_eq!(mapping.degree(21), Some(-39));
+    assert_eq!(mapping.degree(20), None);
+    assert_eq!(mapping.degree(109), None);
+}
+
+#[test]
+fn rejects_malformed_mapping() {
+    assert_eq!(
+        KeyboardMapping::parse(&MAPPING.replace("\nX\n", "\ny\n")),
+        Err(ScalaError::InvalidLine {
+            line: 18,
+            expected: "mapped degree"
+        })
+    );
+    assert_eq!(
+        KeyboardMapping::parse(&MAPPING.replace("440.0", "-440.0")),
+        Err(ScalaError::InvalidLine {
+            line: 11,
+            expected: "reference frequency"
+        })
+    );
+    assert_eq!(
+        KeyboardMapping::parse("5\n60\n72\n"),
+        Err(ScalaError::MissingLine("middle note"))
+    );
+}
+
+#[test]
+fn imports_mapped_degrees() {
+    let scale = Scale::parse("tetrachord\n 3\n 9/8\n 5/4\n 2/1\n").unwrap();
+    let mapping = KeyboardMapping::parse(MAPPING).unwrap();
+    let import = ScalaImport::new(&scale, Some(&mapping)).unwrap();
+
+    // the reference note plays 5/4 at 440 Hz
+    assert_eq!(import.reference_frequency, 352.0);
+    // no key plays the first degree
+    assert_eq!(
+        import.ratios,
+        [Ratio::new(1, 1), Ratio::new(5, 4), Ratio::new(2, 1)]
+    );
+
+    let unmapped_reference = MAPPING.replace("\n62\n", "\n61\n");
+    let mapping = KeyboardMapping::parse(&unmapped_reference).unwrap();
+    assert_eq!(
+        ScalaImport::new(&scale, Some(&mapping)),
+        Err(ScalaError::UnmappedReference)
+    );
+}
+
+#[test]
+fn exports_octave_reduced_ratios() {
+    let scale = Scale::from_ratios(
+        String::from("exported"),
+        [
+            Ratio::new(3, 2),
+            Ratio::new(5, 4),
+            Ratio::new(9, 4),
+            Ratio::new(1, 1),
+            Ratio::new(3, 1),
+        ],
+    );
+
+    assert_eq!(
+        scale.pitches,
+        [
+            Pitch::Ratio(Ratio::new(9, 8)),
+            Pitch::Ratio(Ratio::new(5, 4)),
+            Pitch::Ratio(Ratio::new(3, 2)),
+            Pitch::Ratio(Ratio::new(2, 1)),
+        ]
+    );
+    let text = scale.to_string();
+    assert_eq!(text, "exported\n 4\n!\n 9/8\n 5/4\n 3/2\n 2/1\n");
+    assert_eq!(Scale::parse(&text), Ok(scale));
+}
@@ -0,0 +1,72 @@
+use harmony_playground::audio::{
+    engine::{AudioEngine, EngineControl, SharedFrequency, SharedVolumeMultiplier},
+    envelope::Envelope,
+    scope::ScopeBuffer,
+};
+
+/// Fill a scope with a sawtooth of the given period in frames, starting halfway through a period
+fn sawtooth(period: usize, frames: usize) -> ScopeBuffer {
+    let scope = ScopeBuffer::default();
+    for frame in 0..frames {
+        let phase = ((frame + period / 2) % period) as f32 / period as f32;
+        scope.push(phase * 2.0 - 1.0, 0.0);
+    }
+    scope
+}
+
+#[test]
+fn snapshots_latest_frames() {
+    let scope = sawtooth(100, 1000);
+    let snapshot = scope.snapshot(300);
+
+    assert_eq!(snapshot.start, 700);
+    assert_eq!(snapshot.mixed.len(), 300);
+    assert_eq!(snapshot.voice.len(), 300);
+    // fewer frames than asked for have been written
+    assert_eq!(ScopeBuffer::default().snapshot(300).mixed.len(), 0);
+}
+
+#[test]
+fn triggers_on_whole_periods() {
+    let scope = sawtooth(100, 1037);
+    let snapshot = scope.snapshot(400);
+    let index = snapshot.trigger(Some(100.0), 200).unwrap();
+
+    assert_eq!((snapshot.start + index as u64) % 100, 0);
+    assert!(index + 200 <= snapshot.mixed.len());
+}
+
+#[test]
+fn triggers_on_zero_crossing_without_fundamental() {
+    let scope = sawtooth(100, 1037);
+    let snapshot = scope.snapshot(400);
+    let index = snapshot.trigger(None, 200).unwrap();
+
+    // the sawtooth crosses zero upwards a whole period after it started halfway through one
+    assert_eq!((snapshot.start + index as u64) % 100, 0);
+    assert!(snapshot.mixed[index - 1] < 0.0 && snapshot.mixed[index] >= 0.0);
+}
+
+#[test]
+fn shows_no_voice_while_one_releases() {
+    let mut engine = AudioEngine::new(48000);
+    let id = engine.add_oscillator(
+        SharedFrequency::new(440.0),
+        SharedVolumeMultiplier::new(1.0),
+        Envelope::default(),
+        None,
+    );
+    engine.play();
+    engine.set_scope_voice(Some(id));
+    // every frame is a left and a right sample
+    engine.by_ref().take(2 * 4800).for_each(drop);
+    assert!(engine.scope(480).voice.iter().any(|sample| *sample != 0.0));
+
+    engine.remove_oscillator(&id);
+    engine.set_scope_voice(None);
+    engine.by_ref().take(2 * 960).for_each(drop);
+    let snapshot = engine.scope(480);
+    // the release is still heard, but isn't shown as the voice
+    assert!(snapshot.mixed.iter().any(|sample| *sample != 0.0));
+    assert!(snapshot.voice.iter().all(|sample| *sample == 0.0));
+}
@@ -0,0 +1,82 @@
+use std::path::PathBuf;
+
+use harmony_playground::{
+    midi::{MidiOutputSettings, MpeZone},
+    settings::{Settings, SettingsError},
+};
+
+#[test]
+fn loads_unversioned_settings_without_null_output_file() {
+    let bytes = postcard::to_allocvec(&(Some("Speakers"), Some(44100u32))).unwrap();
+    let settings = Settings::from_bytes(&bytes).unwrap();
+
+    assert_eq!(settings.output_device.as_deref(), Some("Speakers"));
+    assert_eq!(settings.sample_rate, Some(44100));
+    assert_eq!(settings.null_output_file, None);
+    assert_eq!(settings.midi_output, MidiOutputSettings::default());
+}
+
+#[test]
+fn loads_unversioned_settings_without_midi_output() {
+    let bytes = postcard::to_allocvec(&(
+        Some("Speakers"),
+        None::<u32>,
+        Some(PathBuf::from("out.wav")),
+    ))
+    .unwrap();
+    let settings = Settings::from_bytes(&bytes).unwrap();
+
+    assert_eq!(settings.output_device.as_deref(), Some("Speakers"));
+    assert_eq!(settings.sample_rate, None);
+    assert_eq!(settings.null_output_file, Some(PathBuf::from("out.wav")));
+    assert_eq!(settings.midi_output, MidiOutputSettings::default());
+}
+
+#[test]
+fn loads_unversioned_settings_with_midi_output() {
+    let midi_output = MidiOutputSettings {
+        zone: MpeZone::Upper,
+        member_channels: 4,
+        bend_range: 2,
+    };
+    let bytes =
+        postcard::to_allocvec(&(None::<String>, Some(48000u32), None::<PathBuf>, midi_output))
+            .unwrap();
+
+    assert_eq!(
+        Settings::from_bytes(&bytes).unwrap().midi_output,
+        midi_output
+    );
+}
+
+#[test]
+fn saves_current_version() {
+    let settings = Settings {
+        output_device: Some(String::from("Speakers")),
+        sample_rate: Some(96000),
+        null_output_file: None,
+        midi_output: MidiOutputSettings::default(),
+    };
+
+    let bytes = settings.to_bytes().unwrap();
+    assert_eq!(Settings::from_bytes(&bytes).unwrap(), settings);
+}
+
+#[test]
+fn rejects_newer_version() {
+    let mut bytes = Settings::default().to_bytes().unwrap();
+    bytes[4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+
+    assert!(matches!(
+        Settings::from_bytes(&bytes),
+        Err(SettingsError::UnsupportedVersion(u32::MAX))
+    ));
+}
+
+#[test]
+fn reports_malformed_settings() {
+    assert!(matches!(
+        Settings::from_bytes(&[1, 200]),
+        Err(SettingsError::Postcard(_))
+    ));
+}
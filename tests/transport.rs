@@ -0,0 +1,53 @@
+use std::collections::BTreeSet;
+
+use harmony_playground::audio::transport::{Transport, TransportEvent, TransportStep};
+
+/// Create a transport whose steps last the frames, and collect what happens on each of its first frames
+fn events(frames: &[usize], looping: bool, count: usize) -> Vec<Option<TransportEvent>> {
+    let steps = frames
+        .iter()
+        .map(|&frames| TransportStep {
+            frames,
+            frequencies: Vec::new(),
+            oscillators: BTreeSet::new(),
+        })
+        .collect();
+    let mut transport = Transport::new(steps, looping);
+    (0..count).map(|_| transport.advance()).collect()
+}
+
+#[test]
+fn starts_steps_on_their_exact_frame() {
+    use TransportEvent::{Finished, StepStarted};
+
+    assert_eq!(
+        events(&[3, 1, 2], false, 8),
+        [
+            Some(StepStarted(0)),
+            None,
+            None,
+            Some(StepStarted(1)),
+            Some(StepStarted(2)),
+            None,
+            Some(Finished),
+            Some(Finished),
+        ]
+    );
+    // a looping timeline starts over on the frame after its last step
+    assert_eq!(
+        events(&[2, 1], true, 6),
+        [
+            Some(StepStarted(0)),
+            None,
+            Some(StepStarted(1)),
+            Some(StepStarted(0)),
+            None,
+            Some(StepStarted(1)),
+        ]
+    );
+    // steps last at least a frame
+    assert_eq!(
+        events(&[0, 0], false, 3),
+        [Some(StepStarted(0)), Some(StepStarted(1)), Some(Finished)]
+    );
+}
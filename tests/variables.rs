@@ -0,0 +1,153 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use harmony_playground::{
+    expression::{Expression, ExpressionError},
+    gui::{
+        global_frequency::GlobalFrequency,
+        relative_frequency::{Ratio, RelativeFrequency},
+    },
+    variables::{self, Definition, VariableError},
+};
+
+/// Create the variables with the given ids and definitions, without evaluating them
+fn defined(definitions: Vec<(usize, Definition)>) -> BTreeMap<usize, GlobalFrequency> {
+    definitions
+        .into_iter()
+        .map(|(id, definition)| {
+            let mut variable = GlobalFrequency::new(id, 0.0);
+            variable.set_definition(definition);
+            (id, variable)
+        })
+        .collect()
+}
+
+/// Create the variables with the given ids and definitions, evaluated
+fn evaluated(definitions: Vec<(usize, Definition)>) -> BTreeMap<usize, GlobalFrequency> {
+    let mut variables = defined(definitions);
+    variables::evaluate(&mut variables).unwrap();
+    variables
+}
+
+fn interval(base: usize, ratio: usize) -> Definition {
+    Definition::Interval {
+        base,
+        ratio,
+        frequency: 0.0,
+    }
+}
+
+/// A root with two intervals defined from it in a chain, next to an unrelated frequency
+fn chain() -> BTreeMap<usize, GlobalFrequency> {
+    evaluated(vec![
+        (1, Definition::Frequency(100.0)),
+        (2, Definition::Ratio(Ratio::new(3, 2))),
+        (3, interval(1, 2)),
+        (4, interval(3, 2)),
+        (5, Definition::Frequency(50.0)),
+    ])
+}
+
+#[test]
+fn transposes_variables_defined_from_the_transposed_one() {
+    let variables = chain();
+    let transposed = variables::transposed(&variables, 1, 2.0);
+
+    let frequencies = |variables: &BTreeMap<usize, GlobalFrequency>| {
+        [1, 3, 4, 5].map(|id| variables[&id].frequency())
+    };
+    assert_eq!(
+        frequencies(&transposed),
+        [Some(200.0), Some(300.0), Some(450.0), Some(50.0)]
+    );
+    // the project is left unchanged
+    assert_eq!(
+        frequencies(&variables),
+        [Some(100.0), Some(150.0), Some(225.0), Some(50.0)]
+    );
+}
+
+#[test]
+fn finds_dependents_through_other_variables() {
+    let variables = chain();
+
+    assert_eq!(
+        variables::dependents(&variables, 1),
+        BTreeSet::from([1, 3, 4])
+    );
+    assert_eq!(
+        variables::dependents(&variables, 2),
+        BTreeSet::from([2, 3, 4])
+    );
+    assert_eq!(variables::dependents(&variables, 4), BTreeSet::from([4]));
+    assert_eq!(variables::dependents(&variables, 5), BTreeSet::from([5]));
+}
+
+#[test]
+fn finds_dependents_referencing_later_variables() {
+    // every interval is based on one with a higher id, so each pass over them finds only one more
+    let variables = evaluated(vec![
+        (1, interval(2, 4)),
+        (2, interval(3, 4)),
+        (3, Definition::Frequency(25.0)),
+        (4, Definition::Ratio(Ratio::new(2, 1))),
+    ]);
+
+    assert_eq!(
+        variables::dependents(&variables, 3),
+        BTreeSet::from([1, 2, 3])
+    );
+    assert_eq!(
+        variables::dependents(&variables, 4),
+        BTreeSet::from([1, 2, 4])
+    );
+    assert_eq!(
+        variables::evaluation_order(&variables),
+        Ok(vec![3, 4, 2, 1])
+    );
+    assert_eq!(variables[&1].frequency(), Some(100.0));
+}
+
+#[test]
+fn detects_cycles() {
+    let mut variables = defined(vec![
+        (1, interval(2, 4)),
+        (2, interval(3, 4)),
+        (3, interval(1, 4)),
+        (4, Definition::Ratio(Ratio::new(2, 1))),
+        (5, interval(3, 4)),
+    ]);
+
+    let cycle = VariableError::Cycle(vec![1, 2, 3, 1]);
+    assert_eq!(variables::evaluation_order(&variables), Err(cycle.clone()));
+    assert_eq!(variables::evaluate(&mut variables), Err(cycle));
+    // finding the dependents of a variable in a cycle still ends
+    assert_eq!(
+        variables::dependents(&variables, 2),
+        BTreeSet::from([1, 2, 3, 5])
+    );
+}
+
+#[test]
+fn keeps_the_last_ratio_of_a_failed_expression() {
+    let variables = chain();
+    let expression = |source: &str| Some(Expression::new(String::from(source)));
+    let mut relative_frequency =
+        RelativeFrequency::new(1, Ratio::new(1, 1), 0.0).with_expression(expression("g2 * 2"));
+    relative_frequency.evaluate_ratio(&variables);
+    assert_eq!(relative_frequency.ratio(), Ratio::new(3, 1));
+
+    for (source, error) in [
+        ("g2 - 3/2", ExpressionError::NotPositive),
+        ("g2 - 2", ExpressionError::NotPositive),
+        // a ratio can't be calculated from a frequency
+        ("g1 / 100", ExpressionError::FrequencyReference(1)),
+    ] {
+        relative_frequency = relative_frequency.with_expression(expression(source));
+        relative_frequency.evaluate_ratio(&variables);
+        assert_eq!(
+            relative_frequency.expression().and_then(Expression::error),
+            Some(&error)
+        );
+        assert_eq!(relative_frequency.ratio(), Ratio::new(3, 1));
+    }
+}
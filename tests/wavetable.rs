@@ -0,0 +1,28 @@
+use harmony_playground::audio::synthesizer::{Harmonic, WAVETABLE_SIZE, WaveForm, WaveTable};
+
+/// Get the largest absolute sample of a table
+fn peak(wavetable: &WaveTable, table: usize) -> f32 {
+    (0..WAVETABLE_SIZE)
+        .map(|index| wavetable.get_index(table, index).abs())
+        .fold(0.0, f32::max)
+}
+
+#[test]
+fn normalizes_custom_harmonics() {
+    let wavetable = WaveTable::from_waveform(WaveForm::Custom(vec![Harmonic::new(1.0, 0.0); 16]));
+
+    assert!((peak(&wavetable, 0) - 1.0).abs() < 1e-6);
+    // the tables with fewer harmonics are scaled by the same amount, so they are as loud
+    let unnormalized = WaveTable::from_harmonics(&[Harmonic::new(1.0, 0.0); 16]);
+    let index = wavetable.table_index(0.4);
+    assert!(index > 0);
+    let scale = peak(&wavetable, index) / peak(&unnormalized, index);
+    assert!((scale * peak(&unnormalized, 0) - 1.0).abs() < 1e-4);
+}
+
+#[test]
+fn keeps_silent_custom_harmonics_silent() {
+    let wavetable = WaveTable::from_waveform(WaveForm::Custom(vec![Harmonic::new(0.0, 0.0); 4]));
+
+    assert_eq!(peak(&wavetable, 0), 0.0);
+}